@@ -0,0 +1,90 @@
+//! compares padding with the center copied from the tiles against reading the whole padded
+//! window through the vrt, on a 3x3 mosaic of deflate compressed tiles.
+use criterion::{criterion_group, criterion_main, Criterion};
+use gdal::{
+    programs::raster::build_vrt,
+    raster::{Buffer, RasterCreationOption},
+    Dataset, DriverManager,
+};
+use std::path::{Path, PathBuf};
+use vrt_buffer::{vrt_buffer_with_options, PadOptions};
+
+const TILE_SIZE: usize = 1024;
+const MARGIN: usize = 64;
+
+/// writes a 3x3 grid of tiles and a vrt of them to `dir`, returns the vrt path
+fn write_mosaic(dir: &Path) -> PathBuf {
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let options = [
+        RasterCreationOption {
+            key: "COMPRESS",
+            value: "DEFLATE",
+        },
+        RasterCreationOption {
+            key: "TILED",
+            value: "YES",
+        },
+    ];
+    let tiles_dir = dir.join("tiles");
+    std::fs::create_dir_all(&tiles_dir).unwrap();
+
+    let mut tiles = Vec::new();
+    for row in 0..3 {
+        for col in 0..3 {
+            let path = tiles_dir.join(format!("tile_{}_{}.tif", row, col));
+            let mut ds = driver
+                .create_with_band_type_with_options::<f32, _>(
+                    &path,
+                    TILE_SIZE as isize,
+                    TILE_SIZE as isize,
+                    1,
+                    &options,
+                )
+                .unwrap();
+            let x_origin = (col * TILE_SIZE) as f64;
+            let y_origin = -((row * TILE_SIZE) as f64);
+            ds.set_geo_transform(&[x_origin, 1.0, 0.0, y_origin, 0.0, -1.0])
+                .unwrap();
+            let data = (0..TILE_SIZE * TILE_SIZE)
+                .map(|i| ((i % TILE_SIZE) + row * col) as f32)
+                .collect();
+            let buffer = Buffer {
+                size: (TILE_SIZE, TILE_SIZE),
+                data,
+            };
+            ds.rasterband(1)
+                .unwrap()
+                .write((0, 0), (TILE_SIZE, TILE_SIZE), &buffer)
+                .unwrap();
+            drop(ds);
+            tiles.push(Dataset::open(&path).unwrap());
+        }
+    }
+
+    let vrt_path = dir.join("mosaic.vrt");
+    drop(build_vrt(Some(&vrt_path), &tiles, None).unwrap());
+    vrt_path
+}
+
+fn pad_benchmark(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let vrt_path = write_mosaic(dir.path());
+    let tiles_dir = dir.path().join("tiles");
+    let output_dir = dir.path().join("padded");
+
+    let mut group = c.benchmark_group("pad 3x3 deflate mosaic");
+    group.sample_size(10);
+    for (name, center_from_tile) in [("center from tile", true), ("center from vrt", false)] {
+        let options = PadOptions::default().center_from_tile(center_from_tile);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                vrt_buffer_with_options(&tiles_dir, &output_dir, &vrt_path, MARGIN, &options)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pad_benchmark);
+criterion_main!(benches);
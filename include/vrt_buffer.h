@@ -0,0 +1,29 @@
+/* c interface of vrt_buffer, build the crate with `--features ffi` */
+#ifndef VRT_BUFFER_H
+#define VRT_BUFFER_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define VRT_BUFFER_OK 0
+#define VRT_BUFFER_TILES_FAILED 2
+#define VRT_BUFFER_FATAL 3
+
+/* pads the geotiffs of input_dir into output_dir, vrt_file may be NULL to pad from the neighbors */
+int vrt_buffer_pad_dir(const char *input_dir, const char *output_dir, const char *vrt_file,
+                       size_t margin, size_t jobs);
+
+/* crops the buffered geotiffs of input_dir to the extent of the originals in org_dir */
+int vrt_buffer_crop_dir(const char *org_dir, const char *input_dir, const char *output_dir);
+
+/* the last fatal error of the calling thread or NULL, valid until the next call on the thread */
+const char *vrt_buffer_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,198 @@
+//! a pre-flight check of a tile set against its reference vrt.
+use crate::{
+    geometry::{
+        padded_geotransform, resampled_size, window_for_margin, world_to_pixel, GeoExtent,
+        GeoTransform, Margin, Snapping,
+    },
+    IndexedTile, TileIndex, VrtSource,
+};
+use gdal::Dataset;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// origins further than this from the vrt grid, in vrt pixels, are reported as misaligned
+const ALIGNMENT_TOLERANCE: f64 = 0.01;
+
+/// the expected output of a tile.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExpectedOutput {
+    pub input: PathBuf,
+    /// the size of the padded output as (cols, rows)
+    pub size: (usize, usize),
+    /// the uncompressed size of the padded output
+    pub bytes: u64,
+}
+
+/// a summary of a tile set and the problems a pad run would run into.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Analysis {
+    /// the number of tiles in the input directory
+    pub tiles: usize,
+    /// the distinct projections of the tiles, a usable tile set has exactly one
+    pub projections: Vec<String>,
+    /// the distinct pixel sizes of the tiles as (x, y)
+    pub resolutions: Vec<(f64, f64)>,
+    /// the pixel size of the vrt as (x, y)
+    pub vrt_resolution: (f64, f64),
+    /// tiles whose origin is not on the pixel grid of the vrt
+    pub misaligned: Vec<PathBuf>,
+    /// tiles that do not overlap the vrt and would be skipped
+    pub outside_vrt: Vec<PathBuf>,
+    /// tiles whose projection differs from the vrt
+    pub projection_mismatch: Vec<PathBuf>,
+    /// the expected outputs of the tiles inside the vrt
+    pub outputs: Vec<ExpectedOutput>,
+}
+
+impl Analysis {
+    /// the uncompressed size of every expected output
+    pub fn total_bytes(&self) -> u64 {
+        self.outputs.iter().map(|output| output.bytes).sum()
+    }
+
+    /// true when no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.projections.len() <= 1
+            && self.misaligned.is_empty()
+            && self.outside_vrt.is_empty()
+            && self.projection_mismatch.is_empty()
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tiles: {}", self.tiles)?;
+        writeln!(f, "projections: {}", self.projections.len())?;
+        for (x, y) in &self.resolutions {
+            writeln!(f, "tile resolution: {} x {}", x, y)?;
+        }
+        writeln!(
+            f,
+            "vrt resolution: {} x {}",
+            self.vrt_resolution.0, self.vrt_resolution.1
+        )?;
+        let problems = [
+            ("misaligned with the vrt", &self.misaligned),
+            ("outside of the vrt", &self.outside_vrt),
+            ("projection differs from the vrt", &self.projection_mismatch),
+        ];
+        for (problem, paths) in problems {
+            for path in paths {
+                writeln!(f, "{}: {:?}", problem, path)?;
+            }
+        }
+        writeln!(f, "outputs: {}", self.outputs.len())?;
+        write!(
+            f,
+            "estimated size: {:.1} MiB uncompressed",
+            self.total_bytes() as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+/// inspects the geotiffs of `input_dir` against the `reference` mosaic, or a vrt of the geotiffs
+/// themselves when it is `None`, without writing anything.
+/// margin: the margin the outputs are sized for
+pub fn analyze(
+    input_dir: &Path,
+    reference: Option<&Path>,
+    margin: impl Into<Margin>,
+) -> Result<Analysis, Box<dyn Error>> {
+    let index = TileIndex::load(input_dir, &[], false)?;
+    analyze_with_index(&index, reference, margin)
+}
+
+/// the same as `analyze` for the tiles of `index`, which are not opened again.
+pub fn analyze_with_index(
+    index: &TileIndex,
+    reference: Option<&Path>,
+    margin: impl Into<Margin>,
+) -> Result<Analysis, Box<dyn Error>> {
+    let margin = margin.into();
+    let tiles = index.tiles();
+    let vrt = match reference {
+        Some(reference) => VrtSource::open(reference)?,
+        None => {
+            let paths: Vec<PathBuf> = tiles.iter().map(|tile| tile.path.clone()).collect();
+            VrtSource::in_memory(&paths)?
+        }
+    };
+    let analysis = vrt.with_dataset(|vrt_ds| analyze_tiles(tiles, vrt_ds, margin));
+    if reference.is_none() {
+        vrt.remove()?;
+    }
+    analysis
+}
+
+fn analyze_tiles(
+    tiles: &[IndexedTile],
+    vrt_ds: &Dataset,
+    margin: Margin,
+) -> Result<Analysis, Box<dyn Error>> {
+    let vrt_geotransform = geo_transform(vrt_ds)?;
+    let vrt_extent = GeoExtent::from_dataset(vrt_ds)?;
+    let vrt_projection = vrt_ds.projection();
+    let mut analysis = Analysis {
+        tiles: tiles.len(),
+        vrt_resolution: (vrt_geotransform[1], vrt_geotransform[5]),
+        ..Analysis::default()
+    };
+
+    for tile in tiles {
+        let geotransform = tile.geo_transform;
+
+        if tile.projection != vrt_projection {
+            analysis.projection_mismatch.push(tile.path.clone());
+        }
+        if !analysis.projections.contains(&tile.projection) {
+            analysis.projections.push(tile.projection.clone());
+        }
+        let resolution = (geotransform[1], geotransform[5]);
+        if !analysis.resolutions.contains(&resolution) {
+            analysis.resolutions.push(resolution);
+        }
+
+        let (col, row) = world_to_pixel(&vrt_geotransform, geotransform[0], geotransform[3]);
+        if (col - col.round()).abs() > ALIGNMENT_TOLERANCE
+            || (row - row.round()).abs() > ALIGNMENT_TOLERANCE
+        {
+            analysis.misaligned.push(tile.path.clone());
+        }
+
+        if !tile.extent().intersects(&vrt_extent) {
+            analysis.outside_vrt.push(tile.path.clone());
+            continue;
+        }
+
+        let new_geotransform = padded_geotransform(&geotransform, margin);
+        let window = window_for_margin(
+            &new_geotransform,
+            tile.size,
+            margin,
+            &vrt_geotransform,
+            vrt_ds.raster_size(),
+            Snapping::default(),
+        )?;
+        let size = resampled_size(&window, &new_geotransform, &vrt_geotransform);
+        // the outputs are written as f32 unless the rasters are Float64, which the index does
+        // not record
+        let pixel_bytes = std::mem::size_of::<f32>() as u64;
+        analysis.outputs.push(ExpectedOutput {
+            input: tile.path.clone(),
+            size,
+            bytes: size.0 as u64 * size.1 as u64 * tile.bands as u64 * pixel_bytes,
+        });
+    }
+    Ok(analysis)
+}
+
+fn geo_transform(ds: &Dataset) -> Result<GeoTransform, Box<dyn Error>> {
+    match ds.geo_transform() {
+        Ok(geo_transform) => Ok(geo_transform),
+        Err(e) => Err(Box::new(e)),
+    }
+}
@@ -0,0 +1,92 @@
+//! writing the outputs of a batch into a tar archive instead of loose files.
+//!
+//! gdal writes an output to the output directory as usual, it is appended to the archive with its
+//! sidecars as soon as it is complete and then removed, so the directory only ever holds the
+//! outputs that are being written. the entries are named by their path relative to the output
+//! directory. the archive only describes the outputs of the run that wrote it, so it can not be
+//! combined with options that look at the outputs once the batch is done.
+use crate::{
+    messages::message, output::partial_path, provenance::provenance_path, sidecar::sidecar_paths,
+    OutputOptions, TileReport, TileStatus,
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tar::Builder;
+
+/// a tar archive the outputs of a batch are appended to.
+pub(crate) struct TarSink {
+    path: PathBuf,
+    builder: Mutex<Builder<File>>,
+}
+
+impl TarSink {
+    /// starts the archive of `output_options`, `None` when the outputs stay loose files
+    pub(crate) fn create(output_options: &OutputOptions) -> Result<Option<Self>, Box<dyn Error>> {
+        let path = match &output_options.archive {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        if output_options.resume || output_options.stac || output_options.manifest {
+            return Err(
+                "an archive can not be combined with resuming, STAC items or a manifest".into(),
+            );
+        }
+        // the archive is renamed into place once it is complete like the outputs
+        let file = File::create(partial_path(path))?;
+        Ok(Some(TarSink {
+            path: path.clone(),
+            builder: Mutex::new(Builder::new(file)),
+        }))
+    }
+
+    /// moves the output of a written tile and its sidecars into the archive. a tile whose output
+    /// can not be archived fails
+    pub(crate) fn archive(&self, output_dir: &Path, report: &mut TileReport) {
+        if report.status != TileStatus::Written {
+            return;
+        }
+        if let Err(e) = self.append(output_dir, &report.output) {
+            message(&format!("Could not archive {:?}: {}", report.output, e));
+            report.status = TileStatus::Failed(format!("could not archive the output: {}", e));
+        }
+    }
+
+    fn append(&self, output_dir: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+        let mut files = vec![output.to_path_buf()];
+        files.extend(
+            sidecar_paths(output)
+                .into_iter()
+                .chain([provenance_path(output)])
+                .filter(|path| path.is_file()),
+        );
+        {
+            let mut builder = match self.builder.lock() {
+                Ok(builder) => builder,
+                Err(_) => return Err("a thread panicked while writing the archive".into()),
+            };
+            for file in &files {
+                let name = file.strip_prefix(output_dir).unwrap_or(file);
+                builder.append_path_with_name(file, name)?;
+            }
+        }
+        for file in &files {
+            fs::remove_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// writes the end of the archive and moves it to its final name
+    pub(crate) fn finish(self) -> Result<PathBuf, Box<dyn Error>> {
+        let builder = match self.builder.into_inner() {
+            Ok(builder) => builder,
+            Err(_) => return Err("a thread panicked while writing the archive".into()),
+        };
+        builder.into_inner()?.sync_all()?;
+        fs::rename(partial_path(&self.path), &self.path)?;
+        Ok(self.path)
+    }
+}
@@ -1,53 +1,722 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use clap::{Args, Subcommand};
+use clap_complete::Shell;
+use vrt_buffer::{
+    geometry::{GeoExtent, Snapping},
+    BandKind, BandPolicy, BigTiff, Calc, CompressionThreads, DiskSpaceCheck, DuplicateNames,
+    EdgeFill, Margin, MarginPolicy, OutOfRange, OutputFormat, OutputType, Resampling, Rounding,
+    ShardTemplate, StatisticsMode, TileIdPattern, TileOrder,
+};
+
+/// the examples at the end of `pad --help`
+const PAD_EXAMPLES: &str = "Examples:
+  pad every raster of tiles by 64 pixels from a vrt of all of them:
+    vrt_buffer pad -i tiles -r mosaic.vrt -o padded -p 64
+
+  pad 8 rasters at a time into cogs, filling the margin beyond the mosaic with nodata:
+    vrt_buffer pad -i tiles -r mosaic.vrt -o padded -p 64 -j 8 --format cog --fill nodata
+
+  pad pixels that are twice as tall as wide to the same distance on both axes:
+    vrt_buffer pad -i tiles -r mosaic.vrt -o padded --pad-x 64 --pad-y 32
+
+  split a single large raster into overlapping chunks of 1024 pixels:
+    vrt_buffer pad -r mosaic.tif -o chunks --tile-size 1024 -p 64";
+
+/// the examples at the end of `crop --help`
+const CROP_EXAMPLES: &str = "Examples:
+  crop the processed rasters back to the extents of their originals:
+    vrt_buffer crop -g tiles -i processed -o cropped
+
+  trim a known margin off when the originals are gone:
+    vrt_buffer crop -i processed -o cropped --pixels 64
+
+  replace the originals with the cropped rasters and keep them as .bak:
+    vrt_buffer crop -g tiles -i processed --in-place --backup
+
+  crop very large rasters a strip of 512 rows at a time into cogs:
+    vrt_buffer crop -g tiles -i processed -o cropped --chunk-rows 512 --format cog";
+
+/// the examples at the end of `round-trip --help`
+const ROUND_TRIP_EXAMPLES: &str = "Examples:
+  pad the tiles, run a script on every padded raster and crop its results:
+    vrt_buffer round-trip -i tiles -r mosaic.vrt -o results -p 64 \\
+      --exec 'process.sh \"{input}\" \"{output}\"'
+
+  run 4 commands at a time and keep the padded and processed rasters to inspect them:
+    vrt_buffer round-trip -i tiles -o results -p 64 -j 4 --keep-temp \\
+      --exec 'gdal_calc.py -A \"{input}\" --outfile \"{output}\" --calc \"A*2\"'";
 
 #[derive(Debug, clap::Parser)]
-#[clap(author = "Bart Carlson", version = "1.0")]
+#[clap(author = "Bart Carlson", version = "1.0", args_override_self = true)]
 pub struct BufferCliArgs {
     #[clap(subcommand)]
     pub subcmd: Subaction,
+
+    /// read the subcommand and its options from a toml file, e.g. command = "pad", input = "tiles",
+    /// pad = 64. options given on the command line override the file
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// print the batch report as json to stdout.
+    /// exits with 0 when every raster succeeded, 2 when some failed and 3 on a fatal error
+    #[clap(long, global = true)]
+    pub json: bool,
+
+    /// print the path of every output of a pad or crop run followed by a NUL instead of the
+    /// report, for xargs -0 and parallel -0
+    #[clap(long, global = true, conflicts_with = "json")]
+    pub print0: bool,
+
+    /// do not print warnings and per raster messages, fatal errors are still printed
+    #[clap(long, global = true, conflicts_with = "log_file")]
+    pub quiet: bool,
+
+    /// append the warnings and per raster messages to this file instead of printing them
+    #[clap(long, global = true)]
+    pub log_file: Option<PathBuf>,
 }
 #[derive(Debug, Subcommand)]
 pub enum Subaction {
     /// pads the raster file with a border of additional pixels who are sourced from the adjacent
     /// rasters using a vrt file
-    Pad(PadCommand),
+    #[clap(after_long_help = PAD_EXAMPLES)]
+    Pad(Box<PadCommand>),
 
     /// crops the processed raster to the extent of the original raster
+    #[clap(after_long_help = CROP_EXAMPLES)]
     Crop(CropCommand),
+
+    /// summarizes the rasters of a directory against the vrt without writing anything: the
+    /// projections, resolutions, misaligned rasters, rasters outside of the vrt and output sizes
+    Info(InfoCommand),
+
+    /// prints the extents the rasters will have after padding as a geojson feature collection,
+    /// without reading any pixels
+    Extent(ExtentCommand),
+
+    /// pads the rasters into a temp directory, runs a command on them and crops its results back
+    /// to the extents of the original rasters
+    #[clap(after_long_help = ROUND_TRIP_EXAMPLES)]
+    RoundTrip(RoundTripCommand),
+
+    /// checks the rasters of a directory against the sha256 in its manifest.sha256, exits with 2
+    /// when a raster changed or is missing
+    Verify(VerifyCommand),
+
+    /// merges the rasters of a directory, e.g. the output of a crop, into a single raster once
+    /// they are checked to be on one grid without gaps or overlaps
+    Mosaic(MosaicCommand),
+
+    /// compares the rasters of two directories pixel by pixel, e.g. the cropped outputs with
+    /// their originals or the outputs of two runs
+    Diff(DiffCommand),
+
+    /// prints the shell completions of vrt_buffer, e.g. vrt_buffer completions bash >
+    /// /etc/bash_completion.d/vrt_buffer
+    Completions(CompletionsCommand),
 }
 
 #[derive(Debug, Args)]
 pub struct PadCommand {
     /// the input raster directory
-    #[clap(short, long)]
-    pub input: PathBuf,
+    #[clap(short, long, required_unless_present = "tile_size")]
+    pub input: Option<PathBuf>,
 
     /// the output raster directory
     #[clap(short, long)]
     pub output: PathBuf,
 
-    /// the vrt file that describes the subject area including the adjacent rasters
-    #[clap(short, long)]
-    pub vrt: PathBuf,
+    /// split --reference into tiles of this many pixels and pad those instead of the rasters of
+    /// an input directory, only the tiles that intersect --aoi when it is set
+    #[clap(long, requires = "reference", conflicts_with_all = ["input", "levels"])]
+    pub tile_size: Option<usize>,
 
-    /// the number of pixels to pad the raster with
-    #[clap(short, long)]
-    pub pad: u32,
+    /// the mosaic that describes the subject area including the adjacent rasters, a vrt or any
+    /// raster gdal can read, e.g. a large cog or a tile index.
+    /// when omitted the margin is read from the neighboring rasters in the input directory
+    #[clap(short, long, visible_alias = "vrt", short_alias = 'v')]
+    pub reference: Option<PathBuf>,
+
+    /// the number of pixels to pad the raster with, 0 rewrites the rasters with the output options
+    #[clap(
+        short,
+        long,
+        required_unless_present = "pad_x",
+        conflicts_with_all = ["pad_x", "pad_y"]
+    )]
+    pub pad: Option<u32>,
+
+    /// the number of pixels to pad the left and right of the raster with, for rasters whose
+    /// pixels are not square. requires --pad-y
+    #[clap(long, requires = "pad_y")]
+    pub pad_x: Option<u32>,
+
+    /// the number of pixels to pad the top and bottom of the raster with. requires --pad-x
+    #[clap(long, requires = "pad_x")]
+    pub pad_y: Option<u32>,
+
+    /// a csv of name,margin or name,x,y rows or a json object of names to margins that pad the
+    /// named rasters by their own margin instead of --pad, e.g. coastal rasters that need more
+    #[clap(long)]
+    pub margin_file: Option<PathBuf>,
+
+    /// the number of rasters to pad at the same time
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// the number of rasters that read from the vrt at the same time, lower than --jobs for
+    /// storage that slows down with concurrent reads. defaults to --jobs
+    #[clap(long)]
+    pub threads_io: Option<usize>,
+
+    /// pad a band from its own vrt instead of --vrt, as BAND=VRT, e.g. 2=intensity.vrt.
+    /// can be repeated
+    #[clap(long = "band-vrt", value_parser = parse_band_vrt)]
+    pub band_vrts: Vec<(isize, PathBuf)>,
+
+    /// pad a band of the rasters from another band of --reference, as BAND:VRT_BAND, e.g. 1:3
+    /// pads band 1 of single band rasters from band 3 of a 4 band mosaic. can be repeated
+    #[clap(long = "band-map", value_parser = parse_band_map)]
+    pub band_map: Vec<(isize, isize)>,
+
+    /// fill the margin pixels that are nodata in --reference, e.g. where neighbor rasters are
+    /// missing, from this mosaic, such as a coarser national dem
+    #[clap(long, requires = "reference")]
+    pub fallback_vrt: Option<PathBuf>,
+
+    /// copy rasters that are outside of the vrt to the output unpadded instead of skipping them
+    #[clap(long)]
+    pub pass_through_outside: bool,
+
+    /// write a vrt stub per output with the size and georeferencing of the padded raster but no
+    /// pixels, to check the footprints of a run in a gis before the real run
+    #[clap(long)]
+    pub metadata_only: bool,
+
+    /// how raster origins are snapped to the vrt grid: floor, round or error:<tolerance in pixels>
+    #[clap(long, default_value = "round")]
+    pub snap: Snapping,
+
+    /// read the whole padded window from the vrt instead of copying the center from the raster,
+    /// for vrts whose pixels differ from the rasters, e.g. with overlapping rasters
+    #[clap(long)]
+    pub center_from_vrt: bool,
+
+    /// skip rasters that contain nothing but nodata
+    #[clap(long)]
+    pub skip_empty: bool,
+
+    /// how the vrt is resampled for rasters with a different resolution: nearest, bilinear, cubic
+    /// or average. rasters coarser than the vrt, e.g. 10 m tiles of a 1 m vrt, are decimated
+    #[clap(long, default_value = "nearest")]
+    pub margin_resampling: Resampling,
+
+    /// the kind of a band as BAND=KIND, auto, continuous or categorical, e.g. 1=categorical.
+    /// categorical bands are always resampled with nearest, auto treats Byte bands with a color
+    /// table as categorical. can be repeated
+    #[clap(long = "band-kind", value_parser = parse_band_kind)]
+    pub band_kinds: Vec<(isize, BandKind)>,
+
+    /// what happens to rasters whose margin extends beyond the edge of the vrt: clamp stops the
+    /// margin at the edge and reports the raster as clamped, error fails the raster, fill or
+    /// fill:VALUES fills the part beyond the edge like --fill
+    #[clap(long, default_value = "clamp")]
+    pub margin_policy: MarginPolicy,
+
+    /// pad every raster to the full margin, filling the part beyond the edge of the vrt with the
+    /// nodata value of each band (nodata) or a value per band, e.g. 0,0,255.
+    /// the same as --margin-policy fill:VALUES
+    #[clap(long, conflicts_with = "margin_policy")]
+    pub fill: Option<EdgeFill>,
+
+    /// the order the rasters are padded in: name, or hilbert to pad neighboring rasters close
+    /// together, which keeps the shared parts of the vrt in the gdal cache
+    #[clap(long, default_value = "name")]
+    pub order: TileOrder,
+
+    /// apply an expression to every pixel while padding, with A as the pixel value, e.g.
+    /// "A * 0.1 - 100". nodata pixels are kept and the outputs are f32, or f64 for f64 inputs
+    #[clap(long)]
+    pub calc: Option<Calc>,
+
+    /// only pad the rasters that intersect this bbox, min_x,min_y,max_x,max_y in the crs of the
+    /// rasters. without --reference only the rasters around them are read
+    #[clap(long, allow_hyphen_values = true)]
+    pub aoi: Option<GeoExtent>,
+
+    /// keep an index of the raster grids in the input directory, so later runs do not open every
+    /// raster again to filter or order them
+    #[clap(long)]
+    pub index: bool,
+
+    /// write a vrt mosaic of the padded rasters to this path and print it
+    #[clap(long)]
+    pub mosaic_vrt: Option<PathBuf>,
+
+    /// also pad the rasters decimated by these comma separated factors, e.g. 1,2,4, each level
+    /// into a level_<factor> subdirectory of the output. needs --reference
+    #[clap(long, value_delimiter = ',')]
+    pub levels: Vec<usize>,
+
+    #[clap(flatten)]
+    pub output_args: OutputArgs,
+
+    #[clap(flatten)]
+    pub gdal_args: GdalArgs,
+}
+
+impl PadCommand {
+    /// the margin from --pad or --pad-x and --pad-y
+    pub fn margin(&self) -> Margin {
+        match (self.pad, self.pad_x, self.pad_y) {
+            (Some(pad), _, _) => Margin::Uniform(pad as usize),
+            (None, x, y) => Margin::PerAxis {
+                x: x.unwrap_or_default() as usize,
+                y: y.unwrap_or_default() as usize,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Args)]
 pub struct CropCommand {
     /// the original raster directory used for knowing the extent to crop to
-    #[clap(short = 'g', long = "original")]
-    pub original: PathBuf,
+    #[clap(short = 'g', long = "original", required_unless_present = "pixels")]
+    pub original: Option<PathBuf>,
+
+    /// trim this many pixels off each side of the buffered rasters instead of cropping them to
+    /// the originals, for when the margin is known and the originals are gone
+    #[clap(long, conflicts_with_all = ["original", "in_place"])]
+    pub pixels: Option<u32>,
 
     /// the input raster directory
     #[clap(short = 'i', long = "input")]
     pub input: PathBuf,
 
     /// the output raster directory
-    #[clap(short = 'o', long = "output")]
+    #[clap(short = 'o', long = "output", required_unless_present = "in_place")]
+    pub output: Option<PathBuf>,
+
+    /// replace the original rasters with the cropped rasters instead of writing them to --output.
+    /// each original is only replaced once its cropped raster is complete
+    #[clap(long, conflicts_with_all = ["output", "shard"])]
+    pub in_place: bool,
+
+    /// keep each raster that a cropped raster replaces as <name>.bak
+    #[clap(long)]
+    pub backup: bool,
+
+    /// how the original extents are snapped to the buffered grid: floor, round or error:<tolerance>
+    #[clap(long, default_value = "round")]
+    pub snap: Snapping,
+
+    /// delete each buffered raster once its cropped output has been written and checked
+    #[clap(long)]
+    pub cleanup: bool,
+
+    /// copy the pixels this many rows at a time and stage cog outputs on disk, so cropping very
+    /// large rasters needs memory for a strip of rows instead of the whole raster
+    #[clap(long)]
+    pub chunk_rows: Option<usize>,
+
+    /// fill the rows and columns of the original extent that a buffered raster does not cover,
+    /// e.g. one warped by another tool, with nodata instead of failing the raster
+    #[clap(long)]
+    pub fill_shortfall: bool,
+
+    /// the number of rasters to crop at the same time
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    #[clap(flatten)]
+    pub output_args: OutputArgs,
+
+    #[clap(flatten)]
+    pub gdal_args: GdalArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoCommand {
+    /// the input raster directory
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// the mosaic the rasters would be padded from, when omitted a vrt of the input rasters
+    #[clap(short, long, visible_alias = "vrt", short_alias = 'v')]
+    pub reference: Option<PathBuf>,
+
+    /// the number of pixels the outputs are sized for
+    #[clap(short, long, default_value_t = 0)]
+    pub pad: u32,
+
+    /// read the raster grids from the index in the input directory and update it, see pad --index
+    #[clap(long)]
+    pub index: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RoundTripCommand {
+    /// the input raster directory
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// the directory the cropped results are written to
+    #[clap(short, long)]
+    pub output: PathBuf,
+
+    /// the mosaic the margin is read from, the neighboring rasters in the input directory when
+    /// omitted
+    #[clap(short, long, visible_alias = "vrt", short_alias = 'v')]
+    pub reference: Option<PathBuf>,
+
+    /// the number of pixels to pad the rasters with
+    #[clap(short, long)]
+    pub pad: u32,
+
+    /// the command that processes the padded rasters, run once through the shell. {input} is
+    /// replaced by the directory of the padded rasters and {output} by the directory the command
+    /// must write its results to, with the names of the padded rasters
+    #[clap(long)]
+    pub exec: String,
+
+    /// the number of rasters to pad and crop at the same time
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// the directory the temp directory of the run is created in, the system temp directory
+    /// when omitted
+    #[clap(long)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// keep the padded and processed rasters and print where they are
+    #[clap(long)]
+    pub keep_temp: bool,
+
+    /// start without checking that the temp directory has room for the intermediates
+    #[clap(long)]
+    pub skip_disk_check: bool,
+
+    #[clap(flatten)]
+    pub output_args: OutputArgs,
+
+    #[clap(flatten)]
+    pub gdal_args: GdalArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyCommand {
+    /// the directory with the manifest.sha256, e.g. the output directory of a pad run
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// the number of rasters to hash at the same time
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct MosaicCommand {
+    /// the directory of the rasters to merge
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// the raster the mosaic is written to
+    #[clap(short, long)]
     pub output: PathBuf,
+
+    /// the format of the mosaic: cog, gtiff or vrt
+    #[clap(long, default_value = "cog")]
+    pub format: OutputFormat,
+
+    /// write bigtiffs: auto, yes, no or if_safer
+    #[clap(long, default_value = "auto")]
+    pub bigtiff: BigTiff,
+
+    /// write the mosaic even when the rasters leave gaps, which become nodata
+    #[clap(long)]
+    pub allow_gaps: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsCommand {
+    /// the shell to print the completions for: bash, zsh, fish, powershell or elvish
+    #[clap(value_enum)]
+    pub shell: Shell,
+}
+
+#[derive(Debug, Args)]
+pub struct DiffCommand {
+    /// the first raster directory, e.g. the originals
+    #[clap(short = 'a', long)]
+    pub first: PathBuf,
+
+    /// the second raster directory, its rasters are paired with the first by file name
+    #[clap(short = 'b', long)]
+    pub second: PathBuf,
+
+    /// the largest absolute difference of a pixel that is not reported
+    #[clap(short, long, default_value_t = 0.0)]
+    pub tolerance: f64,
+
+    /// the number of pairs of rasters to compare at the same time
+    #[clap(short, long, default_value_t = 1)]
+    pub jobs: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct ExtentCommand {
+    /// a raster or a directory of rasters
+    #[clap(short, long)]
+    pub input: PathBuf,
+
+    /// the number of pixels the rasters are padded with
+    #[clap(short, long)]
+    pub pad: u32,
+
+    /// print one `path<TAB>wkt polygon` line per raster instead of geojson
+    #[clap(long)]
+    pub wkt: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// comma separated overview levels to build on each output, e.g. 2,4,8,16
+    #[clap(long, value_delimiter = ',')]
+    pub overviews: Vec<i32>,
+
+    /// the resampling method used to build the overviews
+    #[clap(long, default_value = "average")]
+    pub resampling: String,
+
+    /// copy .prj and .aux.xml sidecars of the inputs and regenerate their world files
+    #[clap(long)]
+    pub sidecars: bool,
+
+    /// what happens when the output file system may not have room for the outputs: error fails
+    /// before the first raster, warn runs anyway, off skips the check
+    #[clap(long, default_value = "error")]
+    pub disk_check: DiskSpaceCheck,
+
+    /// compute band statistics on each output, either approx or exact
+    #[clap(long)]
+    pub stats: Option<StatisticsMode>,
+
+    /// write bigtiffs: auto, yes, no or if_safer. auto switches for outputs close to 4 GiB
+    #[clap(long, default_value = "auto")]
+    pub bigtiff: BigTiff,
+
+    /// the threads gdal uses to compress each tiff output, independent of --jobs: a number,
+    /// all_cpus, or auto for GDAL_NUM_THREADS when it is set and all cores otherwise
+    #[clap(long, default_value = "auto")]
+    pub threads_compute: CompressionThreads,
+
+    /// the output format: gtiff, cog, vrt, hfa (erdas imagine .img), gpkg, virtual, a vrt per
+    /// padded raster that crops the reference without copying pixels, or zarr, a store per output
+    #[clap(long, default_value = "gtiff")]
+    pub format: OutputFormat,
+
+    /// skip rasters whose inputs and options did not change since the last run with --resume,
+    /// tracked in a state file in the output directory
+    #[clap(long)]
+    pub resume: bool,
+
+    /// only process this many rasters in processing order, rasters skipped by --resume are not
+    /// counted so the next run with --resume continues with the following ones
+    #[clap(long)]
+    pub max_tiles: Option<usize>,
+
+    /// do not start rasters once the run has taken this long, e.g. 90s, 30m or 2h
+    #[clap(long, value_parser = parse_duration)]
+    pub max_duration: Option<Duration>,
+
+    /// fail a raster that runs longer than this, e.g. 10m, and go on with the next one, for
+    /// corrupt rasters whose reads do not end. a single gdal read can not be interrupted
+    #[clap(long, value_parser = parse_duration)]
+    pub tile_timeout: Option<Duration>,
+
+    /// show the number of rasters that are done, failed and running on stderr
+    #[clap(long)]
+    pub progress: bool,
+
+    /// only process the rasters of --input in this file, one path per line or separated by NUL
+    /// as written by find -print0. - reads the list from stdin
+    #[clap(long)]
+    pub file_list: Option<PathBuf>,
+
+    /// stop at the first raster that fails instead of going on, e.g. for validation runs in ci
+    #[clap(long, conflicts_with = "max_failures")]
+    pub fail_fast: bool,
+
+    /// stop once this many rasters failed instead of going on
+    #[clap(long)]
+    pub max_failures: Option<usize>,
+
+    /// write each output to a subdirectory built from a template: {stem}, {stem:START-END} or
+    /// the grid cell {x:SIZE} and {y:SIZE} of the raster center, e.g. {x:100000}/{y:100000}
+    #[clap(long)]
+    pub shard: Option<ShardTemplate>,
+
+    /// name each output by the tile id in the name of its input, a template such as S2_{id}_*
+    /// or a regex whose group named id, or first group, is the id. crop pairs the rasters with
+    /// the originals by the same id. rasters with the same output fail instead of overwriting it
+    #[clap(long)]
+    pub tile_id: Option<TileIdPattern>,
+
+    /// what happens to rasters of the same name, e.g. tables of the same name in two geopackages:
+    /// error fails all but the first, prefix-parent prefixes the outputs with the name of their
+    /// geopackage or directory and mirror writes them to a subdirectory of that name
+    #[clap(long, default_value = "error")]
+    pub duplicate_names: DuplicateNames,
+
+    /// write a STAC item next to each output and a catalog.json of them to the output directory
+    #[clap(long)]
+    pub stac: bool,
+
+    /// write a <name>.provenance.json next to each output with the inputs and their sha256, the
+    /// vrt, the margin, the gdal and crate versions, the command line and the timings
+    #[clap(long)]
+    pub provenance: bool,
+
+    /// write the input, output, error and extent of each failed raster as a json line to
+    /// errors.jsonl in the output directory
+    #[clap(long)]
+    pub error_log: bool,
+
+    /// write the sha256 of each output to manifest.sha256 in the output directory, see verify
+    #[clap(long)]
+    pub manifest: bool,
+
+    /// append each output and its sidecars to this tar archive as soon as it is written, instead
+    /// of keeping it in the output directory
+    #[clap(long)]
+    pub archive: Option<PathBuf>,
+
+    /// allow an output directory that is the input or original directory or inside of it, where
+    /// the outputs replace the rasters of the same name
+    #[clap(long)]
+    pub allow_overlapping_dirs: bool,
+
+    /// copy the files of the input directory that are not rasters or their sidecars, e.g. xml
+    /// metadata, las indices and reports, to the output directory
+    #[clap(long)]
+    pub copy_non_raster_files: bool,
+
+    /// convert the outputs to this data type: Byte, UInt16, Int16, UInt32, Int32, Float32 or
+    /// Float64. nodata pixels become the nodata value of the output
+    #[clap(long = "ot")]
+    pub output_type: Option<OutputType>,
+
+    /// how values are rounded to an integer --ot: nearest, floor or truncate
+    #[clap(long, default_value = "nearest", requires = "output_type")]
+    pub rounding: Rounding,
+
+    /// what happens to values --ot can not hold: clamp to its range or nodata
+    #[clap(long, default_value = "clamp", requires = "output_type")]
+    pub out_of_range: OutOfRange,
+
+    /// the nodata value of the outputs when --ot can not hold the nodata value of the source,
+    /// the largest value of unsigned and the smallest of signed types by default
+    #[clap(long, requires = "output_type", allow_hyphen_values = true)]
+    pub ot_nodata: Option<f64>,
+
+    /// comma separated bands to write, in this order, e.g. 1,3. all bands by default
+    #[clap(long, value_delimiter = ',')]
+    pub bands: Vec<isize>,
+
+    /// the nodata value and fill of a band as BAND:POLICY, e.g. 7:nodata=255,fill=255 for a qa
+    /// band. the nodata pixels of the source become the new nodata value, fill=nodata or a value
+    /// fills the margin beyond the edge of the vrt and the shortfall of a crop. can be repeated
+    #[clap(long = "band-policy", value_parser = parse_band_policy)]
+    pub band_policies: Vec<(isize, BandPolicy)>,
+}
+
+#[derive(Debug, Args)]
+pub struct GdalArgs {
+    /// set a gdal configuration option as KEY=VALUE, e.g. GDAL_CACHEMAX=2048. can be repeated
+    #[clap(long = "gdal-config", value_parser = parse_key_value)]
+    pub gdal_config: Vec<(String, String)>,
+
+    /// pass an open option to the input rasters as KEY=VALUE, e.g. NUM_THREADS=ALL_CPUS.
+    /// can be repeated
+    #[clap(long = "open-option", value_parser = parse_key_value)]
+    pub open_options: Vec<(String, String)>,
+
+    /// read only this raster table from geopackage inputs, every raster table by default. can be
+    /// repeated
+    #[clap(long = "table")]
+    pub tables: Vec<String>,
+
+    /// try a failed open or read of a raster again this many times, e.g. for flaky network or
+    /// nfs reads
+    #[clap(long, default_value_t = 0)]
+    pub retries: usize,
+
+    /// the wait before the first retry, doubled for each further one, e.g. 2s or 500ms
+    #[clap(long, default_value = "1s", value_parser = parse_duration)]
+    pub retry_delay: Duration,
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", value)),
+    }
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit) = if let Some(number) = value.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = value.strip_suffix('m') {
+        (number, 60.0)
+    } else if let Some(number) = value.strip_suffix('h') {
+        (number, 3600.0)
+    } else {
+        (value.strip_suffix('s').unwrap_or(value), 1.0)
+    };
+    match number.parse::<f64>() {
+        Ok(number) if number >= 0.0 => Ok(Duration::from_secs_f64(number * unit)),
+        _ => Err(format!(
+            "expected a duration like 2s, 500ms, 30m or 2h, got {:?}",
+            value
+        )),
+    }
+}
+
+fn parse_band_vrt(value: &str) -> Result<(isize, PathBuf), String> {
+    let (band, vrt) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected BAND=VRT, got {:?}", value))?;
+    Ok((parse_band_index(band)?, PathBuf::from(vrt)))
+}
+
+fn parse_band_map(value: &str) -> Result<(isize, isize), String> {
+    let (band, vrt_band) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected BAND:VRT_BAND, got {:?}", value))?;
+    Ok((parse_band_index(band)?, parse_band_index(vrt_band)?))
+}
+
+fn parse_band_kind(value: &str) -> Result<(isize, BandKind), String> {
+    let (band, kind) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected BAND=KIND, got {:?}", value))?;
+    Ok((parse_band_index(band)?, kind.parse()?))
+}
+
+fn parse_band_policy(value: &str) -> Result<(isize, BandPolicy), String> {
+    let (band, policy) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected BAND:POLICY, got {:?}", value))?;
+    Ok((parse_band_index(band)?, policy.parse()?))
+}
+
+fn parse_band_index(band: &str) -> Result<isize, String> {
+    let band = band
+        .parse::<isize>()
+        .map_err(|e| format!("invalid band {:?}: {}", band, e))?;
+    if band < 1 {
+        return Err(format!("band indices start at 1, got {}", band));
+    }
+    Ok(band)
 }
@@ -0,0 +1,115 @@
+//! async variants of the batches for tokio services, enabled with the `tokio` feature.
+//!
+//! the batch runs on the blocking thread pool of the runtime and processes `jobs` tiles at the
+//! same time like the blocking functions, so a service does not manage threads of its own. the
+//! report of every tile is sent to a stream as soon as the tile is done.
+use crate::{
+    crop_down_to_size_with_options, neighbor_buffer_with_options, vrt_buffer_with_options,
+    BatchReport, CancelToken, CropOptions, Margin, PadOptions, TileEvent, TileListener, TileReport,
+};
+use std::{
+    error::Error,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::{sync::mpsc, task::JoinHandle};
+use tokio_stream::Stream;
+
+/// the reports of the tiles of a running batch in the order they finish. the stream ends with
+/// the batch, dropping it before then cancels the batch.
+pub struct TileStream {
+    reports: mpsc::UnboundedReceiver<TileReport>,
+    batch: JoinHandle<Result<BatchReport, String>>,
+    cancel: CancelToken,
+}
+
+impl TileStream {
+    /// waits for the batch to finish and returns its report, or the error of a batch that could
+    /// not start
+    pub async fn finish(mut self) -> Result<BatchReport, Box<dyn Error + Send + Sync>> {
+        match (&mut self.batch).await {
+            Ok(Ok(report)) => Ok(report),
+            Ok(Err(e)) => Err(e.into()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+impl Stream for TileStream {
+    type Item = TileReport;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<TileReport>> {
+        self.reports.poll_recv(cx)
+    }
+}
+
+impl Drop for TileStream {
+    fn drop(&mut self) {
+        if !self.batch.is_finished() {
+            self.cancel.cancel();
+        }
+    }
+}
+
+/// pads the tiles of `input_dir` like `vrt_buffer_with_options`, or like
+/// `neighbor_buffer_with_options` when `reference` is `None`, on the blocking thread pool.
+/// must be called from within a tokio runtime.
+pub fn vrt_buffer_async(
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    reference: Option<PathBuf>,
+    margin: impl Into<Margin>,
+    options: PadOptions,
+) -> TileStream {
+    let margin = margin.into();
+    let (cancel, previous) = (options.cancel.clone(), options.listener.clone());
+    spawn_batch(cancel, previous, move |listener| {
+        let options = options.listener(listener);
+        match &reference {
+            Some(reference) => {
+                vrt_buffer_with_options(&input_dir, &output_dir, reference, margin, &options)
+            }
+            None => neighbor_buffer_with_options(&input_dir, &output_dir, margin, &options),
+        }
+    })
+}
+
+/// crops the buffered rasters of `input_dir` like `crop_down_to_size_with_options` on the
+/// blocking thread pool.
+/// must be called from within a tokio runtime.
+pub fn crop_async(
+    org_dir: PathBuf,
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    options: CropOptions,
+) -> TileStream {
+    let (cancel, previous) = (options.cancel.clone(), options.listener.clone());
+    spawn_batch(cancel, previous, move |listener| {
+        let options = options.listener(listener);
+        crop_down_to_size_with_options(&org_dir, &input_dir, &output_dir, &options)
+    })
+}
+
+/// runs `batch` with a listener that sends the tile reports to the stream, after passing them
+/// to the `previous` listener of the options
+fn spawn_batch(
+    cancel: CancelToken,
+    previous: TileListener,
+    batch: impl FnOnce(TileListener) -> Result<BatchReport, Box<dyn Error>> + Send + 'static,
+) -> TileStream {
+    let (sender, reports) = mpsc::unbounded_channel();
+    let listener = TileListener::events(move |event| {
+        previous.send(event);
+        if let TileEvent::TileFinished(report) | TileEvent::TileFailed(report) = event {
+            // the stream may have been dropped, the batch is cancelled then
+            let _ = sender.send(report.clone());
+        }
+    });
+    let batch = tokio::task::spawn_blocking(move || batch(listener).map_err(|e| e.to_string()));
+    TileStream {
+        reports,
+        batch,
+        cancel,
+    }
+}
@@ -0,0 +1,242 @@
+//! running an operation on every raster of a directory with the machinery of the pad and crop
+//! batches: the output paths, the worker threads, retries, cancellation and the batch report.
+//!
+//! `PadOp` and `CropOp` pad and crop single rasters, a custom step such as a hillshade implements
+//! `TileOperation` and runs in the same `BatchRunner`.
+use crate::{
+    add_margin_to_geotiff, cancel::FailureLimit, check_driver, check_separate_dirs,
+    check_virtual_output, gpkg, messages::message, raster_files, run_parallel, run_tile,
+    throttle::IoLimit, trim_buffered, BatchReport, CancelToken, CropOptions, ErrorPolicy, Margin,
+    Originals, OutputOptions, Outputs, PadOptions, RetryPolicy, TileListener, TileOutcome, TrimTo,
+    VrtSource,
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// the work done on a single raster of a batch.
+pub trait TileOperation: Sync {
+    /// processes `tile` into `output`. the parent directory of `output` exists, an error fails
+    /// the tile and is retried following the `RetryPolicy` of the runner
+    fn run(&self, tile: &Path, output: &Path) -> Result<TileOutcome, Box<dyn Error>>;
+}
+
+/// pads single rasters from a reference mosaic, like `vrt_buffer_with_options`.
+pub struct PadOp {
+    vrt: VrtSource,
+    band_vrts: Vec<(isize, VrtSource)>,
+    margin: Margin,
+    io: IoLimit,
+    options: PadOptions,
+}
+
+impl PadOp {
+    /// opens `reference` and the band vrts of `options`, margin: a number of pixels or a `Margin`
+    pub fn new(
+        reference: &Path,
+        margin: impl Into<Margin>,
+        options: PadOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        options.gdal.apply()?;
+        check_driver(options.output.format)?;
+        check_virtual_output(&options)?;
+        let mut band_vrts = Vec::new();
+        for (band_index, band_vrt_file) in &options.band_vrts {
+            band_vrts.push((*band_index, VrtSource::open(band_vrt_file)?));
+        }
+        Ok(PadOp {
+            vrt: VrtSource::open(reference)?,
+            band_vrts,
+            margin: margin.into(),
+            io: IoLimit::new(options.io_threads),
+            options,
+        })
+    }
+}
+
+impl TileOperation for PadOp {
+    fn run(&self, tile: &Path, output: &Path) -> Result<TileOutcome, Box<dyn Error>> {
+        add_margin_to_geotiff(
+            tile,
+            output,
+            self.margin,
+            &self.vrt,
+            &self.band_vrts,
+            &self.io,
+            &self.options,
+        )
+    }
+}
+
+/// crops single buffered rasters back to their originals, like `crop_down_to_size_with_options`,
+/// or trims a known margin off them, like `crop_fixed_with_options`.
+pub struct CropOp {
+    /// the originals, or the margin when there are none
+    originals: Result<Originals, Margin>,
+    options: CropOptions,
+}
+
+impl CropOp {
+    /// crops the rasters to the original of the same name in `org_dir`
+    pub fn to_originals(org_dir: &Path, options: CropOptions) -> Result<Self, Box<dyn Error>> {
+        options.gdal.apply()?;
+        check_driver(options.output.format)?;
+        Ok(CropOp {
+            originals: Ok(Originals::new(
+                org_dir,
+                &options.gdal.tables,
+                options.output.tile_id.as_ref(),
+            )?),
+            options,
+        })
+    }
+
+    /// trims `margin` off each side of the rasters
+    pub fn fixed(margin: impl Into<Margin>, options: CropOptions) -> Result<Self, Box<dyn Error>> {
+        options.gdal.apply()?;
+        check_driver(options.output.format)?;
+        Ok(CropOp {
+            originals: Err(margin.into()),
+            options,
+        })
+    }
+}
+
+impl TileOperation for CropOp {
+    fn run(&self, tile: &Path, output: &Path) -> Result<TileOutcome, Box<dyn Error>> {
+        let trim_to = match &self.originals {
+            Ok(originals) => match gpkg::tile_file_name(tile) {
+                Some(file_name) => originals.trim_to(&file_name),
+                None => return Err(format!("{:?} has no file name", tile).into()),
+            },
+            Err(margin) => TrimTo::Margin(*margin),
+        };
+        trim_buffered(tile, &trim_to, output, &self.options)
+    }
+}
+
+/// runs a `TileOperation` on every raster of a directory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchRunner {
+    /// the number of rasters processed at the same time, 0 and 1 process them one after the
+    /// other
+    pub jobs: usize,
+    /// how often the operation of a raster that failed is run again, for operations that can be
+    /// repeated. `PadOp` and `CropOp` retry their reads through `GdalConfig::retry` instead
+    pub retry: RetryPolicy,
+    /// stops the batch early when cancelled
+    pub cancel: CancelToken,
+    /// whether the batch goes on after rasters failed
+    pub error_policy: ErrorPolicy,
+    /// called with the report of every raster as soon as it is done
+    pub listener: TileListener,
+    /// names the outputs, see `OutputOptions::output_path`
+    pub output: OutputOptions,
+    /// the raster tables of the geopackages to process, every table when empty
+    pub tables: Vec<String>,
+    /// fail a raster with `TimedOut` once it has run this long
+    pub tile_timeout: Option<Duration>,
+}
+
+impl BatchRunner {
+    /// set the number of rasters processed at the same time.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// set how often a raster that failed is tried again.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// stop the batch when `cancel` is cancelled.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// fail a raster that runs longer than `timeout` with `TimedOut` and go on with the next one.
+    pub fn tile_timeout(mut self, timeout: Duration) -> Self {
+        self.tile_timeout = Some(timeout);
+        self
+    }
+
+    /// stop the batch after failed rasters instead of going on.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// call `listener` with the report of every raster as soon as it is done.
+    pub fn listener(mut self, listener: TileListener) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// set the options the output paths are composed with, e.g. the format for the extension.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// only process the named raster tables of the geopackages.
+    pub fn tables(mut self, tables: Vec<String>) -> Self {
+        self.tables = tables;
+        self
+    }
+
+    /// runs `operation` on every raster of `input_dir`, see `raster_files`, with the outputs in
+    /// `output_dir`. rasters that fail do not stop the batch unless the `ErrorPolicy` says so.
+    pub fn run(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+        operation: &impl TileOperation,
+    ) -> Result<BatchReport, Box<dyn Error>> {
+        check_separate_dirs(input_dir, output_dir, &self.output)?;
+        fs::create_dir_all(output_dir)?;
+
+        let mut tiles_and_outputs: Vec<(PathBuf, PathBuf)> = Vec::new();
+        let mut outputs = Outputs::default();
+        for path in raster_files(input_dir, &self.tables)? {
+            let output_path = match self.output.output_path(output_dir, &path) {
+                Ok(output_path) => output_path,
+                Err(e) => {
+                    message(&format!(
+                        "Could not compose the output path of {:?}: {}. Skipping...",
+                        path, e
+                    ));
+                    continue;
+                }
+            };
+            if !outputs.claim(&path, &output_path) {
+                continue;
+            }
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            tiles_and_outputs.push((path, output_path));
+        }
+
+        let failures = FailureLimit::new(self.error_policy);
+        let mut reports = run_parallel(&tiles_and_outputs, self.jobs, |(path, output_path)| {
+            let report = run_tile(
+                path,
+                output_path,
+                "Error processing raster. Skipping...",
+                (&self.cancel, &failures, self.tile_timeout),
+                &self.listener,
+                || self.retry.run(|| operation.run(path, output_path)),
+            );
+            self.listener.notify(&report);
+            report
+        });
+        reports.extend(outputs.collisions(&self.listener));
+        Ok(BatchReport::new(reports, None))
+    }
+}
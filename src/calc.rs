@@ -0,0 +1,161 @@
+//! a per pixel expression applied while padding, e.g. `A * 0.1 - 100`.
+//!
+//! `A` is the value of the pixel, the expression may use numbers, `+ - * /` and parentheses with
+//! the usual precedence. the expression is evaluated in f64 for every band, nodata pixels keep
+//! their value.
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Value,
+    Number(f64),
+    Negate(Box<Node>),
+    Binary(Box<Node>, char, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, value: f64) -> f64 {
+        match self {
+            Node::Value => value,
+            Node::Number(number) => *number,
+            Node::Negate(node) => -node.eval(value),
+            Node::Binary(left, operator, right) => {
+                let (left, right) = (left.eval(value), right.eval(value));
+                match operator {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    _ => left / right,
+                }
+            }
+        }
+    }
+}
+
+/// a parsed expression, see the module documentation for the syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calc {
+    expression: String,
+    root: Node,
+}
+
+impl Calc {
+    /// the expression evaluated for a pixel value
+    pub fn apply(&self, value: f64) -> f64 {
+        self.root.eval(value)
+    }
+
+    /// applies the expression to every pixel that is not `nodata`
+    pub(crate) fn apply_to(&self, data: &mut [f64], nodata: Option<f64>) {
+        for pixel in data.iter_mut() {
+            if Some(*pixel) != nodata {
+                *pixel = self.apply(*pixel);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Calc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl FromStr for Calc {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().filter(|c| !c.is_whitespace()).collect(),
+            position: 0,
+        };
+        let root = parser.sum()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected {:?} in expression {:?}", c, s));
+        }
+        Ok(Calc {
+            expression: s.to_string(),
+            root,
+        })
+    }
+}
+
+/// a recursive descent parser over the expression without its whitespace
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    /// terms joined by + and -
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        while let Some(operator @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(Box::new(node), operator, Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    /// factors joined by * and /
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.factor()?;
+        while let Some(operator @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            node = Node::Binary(Box::new(node), operator, Box::new(self.factor()?));
+        }
+        Ok(node)
+    }
+
+    fn factor(&mut self) -> Result<Node, String> {
+        match self.peek() {
+            Some('-') => {
+                self.position += 1;
+                Ok(Node::Negate(Box::new(self.factor()?)))
+            }
+            Some('+') => {
+                self.position += 1;
+                self.factor()
+            }
+            Some('A') => {
+                self.position += 1;
+                Ok(Node::Value)
+            }
+            Some('(') => {
+                self.position += 1;
+                let node = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err("unclosed ( in expression".to_string());
+                }
+                self.position += 1;
+                Ok(node)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position;
+                while let Some(c) = self.peek() {
+                    // the sign of an exponent belongs to the number, e.g. 1e-3
+                    let exponent_sign = matches!(c, '+' | '-')
+                        && matches!(self.chars[self.position - 1], 'e' | 'E');
+                    if !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E') || exponent_sign) {
+                        break;
+                    }
+                    self.position += 1;
+                }
+                let number: String = self.chars[start..self.position].iter().collect();
+                match number.parse() {
+                    Ok(number) => Ok(Node::Number(number)),
+                    Err(_) => Err(format!("invalid number {:?} in expression", number)),
+                }
+            }
+            Some(c) => Err(format!(
+                "unexpected {:?} in expression, use A for the pixel",
+                c
+            )),
+            None => Err("the expression ends early".to_string()),
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! stopping a batch early without leaving half written outputs behind.
+//!
+//! a tile that runs past its timeout, e.g. a corrupt tile whose reads never end, fails with
+//! `TimedOut`. gdal reads can not be interrupted, so the deadline is checked between the reads of
+//! a tile and by the progress function of gdal's copies, a single read that hangs still blocks
+//! its worker thread.
+use crate::{messages::message, ErrorPolicy};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    ffi::{c_char, c_int, c_void},
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how often a wait before a retry looks at the cancel token and the deadline of its tile
+const RETRY_POLL: Duration = Duration::from_millis(50);
+
+thread_local! {
+    /// the deadline and the timeout of the tile processed on this thread
+    static TILE_DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+    /// the cancel token of the batch of the tile processed on this thread
+    static TILE_CANCEL: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// a flag shared with a running batch. once it is cancelled the tiles that are being processed
+/// are finished and the remaining tiles are skipped with `SkipReason::Cancelled`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// asks the batch to stop after the tiles that are being processed.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// the underlying flag, e.g. to set it from a signal handler
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+}
+
+/// tokens are equal when they share the same flag
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.cancelled, &other.cancelled)
+    }
+}
+
+/// bounds a batch to a number of tiles and a time, the tiles past the bounds are skipped with
+/// `SkipReason::Limit`. tiles whose output is up to date are not counted, so a resumed run
+/// continues with the next tiles.
+pub(crate) struct RunLimit {
+    max_tiles: Option<usize>,
+    deadline: Option<Instant>,
+    started: AtomicUsize,
+}
+
+impl RunLimit {
+    pub(crate) fn new(max_tiles: Option<usize>, max_duration: Option<Duration>) -> Self {
+        RunLimit {
+            max_tiles,
+            deadline: max_duration.map(|duration| Instant::now() + duration),
+            started: AtomicUsize::new(0),
+        }
+    }
+
+    /// counts a tile that is about to be processed, false when it is past the bounds
+    pub(crate) fn start(&self) -> bool {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return false;
+        }
+        match self.max_tiles {
+            Some(max_tiles) => self.started.fetch_add(1, Ordering::SeqCst) < max_tiles,
+            None => true,
+        }
+    }
+}
+
+/// stops a batch once as many tiles failed as the `ErrorPolicy` allows, the tiles that were not
+/// started by then are skipped with `SkipReason::Aborted`.
+pub(crate) struct FailureLimit {
+    max_failures: Option<usize>,
+    failures: AtomicUsize,
+}
+
+impl FailureLimit {
+    pub(crate) fn new(policy: ErrorPolicy) -> Self {
+        FailureLimit {
+            max_failures: policy.max_failures(),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// counts a failed tile
+    pub(crate) fn fail(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.max_failures == Some(failures) {
+            message(&format!(
+                "Stopping the batch after {} failed tiles, the remaining tiles are skipped",
+                failures
+            ));
+        }
+    }
+
+    /// true when no more tiles may be started
+    pub(crate) fn is_reached(&self) -> bool {
+        self.max_failures
+            .is_some_and(|max_failures| self.failures.load(Ordering::SeqCst) >= max_failures)
+    }
+}
+
+/// a tile ran longer than the tile timeout of its batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the tile timed out after {:?}", self.timeout)
+    }
+}
+
+impl Error for TimedOut {}
+
+/// the deadline and the cancel token of the tile processed on the calling thread, cleared when
+/// it is dropped
+pub(crate) struct TileDeadline;
+
+impl TileDeadline {
+    /// starts the deadline of a tile of the batch of `cancel`, a tile without a timeout has none
+    pub(crate) fn start(timeout: Option<Duration>, cancel: &CancelToken) -> Self {
+        let deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout));
+        TILE_DEADLINE.with(|tile_deadline| tile_deadline.set(deadline));
+        TILE_CANCEL.with(|tile_cancel| *tile_cancel.borrow_mut() = Some(cancel.clone()));
+        TileDeadline
+    }
+}
+
+impl Drop for TileDeadline {
+    fn drop(&mut self) {
+        TILE_DEADLINE.with(|tile_deadline| tile_deadline.set(None));
+        TILE_CANCEL.with(|tile_cancel| *tile_cancel.borrow_mut() = None);
+    }
+}
+
+/// fails with `TimedOut` once the tile processed on the calling thread is past its deadline
+pub(crate) fn check_tile_deadline() -> Result<(), TimedOut> {
+    match TILE_DEADLINE.with(Cell::get) {
+        Some((deadline, timeout)) if Instant::now() >= deadline => Err(TimedOut { timeout }),
+        _ => Ok(()),
+    }
+}
+
+/// waits `delay` before a retry, false as soon as the batch of the tile processed on the calling
+/// thread is cancelled or the tile is past its deadline, the retry is then given up
+pub(crate) fn wait_for_retry(delay: Duration) -> bool {
+    let end = Instant::now() + delay;
+    loop {
+        let cancelled = TILE_CANCEL.with(|tile_cancel| {
+            tile_cancel
+                .borrow()
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+        });
+        if cancelled || check_tile_deadline().is_err() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= end {
+            return true;
+        }
+        thread::sleep((end - now).min(RETRY_POLL));
+    }
+}
+
+/// the progress function of gdal's copies, aborts the copy once the tile is past its deadline
+pub(crate) unsafe extern "C" fn abort_past_deadline(
+    _complete: f64,
+    _message: *const c_char,
+    _data: *mut c_void,
+) -> c_int {
+    c_int::from(check_tile_deadline().is_ok())
+}
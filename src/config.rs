@@ -0,0 +1,89 @@
+//! the `--config` file of the command line.
+//!
+//! the file names the subcommand with `command` and sets its options with their long names,
+//! `skip_empty` and `skip-empty` are the same option:
+//! ```toml
+//! command = "pad"
+//! input = "tiles"
+//! output = "padded"
+//! vrt = "tiles.vrt"
+//! pad = 64
+//! jobs = 8
+//! overviews = [2, 4, 8]
+//! skip_empty = true
+//! band_policy = ["7:nodata=255,fill=255"]
+//! ```
+//! the file is turned into arguments placed in front of the ones given on the command line, so
+//! the command line overrides single valued options and adds to repeatable ones.
+use std::{error::Error, ffi::OsString, fs, path::Path};
+use toml::{Table, Value};
+
+/// the command line arguments with the arguments of the `--config` file inserted, or unchanged
+/// when there is no `--config`
+pub fn args_with_config(args: Vec<OsString>) -> Result<Vec<OsString>, Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_default();
+    let mut config = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            config = args.next();
+        } else if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            config = Some(OsString::from(path));
+        } else {
+            rest.push(arg);
+        }
+    }
+    let config = match config {
+        Some(config) => config,
+        None => return Ok(std::iter::once(program).chain(rest).collect()),
+    };
+
+    let (command, file_args) = read_config(Path::new(&config))?;
+    // the subcommand may be repeated on the command line, it is already taken from the file
+    if rest.first().is_some_and(|arg| *arg == *command) {
+        rest.remove(0);
+    }
+    let mut expanded = vec![program, OsString::from(command)];
+    expanded.extend(file_args);
+    expanded.extend(rest);
+    Ok(expanded)
+}
+
+/// the subcommand and the arguments of a config file
+fn read_config(path: &Path) -> Result<(String, Vec<OsString>), Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut table = match text.parse::<Table>() {
+        Ok(table) => table,
+        Err(e) => return Err(format!("invalid config file {:?}: {}", path, e).into()),
+    };
+    let command = match table.remove("command") {
+        Some(Value::String(command)) => command,
+        _ => return Err(format!("{:?} does not name the subcommand with command", path).into()),
+    };
+
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(OsString::from(&flag)),
+                Value::Boolean(false) => {}
+                Value::String(value) => {
+                    args.push(OsString::from(&flag));
+                    args.push(OsString::from(value));
+                }
+                Value::Integer(_) | Value::Float(_) => {
+                    args.push(OsString::from(&flag));
+                    args.push(OsString::from(value.to_string()));
+                }
+                _ => return Err(format!("unsupported value for {} in {:?}", key, path).into()),
+            }
+        }
+    }
+    Ok((command, args))
+}
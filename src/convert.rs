@@ -0,0 +1,119 @@
+//! converting the f64 pixels of an output to `OutputOptions::output_type`.
+//!
+//! the pixels are rounded and clamped here rather than by gdal, so the rounding and the handling
+//! of values out of range can be chosen, and nodata pixels become the nodata value of the output
+//! instead of being clamped like any other value.
+use crate::{OutOfRange, OutputOptions, OutputType, Rounding};
+use std::error::Error;
+
+/// the conversion of the pixels of one band.
+pub(crate) struct BandConversion {
+    output_type: OutputType,
+    rounding: Rounding,
+    out_of_range: OutOfRange,
+    source_nodata: Option<f64>,
+    /// the nodata value of the output band
+    pub(crate) nodata: Option<f64>,
+}
+
+impl BandConversion {
+    /// the conversion of a band with `source_nodata` to the output type of `output_options`,
+    /// `None` when no output type is set
+    pub(crate) fn new(
+        output_options: &OutputOptions,
+        source_nodata: Option<f64>,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let output_type = match output_options.output_type {
+            Some(output_type) => output_type,
+            None => return Ok(None),
+        };
+        let (min, max) = output_type.range();
+        let fits = |value: f64| {
+            value >= min && value <= max && (!output_type.is_integer() || value.fract() == 0.0)
+        };
+        // the nodata of the source is kept when the output type can hold it
+        let default_nodata = match source_nodata {
+            Some(nodata) if fits(nodata) || (nodata.is_nan() && !output_type.is_integer()) => {
+                nodata
+            }
+            _ if output_type.is_integer() && min == 0.0 => max,
+            _ if output_type.is_integer() => min,
+            _ => f64::NAN,
+        };
+        let needs_nodata =
+            source_nodata.is_some() || output_options.out_of_range == OutOfRange::Nodata;
+        let nodata = match output_options.output_nodata {
+            Some(nodata) if !fits(nodata) => {
+                return Err(format!(
+                    "the output nodata value {} does not fit the output type {:?}",
+                    nodata, output_type
+                )
+                .into())
+            }
+            Some(nodata) if needs_nodata => Some(nodata),
+            _ if needs_nodata => Some(default_nodata),
+            _ => None,
+        };
+        Ok(Some(BandConversion {
+            output_type,
+            rounding: output_options.rounding,
+            out_of_range: output_options.out_of_range,
+            source_nodata,
+            nodata,
+        }))
+    }
+
+    /// converts the pixels in place, the buffer stays f64 but holds values of the output type
+    pub(crate) fn convert(&self, data: &mut [f64]) {
+        let (min, max) = self.output_type.range();
+        let source_nodata = self.source_nodata;
+        let nodata = self.nodata.unwrap_or(f64::NAN);
+        for pixel in data.iter_mut() {
+            let is_nodata = match source_nodata {
+                Some(source_nodata) => {
+                    *pixel == source_nodata || (source_nodata.is_nan() && pixel.is_nan())
+                }
+                None => false,
+            };
+            if is_nodata {
+                *pixel = nodata;
+                continue;
+            }
+
+            let mut value = *pixel;
+            if self.output_type.is_integer() {
+                if value.is_nan() {
+                    *pixel = if self.nodata.is_some() { nodata } else { 0.0 };
+                    continue;
+                }
+                value = match self.rounding {
+                    Rounding::Nearest => value.round(),
+                    Rounding::Floor => value.floor(),
+                    Rounding::Truncate => value.trunc(),
+                };
+            }
+            *pixel = if value < min || value > max {
+                match (self.out_of_range, self.nodata) {
+                    (OutOfRange::Nodata, Some(_)) => nodata,
+                    _ => value.clamp(min, max),
+                }
+            } else {
+                value
+            };
+        }
+    }
+}
+
+/// replaces the pixels of `data` that are `source_nodata`, or nan when it is nan, with `nodata`,
+/// for bands whose `BandPolicy` sets another nodata value than the one of the source
+pub(crate) fn replace_nodata(data: &mut [f64], source_nodata: Option<f64>, nodata: f64) {
+    let source_nodata = match source_nodata {
+        Some(source_nodata) => source_nodata,
+        None => return,
+    };
+    for pixel in data.iter_mut() {
+        if *pixel == source_nodata || (source_nodata.is_nan() && pixel.is_nan()) {
+            *pixel = nodata;
+        }
+    }
+}
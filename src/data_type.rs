@@ -0,0 +1,66 @@
+//! the band data types the rasters can be processed with.
+//!
+//! the pixels are processed as f64, 64 bit integers and complex values would be silently
+//! rounded or truncated, so rasters with such bands are rejected before anything is written.
+use gdal::{raster::RasterBand, Dataset};
+use gdal_sys::{GDALDataTypeIsComplex, GDALGetDataTypeName, GDALGetRasterDataType};
+use std::{
+    error::Error,
+    ffi::CStr,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// a band whose data type can not be processed without losing values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedDataType {
+    pub path: PathBuf,
+    pub band: isize,
+    /// the gdal name of the data type, e.g. `Int64` or `CFloat32`
+    pub data_type: String,
+}
+
+impl fmt::Display for UnsupportedDataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "band {} of {:?} is {}, which is not supported",
+            self.band, self.path, self.data_type
+        )
+    }
+}
+
+impl Error for UnsupportedDataType {}
+
+/// fails with `UnsupportedDataType` for the first band that is a 64 bit integer or complex
+pub(crate) fn check_data_types(ds: &Dataset, path: &Path) -> Result<(), Box<dyn Error>> {
+    for band_index in 1..=ds.raster_count() {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let name = data_type_name(&band);
+        let is_complex =
+            unsafe { GDALDataTypeIsComplex(GDALGetRasterDataType(band.c_rasterband())) != 0 };
+        if is_complex || matches!(name.as_str(), "Int64" | "UInt64" | "Unknown") {
+            return Err(Box::new(UnsupportedDataType {
+                path: path.to_path_buf(),
+                band: band_index,
+                data_type: name,
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// the gdal name of the data type of `band`, e.g. `Float32`
+pub(crate) fn data_type_name(band: &RasterBand) -> String {
+    unsafe {
+        let name = GDALGetDataTypeName(GDALGetRasterDataType(band.c_rasterband()));
+        if name.is_null() {
+            "Unknown".to_string()
+        } else {
+            CStr::from_ptr(name).to_string_lossy().into_owned()
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! the properties of a raster `gdalinfo` prints, for checking the outputs of a run without
+//! shelling out to gdalinfo.
+use crate::{data_type::data_type_name, geometry::GeoTransform, path::open_dataset};
+use serde::Serialize;
+use std::{error::Error, fmt, path::Path};
+
+/// the size, bands and georeferencing of a raster.
+///
+/// the data type, nodata value and block size are the ones of the first band, the bands of the
+/// outputs of a run share them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RasterDescription {
+    /// (cols, rows)
+    pub size: (usize, usize),
+    /// the number of bands
+    pub bands: usize,
+    /// the gdal name of the data type, e.g. `Float32`
+    pub dtype: String,
+    /// the wkt of the coordinate reference system, empty when the raster has none
+    pub crs_wkt: String,
+    pub geotransform: GeoTransform,
+    pub nodata: Option<f64>,
+    /// (cols, rows) of a block
+    pub block_size: (usize, usize),
+}
+
+impl fmt::Display for RasterDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size is {}, {}", self.size.0, self.size.1)?;
+        writeln!(
+            f,
+            "Origin = ({}, {})",
+            self.geotransform[0], self.geotransform[3]
+        )?;
+        writeln!(
+            f,
+            "Pixel Size = ({}, {})",
+            self.geotransform[1], self.geotransform[5]
+        )?;
+        if !self.crs_wkt.is_empty() {
+            writeln!(f, "Coordinate System is:\n{}", self.crs_wkt)?;
+        }
+        writeln!(
+            f,
+            "{} bands of Type={} Block={}x{}",
+            self.bands, self.dtype, self.block_size.0, self.block_size.1
+        )?;
+        if let Some(nodata) = self.nodata {
+            writeln!(f, "NoData Value={}", nodata)?;
+        }
+        Ok(())
+    }
+}
+
+/// describes the raster at `path`, see `RasterDescription`
+pub fn describe(path: &Path) -> Result<RasterDescription, Box<dyn Error>> {
+    let ds = open_dataset(path, &[])?;
+    let geotransform = match ds.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let band = match ds.rasterband(1) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    Ok(RasterDescription {
+        size: ds.raster_size(),
+        bands: ds.raster_count() as usize,
+        dtype: data_type_name(&band),
+        crs_wkt: ds.projection(),
+        geotransform,
+        nodata: band.no_data_value(),
+        block_size: band.block_size(),
+    })
+}
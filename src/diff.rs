@@ -0,0 +1,216 @@
+//! comparing the rasters of two directories pixel by pixel, e.g. the cropped outputs with their
+//! originals or the outputs of two runs, to check that a change did not change the results.
+//!
+//! the rasters are paired by file name. pixels that are nodata or nan in both rasters are equal,
+//! a pixel that is nodata in only one of them differs and is left out of the differences.
+use crate::{gpkg::tile_file_name, path::open_dataset, raster_files, run_parallel};
+use gdal::{raster::RasterBand, Dataset};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// how the pixels of a pair of rasters differ.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RasterDiff {
+    /// the raster of the first directory
+    pub raster: PathBuf,
+    /// the pixels of all bands that were compared
+    pub pixels: u64,
+    /// the pixels that differ by more than the tolerance or are nodata in only one raster
+    pub differing_pixels: u64,
+    /// the largest absolute difference of a pixel
+    pub max_difference: f64,
+    /// the mean absolute difference of the pixels
+    pub mean_difference: f64,
+}
+
+/// how the rasters of two directories differ.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DirDiff {
+    /// the pairs of rasters that were compared
+    pub rasters: Vec<RasterDiff>,
+    /// the pairs that can not be compared pixel by pixel and why, e.g. of different sizes
+    pub incomparable: Vec<(PathBuf, String)>,
+    /// the rasters of the first directory that the second one does not have
+    pub only_in_first: Vec<PathBuf>,
+    /// the rasters of the second directory that the first one does not have
+    pub only_in_second: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// true when both directories hold the same rasters and no pixel differs
+    pub fn is_identical(&self) -> bool {
+        self.incomparable.is_empty()
+            && self.only_in_first.is_empty()
+            && self.only_in_second.is_empty()
+            && self.rasters.iter().all(|diff| diff.differing_pixels == 0)
+    }
+}
+
+impl fmt::Display for DirDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} rasters compared", self.rasters.len())?;
+        for diff in self.rasters.iter().filter(|diff| diff.differing_pixels > 0) {
+            writeln!(
+                f,
+                "  differs: {}: {} of {} pixels, max {}, mean {}",
+                diff.raster.display(),
+                diff.differing_pixels,
+                diff.pixels,
+                diff.max_difference,
+                diff.mean_difference
+            )?;
+        }
+        for (path, reason) in &self.incomparable {
+            writeln!(f, "  incomparable: {}: {}", path.display(), reason)?;
+        }
+        for path in &self.only_in_first {
+            writeln!(f, "  only in the first directory: {}", path.display())?;
+        }
+        for path in &self.only_in_second {
+            writeln!(f, "  only in the second directory: {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// compares the rasters of `first_dir` with the rasters of the same name in `second_dir`.
+/// tolerance: the largest absolute difference of a pixel that does not count as a difference
+/// jobs: the number of pairs compared at the same time
+pub fn diff_dirs(
+    first_dir: &Path,
+    second_dir: &Path,
+    tolerance: f64,
+    jobs: usize,
+) -> Result<DirDiff, Box<dyn Error>> {
+    let by_name = |dir: &Path| -> Result<BTreeMap<OsString, PathBuf>, Box<dyn Error>> {
+        Ok(raster_files(dir, &[])?
+            .into_iter()
+            .map(|path| (tile_file_name(&path).unwrap_or_default(), path))
+            .collect())
+    };
+    let (first, mut second) = (by_name(first_dir)?, by_name(second_dir)?);
+
+    let mut dir_diff = DirDiff::default();
+    let mut pairs = Vec::new();
+    for (name, path) in first {
+        match second.remove(&name) {
+            Some(other) => pairs.push((path, other)),
+            None => dir_diff.only_in_first.push(path),
+        }
+    }
+    dir_diff.only_in_second = second.into_values().collect();
+
+    let diffs = run_parallel(&pairs, jobs, |(first, second)| {
+        diff_rasters(first, second, tolerance).map_err(|e| e.to_string())
+    });
+    for ((first, _), diff) in pairs.iter().zip(diffs) {
+        match diff {
+            Ok(diff) => dir_diff.rasters.push(diff),
+            Err(e) => dir_diff.incomparable.push((first.clone(), e)),
+        }
+    }
+    Ok(dir_diff)
+}
+
+/// compares the pixels of every band of `first` with `second`
+pub fn diff_rasters(
+    first: &Path,
+    second: &Path,
+    tolerance: f64,
+) -> Result<RasterDiff, Box<dyn Error>> {
+    let (first_ds, second_ds) = (open_dataset(first, &[])?, open_dataset(second, &[])?);
+    check_comparable(&first_ds, &second_ds)?;
+
+    let mut diff = RasterDiff {
+        raster: first.to_path_buf(),
+        ..RasterDiff::default()
+    };
+    let mut sum = 0.0;
+    let mut compared = 0u64;
+    for band_index in 1..=first_ds.raster_count() {
+        let (first_band, second_band) = match (
+            first_ds.rasterband(band_index),
+            second_ds.rasterband(band_index),
+        ) {
+            (Ok(first_band), Ok(second_band)) => (first_band, second_band),
+            (Err(e), _) | (_, Err(e)) => return Err(Box::new(e)),
+        };
+        let (first_nodata, second_nodata) =
+            (first_band.no_data_value(), second_band.no_data_value());
+
+        // one strip of block rows at a time, so large rasters are not held in memory
+        let (cols, rows) = first_ds.raster_size();
+        let block_rows = first_band.block_size().1.max(1);
+        let mut row = 0;
+        while row < rows {
+            let strip_rows = block_rows.min(rows - row);
+            let first_data = read_strip(&first_band, row, (cols, strip_rows))?;
+            let second_data = read_strip(&second_band, row, (cols, strip_rows))?;
+            for (&a, &b) in first_data.iter().zip(&second_data) {
+                diff.pixels += 1;
+                match (is_missing(a, first_nodata), is_missing(b, second_nodata)) {
+                    (true, true) => continue,
+                    (true, false) | (false, true) => {
+                        diff.differing_pixels += 1;
+                        continue;
+                    }
+                    (false, false) => {}
+                }
+                let difference = (a - b).abs();
+                if difference > tolerance {
+                    diff.differing_pixels += 1;
+                }
+                diff.max_difference = diff.max_difference.max(difference);
+                sum += difference;
+                compared += 1;
+            }
+            row += strip_rows;
+        }
+    }
+    if compared > 0 {
+        diff.mean_difference = sum / compared as f64;
+    }
+    Ok(diff)
+}
+
+/// fails when the rasters do not have the same size and number of bands
+fn check_comparable(first: &Dataset, second: &Dataset) -> Result<(), String> {
+    if first.raster_size() != second.raster_size() {
+        return Err(format!(
+            "the sizes differ: {:?} and {:?}",
+            first.raster_size(),
+            second.raster_size()
+        ));
+    }
+    if first.raster_count() != second.raster_count() {
+        return Err(format!(
+            "the band counts differ: {} and {}",
+            first.raster_count(),
+            second.raster_count()
+        ));
+    }
+    Ok(())
+}
+
+/// the `size` strip of `band` that starts at `row`
+fn read_strip(
+    band: &RasterBand,
+    row: usize,
+    size: (usize, usize),
+) -> Result<Vec<f64>, Box<dyn Error>> {
+    match band.read_as::<f64>((0, row as isize), size, size, None) {
+        Ok(data) => Ok(data.data),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// true when `value` is nodata or nan
+fn is_missing(value: f64, nodata: Option<f64>) -> bool {
+    value.is_nan() || nodata == Some(value)
+}
@@ -0,0 +1,83 @@
+//! checking that the output file system has room for a batch before it starts.
+//!
+//! the size of the outputs is estimated from the headers of the inputs: the pixels of each output
+//! times its bands and the size of its data type. geotiffs are written uncompressed, cogs are
+//! assumed to compress to half of that with their default compression and virtual vrts hold no
+//! pixels.
+use crate::{
+    geometry::Margin,
+    messages::message,
+    path::{gdal_path, open_dataset},
+    DiskSpaceCheck, OutputFormat, OutputOptions,
+};
+use gdal::Dataset;
+use gdal_sys::{GDALDataType, GDALGetDataTypeSizeBytes, VSIGetDiskFreeSpace};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// the estimated size of the outputs of `tiles`, each grown by `margin` on every side.
+/// data_type: the data type of the output of an opened tile.
+/// tiles that can not be opened are left out, they fail once the batch runs
+pub(crate) fn estimate_output_bytes(
+    tiles: &[PathBuf],
+    margin: Margin,
+    output_options: &OutputOptions,
+    data_type: impl Fn(&Dataset) -> Result<GDALDataType::Type, Box<dyn Error>>,
+) -> u64 {
+    let mut bytes = 0;
+    for tile in tiles {
+        let ds = match open_dataset(tile, &[]) {
+            Ok(ds) => ds,
+            Err(_) => continue,
+        };
+        let pixel_bytes = match data_type(&ds) {
+            Ok(data_type) => unsafe { GDALGetDataTypeSizeBytes(data_type) as u64 },
+            Err(_) => continue,
+        };
+        let (cols, rows) = ds.raster_size();
+        let pixels = (cols + 2 * margin.x()) as u64 * (rows + 2 * margin.y()) as u64;
+        let bands = match output_options.bands.len() {
+            0 => ds.raster_count() as u64,
+            bands => bands as u64,
+        };
+        bytes += pixels * bands * pixel_bytes;
+    }
+    match output_options.format {
+        OutputFormat::Cog => bytes / 2,
+        OutputFormat::VirtualVrt => 0,
+        _ => bytes,
+    }
+}
+
+/// fails, or warns, when the file system of `dir` has less than `required` bytes free
+pub(crate) fn check_free_space(
+    dir: &Path,
+    required: u64,
+    check: DiskSpaceCheck,
+) -> Result<(), Box<dyn Error>> {
+    if check == DiskSpaceCheck::Off {
+        return Ok(());
+    }
+    let c_path = gdal_path(dir)?;
+    let free = unsafe { VSIGetDiskFreeSpace(c_path.as_ptr()) };
+    // gdal returns a negative value when it can not tell
+    if free < 0 || free as u64 >= required {
+        return Ok(());
+    }
+    let mib = |bytes: u64| bytes / (1024 * 1024);
+    let problem = format!(
+        "{:?} has {} MiB free, the outputs need about {} MiB",
+        dir,
+        mib(free as u64),
+        mib(required)
+    );
+    match check {
+        DiskSpaceCheck::Warn => {
+            message(&format!("Warning: {}", problem));
+            Ok(())
+        }
+        _ => Err(problem.into()),
+    }
+}
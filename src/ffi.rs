@@ -0,0 +1,108 @@
+//! a c compatible interface, enabled with the `ffi` feature.
+//!
+//! the functions return the same codes as the command line: 0 when every raster was processed,
+//! 2 when some rasters failed and 3 when the run could not start. the message of the last fatal
+//! error of the calling thread is returned by `vrt_buffer_last_error`.
+use crate::{
+    crop_down_to_size, neighbor_buffer_with_options, vrt_buffer_with_options, BatchReport,
+    PadOptions,
+};
+use std::{
+    cell::RefCell,
+    error::Error,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+pub const VRT_BUFFER_OK: c_int = 0;
+pub const VRT_BUFFER_TILES_FAILED: c_int = 2;
+pub const VRT_BUFFER_FATAL: c_int = 3;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// pads the geotiffs of `input_dir` into `output_dir`, reading the margin from `vrt_file` or,
+/// when it is null, from the neighboring geotiffs.
+///
+/// # Safety
+/// the paths must be null terminated utf-8 strings, only `vrt_file` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn vrt_buffer_pad_dir(
+    input_dir: *const c_char,
+    output_dir: *const c_char,
+    vrt_file: *const c_char,
+    margin: usize,
+    jobs: usize,
+) -> c_int {
+    run(|| {
+        let input_dir = path_arg(input_dir, "input_dir")?;
+        let output_dir = path_arg(output_dir, "output_dir")?;
+        let options = PadOptions::default().jobs(jobs);
+        if vrt_file.is_null() {
+            neighbor_buffer_with_options(&input_dir, &output_dir, margin, &options)
+        } else {
+            let vrt_file = path_arg(vrt_file, "vrt_file")?;
+            vrt_buffer_with_options(&input_dir, &output_dir, &vrt_file, margin, &options)
+        }
+    })
+}
+
+/// crops the buffered geotiffs of `input_dir` to the extent of the originals in `org_dir` and
+/// saves them to `output_dir`.
+///
+/// # Safety
+/// the paths must be null terminated utf-8 strings.
+#[no_mangle]
+pub unsafe extern "C" fn vrt_buffer_crop_dir(
+    org_dir: *const c_char,
+    input_dir: *const c_char,
+    output_dir: *const c_char,
+) -> c_int {
+    run(|| {
+        let org_dir = path_arg(org_dir, "org_dir")?;
+        let input_dir = path_arg(input_dir, "input_dir")?;
+        let output_dir = path_arg(output_dir, "output_dir")?;
+        crop_down_to_size(&org_dir, &input_dir, &output_dir)
+    })
+}
+
+/// the message of the last fatal error on the calling thread, or null.
+/// the string is owned by the library and valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn vrt_buffer_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// runs a batch, keeping panics from unwinding into the caller
+fn run(f: impl FnOnce() -> Result<BatchReport, Box<dyn Error>>) -> c_int {
+    let result = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(_) => Err("vrt_buffer panicked".into()),
+    };
+    let (code, message) = match result {
+        Ok(report) if report.failed() > 0 => (VRT_BUFFER_TILES_FAILED, None),
+        Ok(_) => (VRT_BUFFER_OK, None),
+        // an interior nul byte can only come from a path, drop the message rather than fail
+        Err(e) => (VRT_BUFFER_FATAL, CString::new(e.to_string()).ok()),
+    };
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+    code
+}
+
+/// # Safety
+/// `value` must be null or a null terminated string
+unsafe fn path_arg(value: *const c_char, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if value.is_null() {
+        return Err(format!("{} is null", name).into());
+    }
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => Ok(PathBuf::from(value)),
+        Err(e) => Err(format!("{} is not valid utf-8: {}", name, e).into()),
+    }
+}
@@ -0,0 +1,119 @@
+//! gdal runtime configuration applied before a batch is processed.
+use crate::{cancel::wait_for_retry, gpkg, messages::message, path::open_dataset, vsi};
+use gdal::{
+    config::set_config_option,
+    raster::{Buffer, GdalType, RasterBand, ResampleAlg},
+    Dataset,
+};
+use std::{error::Error, path::Path, time::Duration};
+
+/// how often a dataset open or a raster read that failed, e.g. on a flaky network read through
+/// /vsicurl, is tried again. the delay doubles after every attempt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: usize,
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// runs `f` until it succeeds or the retries are used up. the wait before a retry ends with
+    /// the last error when the batch is cancelled or the tile is past its deadline
+    pub(crate) fn run<T>(
+        &self,
+        mut f: impl FnMut() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut delay = self.delay;
+        for _ in 0..self.retries {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    message(&format!("Warning: {}, retrying in {:?}", e, delay));
+                    if !wait_for_retry(delay) {
+                        return Err(e);
+                    }
+                    delay *= 2;
+                }
+            }
+        }
+        f()
+    }
+
+    /// reads `window_size` pixels at `offset` of `band` into a buffer of `buffer_size`, see
+    /// `RasterBand::read_as`, trying again when the read fails
+    pub(crate) fn read_as<T: GdalType + Copy>(
+        &self,
+        band: &RasterBand,
+        offset: (isize, isize),
+        window_size: (usize, usize),
+        buffer_size: (usize, usize),
+        resampling: Option<ResampleAlg>,
+    ) -> Result<Buffer<T>, Box<dyn Error>> {
+        self.run(
+            || match band.read_as::<T>(offset, window_size, buffer_size, resampling) {
+                Ok(data) => Ok(data),
+                Err(e) => Err(Box::new(e)),
+            },
+        )
+    }
+}
+
+/// gdal configuration and open options, e.g. `GDAL_CACHEMAX=2048` or `NUM_THREADS=ALL_CPUS`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GdalConfig {
+    /// process wide configuration options, as (key, value)
+    pub config_options: Vec<(String, String)>,
+    /// open options passed to the driver when opening the input rasters, as (key, value)
+    pub open_options: Vec<(String, String)>,
+    /// the raster tables read from geopackage inputs, every raster table when empty
+    pub tables: Vec<String>,
+    /// how often a failed open or read of a tile is tried again before the tile fails
+    pub retry: RetryPolicy,
+}
+
+impl GdalConfig {
+    /// set a gdal configuration option, e.g. `GDAL_CACHEMAX` or `GDAL_NUM_THREADS`.
+    pub fn config_option(mut self, key: &str, value: &str) -> Self {
+        self.config_options
+            .push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// pass an open option to the driver of every input raster, e.g. `NUM_THREADS` for geotiffs.
+    pub fn open_option(mut self, key: &str, value: &str) -> Self {
+        self.open_options.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// read only the raster tables named `name` from geopackage inputs, can be called again to
+    /// read several tables.
+    pub fn table(mut self, name: &str) -> Self {
+        self.tables.push(name.to_string());
+        self
+    }
+
+    /// try a failed dataset open or raster read again up to `retries` times, waiting `delay`
+    /// before the first retry and twice as long before each further one.
+    pub fn retry(mut self, retries: usize, delay: Duration) -> Self {
+        self.retry = RetryPolicy { retries, delay };
+        self
+    }
+
+    /// sets the configuration options. they are process wide and stay set after the batch.
+    pub(crate) fn apply(&self) -> Result<(), Box<dyn Error>> {
+        for (key, value) in &self.config_options {
+            if let Err(e) = set_config_option(key, value) {
+                return Err(Box::new(e));
+            };
+        }
+        Ok(())
+    }
+
+    /// opens an input raster with the open options, trying again when the open fails. a local
+    /// file that does not exist fails right away.
+    pub(crate) fn open(&self, path: &Path) -> Result<Dataset, Box<dyn Error>> {
+        if !vsi::is_virtual(path) && !gpkg::file_of(path).exists() {
+            return open_dataset(path, &self.open_options);
+        }
+        self.retry.run(|| open_dataset(path, &self.open_options))
+    }
+}
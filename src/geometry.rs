@@ -0,0 +1,1121 @@
+//! georeferenced extents and the math to go between them and pixel windows.
+//!
+//! pad and crop use these functions for all of their offset computations, so code that needs the
+//! same rounding and clamping behavior can call them directly. `Affine` is a geotransform in the
+//! form of python's `affine` package for window math of its own.
+use crate::path::open_dataset;
+use gdal::Dataset;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt,
+    ops::Mul,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// the largest side of a raster gdal can address, its pixel indices are `i32`
+pub const MAX_RASTER_SIZE: usize = i32::MAX as usize;
+
+/// a gdal geotransform, `[x_origin, x_res, row_rotation, y_origin, col_rotation, y_res]`
+pub type GeoTransform = [f64; 6];
+
+/// a rectangle of pixels in a raster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PixelWindow {
+    /// the column of the upper left pixel
+    pub x_offset: isize,
+    /// the row of the upper left pixel
+    pub y_offset: isize,
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl PixelWindow {
+    pub fn new(x_offset: isize, y_offset: isize, cols: usize, rows: usize) -> Self {
+        PixelWindow {
+            x_offset,
+            y_offset,
+            cols,
+            rows,
+        }
+    }
+
+    /// the offset in the form gdal's `read_as` expects
+    pub fn offset(&self) -> (isize, isize) {
+        (self.x_offset, self.y_offset)
+    }
+
+    /// the size in the form gdal's `read_as` expects
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// true when the window lies completely inside a raster of `raster_size` pixels
+    pub fn is_within(&self, raster_size: (usize, usize)) -> bool {
+        self.x_offset >= 0
+            && self.y_offset >= 0
+            && self.x_offset as usize + self.cols <= raster_size.0
+            && self.y_offset as usize + self.rows <= raster_size.1
+    }
+
+    /// the part of the window inside a raster of `raster_size` pixels, `None` when they do not
+    /// overlap
+    pub fn clamped_to(&self, raster_size: (usize, usize)) -> Option<PixelWindow> {
+        let x_start = self.x_offset.clamp(0, raster_size.0 as isize);
+        let y_start = self.y_offset.clamp(0, raster_size.1 as isize);
+        let x_end = (self.x_offset + self.cols as isize).clamp(0, raster_size.0 as isize);
+        let y_end = (self.y_offset + self.rows as isize).clamp(0, raster_size.1 as isize);
+        if x_end <= x_start || y_end <= y_start {
+            return None;
+        }
+        Some(PixelWindow::new(
+            x_start,
+            y_start,
+            (x_end - x_start) as usize,
+            (y_end - y_start) as usize,
+        ))
+    }
+}
+
+/// how a fractional pixel offset is snapped to a whole pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Snapping {
+    /// always take the pixel the offset falls in, an offset of 9.9999 becomes 9
+    Floor,
+    /// take the nearest pixel, exact halves go to the larger offset
+    #[default]
+    RoundHalfUp,
+    /// take the nearest pixel but fail when the offset is further than `tolerance` pixels from it
+    ErrorIfMisaligned { tolerance: f64 },
+}
+
+impl Snapping {
+    /// snaps a fractional pixel offset to a whole pixel
+    pub fn snap(&self, offset: f64) -> Result<isize, Misaligned> {
+        match *self {
+            Snapping::Floor => Ok(offset.floor() as isize),
+            Snapping::RoundHalfUp => Ok((offset + 0.5).floor() as isize),
+            Snapping::ErrorIfMisaligned { tolerance } => {
+                let snapped = (offset + 0.5).floor();
+                if (offset - snapped).abs() > tolerance {
+                    Err(Misaligned { offset, tolerance })
+                } else {
+                    Ok(snapped as isize)
+                }
+            }
+        }
+    }
+}
+
+/// parses `floor`, `round` or `error:<tolerance>`, e.g. `error:0.01`
+impl FromStr for Snapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "floor" => Ok(Snapping::Floor),
+            None if s == "round" => Ok(Snapping::RoundHalfUp),
+            Some(("error", tolerance)) => match tolerance.parse::<f64>() {
+                Ok(tolerance) if tolerance >= 0.0 => Ok(Snapping::ErrorIfMisaligned { tolerance }),
+                _ => Err(format!("invalid snapping tolerance {:?}", tolerance)),
+            },
+            _ => Err(format!(
+                "unknown snapping {:?}, expected floor, round or error:<tolerance>",
+                s
+            )),
+        }
+    }
+}
+
+/// the grids of two rasters are offset by more than the snapping tolerance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Misaligned {
+    /// the fractional pixel offset that could not be snapped
+    pub offset: f64,
+    pub tolerance: f64,
+}
+
+impl fmt::Display for Misaligned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pixel offset {} is misaligned by more than {} pixels",
+            self.offset, self.tolerance
+        )
+    }
+}
+
+impl Error for Misaligned {}
+
+/// the margin of a tile extends beyond the edge of the reference mosaic, returned with
+/// `MarginPolicy::Error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarginBeyondReference {
+    pub reference: PathBuf,
+}
+
+impl fmt::Display for MarginBeyondReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the margin extends beyond the edge of {:?}",
+            self.reference
+        )
+    }
+}
+
+impl Error for MarginBeyondReference {}
+
+/// the margin is larger than `Margin::maximum` for the reference, or the padded tile would be
+/// larger than `MAX_RASTER_SIZE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidMargin {
+    pub margin: Margin,
+    /// the largest margin on the x and on the y axis
+    pub maximum: (usize, usize),
+}
+
+impl fmt::Display for InvalidMargin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a margin of {} by {} pixels is too large, at most {} by {} pixels can be added here. \
+             check that the margin is given in pixels, not in map units",
+            self.margin.x(),
+            self.margin.y(),
+            self.maximum.0,
+            self.maximum.1
+        )
+    }
+}
+
+impl Error for InvalidMargin {}
+
+/// an affine transform from pixel coordinates (column, row) to world coordinates, with the
+/// coefficients in the order of python's `affine` package: `x = a * col + b * row + c` and
+/// `y = d * col + e * row + f`.
+///
+/// converts to and from a gdal `GeoTransform`, whose order is `[c, a, b, f, d, e]`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Affine { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Self {
+        Affine::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0)
+    }
+
+    /// moves by `x` and `y`
+    pub fn translation(x: f64, y: f64) -> Self {
+        Affine::new(1.0, 0.0, x, 0.0, 1.0, y)
+    }
+
+    /// scales by `x` and `y`
+    pub fn scale(x: f64, y: f64) -> Self {
+        Affine::new(x, 0.0, 0.0, 0.0, y, 0.0)
+    }
+
+    pub fn from_gdal(geo_transform: &GeoTransform) -> Self {
+        let [c, a, b, f, d, e] = *geo_transform;
+        Affine { a, b, c, d, e, f }
+    }
+
+    pub fn to_gdal(&self) -> GeoTransform {
+        [self.c, self.a, self.b, self.f, self.d, self.e]
+    }
+
+    pub fn determinant(&self) -> f64 {
+        self.a * self.e - self.b * self.d
+    }
+
+    /// the transform from world to pixel coordinates, `None` when the transform can not be
+    /// inverted because its determinant is 0
+    pub fn invert(&self) -> Option<Affine> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let (a, b, d, e) = (self.e / det, -self.b / det, -self.d / det, self.a / det);
+        Some(Affine {
+            a,
+            b,
+            c: -self.c * a - self.f * b,
+            d,
+            e,
+            f: -self.c * d - self.f * e,
+        })
+    }
+
+    /// transforms the point (`col`, `row`)
+    pub fn apply(&self, col: f64, row: f64) -> (f64, f64) {
+        (
+            self.c + col * self.a + row * self.b,
+            self.f + col * self.d + row * self.e,
+        )
+    }
+
+    /// the transform that applies `other` first and then this one, `self * other` in the
+    /// `affine` package
+    pub fn compose(&self, other: &Affine) -> Affine {
+        Affine {
+            a: self.a * other.a + self.b * other.d,
+            b: self.a * other.b + self.b * other.e,
+            c: self.a * other.c + self.b * other.f + self.c,
+            d: self.d * other.a + self.e * other.d,
+            e: self.d * other.b + self.e * other.e,
+            f: self.d * other.c + self.e * other.f + self.f,
+        }
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Affine::identity()
+    }
+}
+
+impl From<GeoTransform> for Affine {
+    fn from(geo_transform: GeoTransform) -> Self {
+        Affine::from_gdal(&geo_transform)
+    }
+}
+
+impl From<Affine> for GeoTransform {
+    fn from(affine: Affine) -> Self {
+        affine.to_gdal()
+    }
+}
+
+/// `self * other` applies `other` first, see `Affine::compose`
+impl Mul for Affine {
+    type Output = Affine;
+
+    fn mul(self, other: Affine) -> Affine {
+        self.compose(&other)
+    }
+}
+
+/// converts world coordinates to fractional pixel coordinates (column, row).
+/// solved directly instead of through `Affine::invert`, which rounds differently, so the offsets
+/// of the outputs stay the same
+pub fn world_to_pixel(geo_transform: &GeoTransform, x: f64, y: f64) -> (f64, f64) {
+    let det = geo_transform[1] * geo_transform[5] - geo_transform[2] * geo_transform[4];
+    let dx = x - geo_transform[0];
+    let dy = y - geo_transform[3];
+    (
+        (geo_transform[5] * dx - geo_transform[2] * dy) / det,
+        (geo_transform[1] * dy - geo_transform[4] * dx) / det,
+    )
+}
+
+/// converts pixel coordinates (column, row) to world coordinates, the inverse of `world_to_pixel`
+pub fn pixel_to_world(geo_transform: &GeoTransform, col: f64, row: f64) -> (f64, f64) {
+    Affine::from_gdal(geo_transform).apply(col, row)
+}
+
+/// the number of pixels added on each side of a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum Margin {
+    /// the same number of pixels on every side
+    Uniform(usize),
+    /// `x` pixels on the left and right and `y` pixels on the top and bottom, for rasters whose
+    /// pixels are not square
+    PerAxis { x: usize, y: usize },
+}
+
+impl Margin {
+    /// the pixels added on the left and on the right
+    pub fn x(&self) -> usize {
+        match *self {
+            Margin::Uniform(margin) => margin,
+            Margin::PerAxis { x, .. } => x,
+        }
+    }
+
+    /// the pixels added on the top and on the bottom
+    pub fn y(&self) -> usize {
+        match *self {
+            Margin::Uniform(margin) => margin,
+            Margin::PerAxis { y, .. } => y,
+        }
+    }
+
+    /// true when no pixels are added on any side
+    pub fn is_zero(&self) -> bool {
+        self.x() == 0 && self.y() == 0
+    }
+
+    /// the largest margin on the x and on the y axis for a reference of `reference_size` pixels:
+    /// the size of the reference, as a wider margin can only read fill, and at most half of
+    /// `MAX_RASTER_SIZE`
+    pub fn maximum(reference_size: (usize, usize)) -> (usize, usize) {
+        (
+            reference_size.0.min(MAX_RASTER_SIZE / 2),
+            reference_size.1.min(MAX_RASTER_SIZE / 2),
+        )
+    }
+
+    /// fails with `InvalidMargin` when the margin is larger than `Margin::maximum` for a
+    /// reference of `reference_size` pixels
+    pub fn validate(&self, reference_size: (usize, usize)) -> Result<(), InvalidMargin> {
+        let maximum = Margin::maximum(reference_size);
+        if self.x() > maximum.0 || self.y() > maximum.1 {
+            return Err(InvalidMargin {
+                margin: *self,
+                maximum,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl From<usize> for Margin {
+    fn from(margin: usize) -> Self {
+        Margin::Uniform(margin)
+    }
+}
+
+/// fails with `InvalidMargin` when a tile of `tile_size` pixels padded by `margin` would be
+/// larger than `MAX_RASTER_SIZE`
+pub(crate) fn check_padded_size(
+    tile_size: (usize, usize),
+    margin: Margin,
+) -> Result<(), InvalidMargin> {
+    let maximum = (
+        MAX_RASTER_SIZE.saturating_sub(tile_size.0) / 2,
+        MAX_RASTER_SIZE.saturating_sub(tile_size.1) / 2,
+    );
+    if margin.x() > maximum.0 || margin.y() > maximum.1 {
+        return Err(InvalidMargin { margin, maximum });
+    }
+    Ok(())
+}
+
+/// true when the columns of a raster run from west to east and its rows from north to south, the
+/// orientation gdal writes. south up rasters have a positive `geo_transform[5]`, rasters whose
+/// columns run west a negative `geo_transform[1]`
+pub fn is_north_up(geo_transform: &GeoTransform) -> bool {
+    geo_transform[1] > 0.0 && geo_transform[5] < 0.0
+}
+
+/// the geotransform of a raster of `size` pixels on `geo_transform` once its columns and rows are
+/// flipped to north up, see `is_north_up`
+pub fn north_up_geotransform(geo_transform: &GeoTransform, size: (usize, usize)) -> GeoTransform {
+    let mut north_up = *geo_transform;
+    if geo_transform[1] < 0.0 {
+        north_up[0] += size.0 as f64 * geo_transform[1];
+        north_up[1] = -geo_transform[1];
+    }
+    if geo_transform[5] > 0.0 {
+        north_up[3] += size.1 as f64 * geo_transform[5];
+        north_up[5] = -geo_transform[5];
+    }
+    north_up
+}
+
+/// the geotransform of a tile after `margin` pixels are added on every side
+pub fn padded_geotransform(geo_transform: &GeoTransform, margin: Margin) -> GeoTransform {
+    let shift = Affine::translation(-(margin.x() as f64), -(margin.y() as f64));
+    Affine::from_gdal(geo_transform).compose(&shift).to_gdal()
+}
+
+/// the extent of the output of padding the raster at `tile` by `margin` pixels, without reading
+/// or writing any pixels
+pub fn padded_extent(tile: &Path, margin: impl Into<Margin>) -> Result<GeoExtent, Box<dyn Error>> {
+    let margin = margin.into();
+    let ds = open_dataset(tile, &[])?;
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    Ok(padded_grid_extent(&geo_transform, ds.raster_size(), margin))
+}
+
+/// the extent of a raster of `size` pixels on `geo_transform` padded by `margin` pixels
+pub(crate) fn padded_grid_extent(
+    geo_transform: &GeoTransform,
+    size: (usize, usize),
+    margin: Margin,
+) -> GeoExtent {
+    let (cols, rows) = size;
+    GeoExtent::from_geotransform(
+        &padded_geotransform(geo_transform, margin),
+        (cols + 2 * margin.x(), rows + 2 * margin.y()),
+    )
+}
+
+/// the extent of the raster at `padded` less `margin` pixels on each side, the extent of the tile
+/// it was padded from
+pub fn trimmed_extent(
+    padded: &Path,
+    margin: impl Into<Margin>,
+) -> Result<GeoExtent, Box<dyn Error>> {
+    let margin = margin.into();
+    let ds = open_dataset(padded, &[])?;
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let (cols, rows) = ds.raster_size();
+    if cols <= 2 * margin.x() || rows <= 2 * margin.y() {
+        return Err(format!(
+            "{:?} is {}x{} pixels, too small to trim {} and {} pixels off its sides",
+            padded,
+            cols,
+            rows,
+            margin.x(),
+            margin.y()
+        )
+        .into());
+    }
+    let mut trimmed = geo_transform;
+    trimmed[0] += (margin.x() as f64) * geo_transform[1];
+    trimmed[3] += (margin.y() as f64) * geo_transform[5];
+    Ok(GeoExtent::from_geotransform(
+        &trimmed,
+        (cols - 2 * margin.x(), rows - 2 * margin.y()),
+    ))
+}
+
+/// the window of the reference raster covered by the padded tile, before it is clamped to the
+/// reference. the offsets are negative where the padded tile extends past the upper left corner
+pub fn padded_window(
+    padded_geotransform: &GeoTransform,
+    tile_size: (usize, usize),
+    margin: Margin,
+    reference_geotransform: &GeoTransform,
+    snapping: Snapping,
+) -> Result<PixelWindow, Misaligned> {
+    let (col, row) = world_to_pixel(
+        reference_geotransform,
+        padded_geotransform[0],
+        padded_geotransform[3],
+    );
+
+    // the size of the padded tile in pixels of the reference, which differ from the pixels of the
+    // tile when the resolutions differ
+    let x_scale = padded_geotransform[1].abs() / reference_geotransform[1].abs();
+    let y_scale = padded_geotransform[5].abs() / reference_geotransform[5].abs();
+    Ok(PixelWindow::new(
+        snapping.snap(col)?,
+        snapping.snap(row)?,
+        ((tile_size.0 + 2 * margin.x()) as f64 * x_scale).round() as usize,
+        ((tile_size.1 + 2 * margin.y()) as f64 * y_scale).round() as usize,
+    ))
+}
+
+/// the window of the reference raster that is read to pad a tile by `margin` pixels.
+/// padded_geotransform: the geotransform of the padded tile, see `padded_geotransform`
+/// tile_size: the size of the tile before padding
+/// the offsets are snapped and clamped to the upper left corner of the reference, the size is
+/// clamped so the window does not exceed the lower right corner of the reference
+pub fn window_for_margin(
+    padded_geotransform: &GeoTransform,
+    tile_size: (usize, usize),
+    margin: Margin,
+    reference_geotransform: &GeoTransform,
+    reference_size: (usize, usize),
+    snapping: Snapping,
+) -> Result<PixelWindow, Misaligned> {
+    let padded = padded_window(
+        padded_geotransform,
+        tile_size,
+        margin,
+        reference_geotransform,
+        snapping,
+    )?;
+    let x_offset = padded.x_offset.max(0);
+    let y_offset = padded.y_offset.max(0);
+
+    // Make sure we don't exceed the raster dimensions
+    let cols = (reference_size.0 as isize - x_offset).min(padded.cols as isize);
+    let rows = (reference_size.1 as isize - y_offset).min(padded.rows as isize);
+
+    Ok(PixelWindow::new(
+        x_offset,
+        y_offset,
+        cols.max(0) as usize,
+        rows.max(0) as usize,
+    ))
+}
+
+/// the size of `window`, a window of the reference raster, in pixels of the grid described by
+/// `geo_transform`. this is the size the window is resampled to when the resolutions differ and
+/// the size of the window itself when they match
+pub fn resampled_size(
+    window: &PixelWindow,
+    geo_transform: &GeoTransform,
+    reference_geotransform: &GeoTransform,
+) -> (usize, usize) {
+    let x_scale = reference_geotransform[1].abs() / geo_transform[1].abs();
+    let y_scale = reference_geotransform[5].abs() / geo_transform[5].abs();
+    (
+        (window.cols as f64 * x_scale).round() as usize,
+        (window.rows as f64 * y_scale).round() as usize,
+    )
+}
+
+/// the distance along a hilbert curve through an `n` by `n` grid, `n` a power of two, of the cell
+/// (x, y). cells that are close on the curve are close in space
+pub fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut index = 0u64;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = u32::from(x & s > 0);
+        let ry = u32::from(y & s > 0);
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // rotate the quadrant so the curve continues where the previous one ended
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// the strips of a window of `window_size` pixels around `center`, which is given in pixels of
+/// the window and must lie inside it. the top and bottom strips span the full width, the left and
+/// right strips only the rows of the center. empty strips are left out
+pub fn margin_strips(window_size: (usize, usize), center: &PixelWindow) -> Vec<PixelWindow> {
+    let (cols, rows) = window_size;
+    let (x, y) = (center.x_offset as usize, center.y_offset as usize);
+    let right = x + center.cols;
+    let bottom = y + center.rows;
+    [
+        PixelWindow::new(0, 0, cols, y),
+        PixelWindow::new(0, bottom as isize, cols, rows - bottom),
+        PixelWindow::new(0, y as isize, x, center.rows),
+        PixelWindow::new(right as isize, y as isize, cols - right, center.rows),
+    ]
+    .into_iter()
+    .filter(|strip| strip.cols > 0 && strip.rows > 0)
+    .collect()
+}
+
+/// the window covering `extent` on the pixel grid described by `geo_transform`.
+/// the offsets are snapped and the size is rounded to whole pixels, the window is not clamped
+pub fn window_for_extent(
+    geo_transform: &GeoTransform,
+    extent: &GeoExtent,
+    snapping: Snapping,
+) -> Result<PixelWindow, Misaligned> {
+    // the first pixel is at the west and north edge unless the raster is flipped
+    let x = if geo_transform[1] < 0.0 {
+        extent.max_x
+    } else {
+        extent.min_x
+    };
+    let y = if geo_transform[5] > 0.0 {
+        extent.min_y
+    } else {
+        extent.max_y
+    };
+    let (col, row) = world_to_pixel(geo_transform, x, y);
+    Ok(PixelWindow::new(
+        snapping.snap(col)?,
+        snapping.snap(row)?,
+        (extent.width() / geo_transform[1].abs()).round() as usize,
+        (extent.height() / geo_transform[5].abs()).round() as usize,
+    ))
+}
+
+/// a rectangle in the coordinate system of a raster.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeoExtent {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl GeoExtent {
+    /// creates an extent from explicit coordinates
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        GeoExtent {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// the extent covered by a raster of `size` (cols, rows) pixels with `geo_transform`
+    pub fn from_geotransform(geo_transform: &GeoTransform, size: (usize, usize)) -> Self {
+        let (cols, rows) = size;
+        let x_end = geo_transform[0] + cols as f64 * geo_transform[1];
+        let y_end = geo_transform[3] + rows as f64 * geo_transform[5];
+
+        GeoExtent::new(
+            geo_transform[0].min(x_end),
+            geo_transform[3].min(y_end),
+            geo_transform[0].max(x_end),
+            geo_transform[3].max(y_end),
+        )
+    }
+
+    /// the extent covered by an opened dataset
+    pub fn from_dataset(ds: &Dataset) -> Result<Self, Box<dyn Error>> {
+        match ds.geo_transform() {
+            Ok(geo_transform) => Ok(GeoExtent::from_geotransform(
+                &geo_transform,
+                ds.raster_size(),
+            )),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// the extent covered by the raster file at `path`
+    pub fn from_raster(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let ds = open_dataset(path, &[])?;
+        GeoExtent::from_dataset(&ds)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    /// the corners counter clockwise from the lower left, closed by repeating the first
+    pub fn ring(&self) -> [(f64, f64); 5] {
+        [
+            (self.min_x, self.min_y),
+            (self.max_x, self.min_y),
+            (self.max_x, self.max_y),
+            (self.min_x, self.max_y),
+            (self.min_x, self.min_y),
+        ]
+    }
+
+    /// the extent as a wkt polygon
+    pub fn to_wkt(&self) -> String {
+        let points: Vec<String> = self
+            .ring()
+            .iter()
+            .map(|(x, y)| format!("{} {}", x, y))
+            .collect();
+        format!("POLYGON (({}))", points.join(", "))
+    }
+
+    /// true when the two extents share some area, touching edges do not count
+    pub fn intersects(&self, other: &GeoExtent) -> bool {
+        self.min_x < other.max_x
+            && other.min_x < self.max_x
+            && self.min_y < other.max_y
+            && other.min_y < self.max_y
+    }
+}
+
+impl fmt::Display for GeoExtent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.min_x, self.min_y, self.max_x, self.max_y
+        )
+    }
+}
+
+/// parses a bbox in the form `min_x,min_y,max_x,max_y`
+impl FromStr for GeoExtent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("invalid bbox {:?}: {}", s, e))?;
+        match values[..] {
+            [min_x, min_y, max_x, max_y] if min_x < max_x && min_y < max_y => {
+                Ok(GeoExtent::new(min_x, min_y, max_x, max_y))
+            }
+            _ => Err(format!(
+                "invalid bbox {:?}: expected min_x,min_y,max_x,max_y",
+                s
+            )),
+        }
+    }
+}
+
+/// with the `geo` feature: an extent from a `geo_types::Rect`
+#[cfg(feature = "geo")]
+impl From<geo_types::Rect<f64>> for GeoExtent {
+    fn from(rect: geo_types::Rect<f64>) -> Self {
+        GeoExtent::new(rect.min().x, rect.min().y, rect.max().x, rect.max().y)
+    }
+}
+
+#[cfg(feature = "geo")]
+impl From<GeoExtent> for geo_types::Rect<f64> {
+    fn from(extent: GeoExtent) -> Self {
+        geo_types::Rect::new(
+            geo_types::coord! { x: extent.min_x, y: extent.min_y },
+            geo_types::coord! { x: extent.max_x, y: extent.max_y },
+        )
+    }
+}
+
+#[cfg(feature = "geo")]
+impl From<GeoExtent> for geo_types::Polygon<f64> {
+    fn from(extent: GeoExtent) -> Self {
+        geo_types::Rect::from(extent).to_polygon()
+    }
+}
+
+/// with the `geo` feature: the bounding box of the exterior of a polygon, e.g. an area of
+/// interest, fails for an empty polygon
+#[cfg(feature = "geo")]
+impl TryFrom<&geo_types::Polygon<f64>> for GeoExtent {
+    type Error = String;
+
+    fn try_from(polygon: &geo_types::Polygon<f64>) -> Result<Self, Self::Error> {
+        let mut coords = polygon.exterior().coords();
+        let first = match coords.next() {
+            Some(first) => first,
+            None => return Err("an empty polygon has no extent".to_string()),
+        };
+        let extent = GeoExtent::new(first.x, first.y, first.x, first.y);
+        Ok(coords.fold(extent, |extent, coord| {
+            GeoExtent::new(
+                extent.min_x.min(coord.x),
+                extent.min_y.min(coord.y),
+                extent.max_x.max(coord.x),
+                extent.max_y.max(coord.y),
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEO_TRANSFORM: GeoTransform = [1000.0, 2.0, 0.0, 5000.0, 0.0, -2.0];
+
+    #[test]
+    fn world_and_pixel_round_trip() {
+        assert_eq!(world_to_pixel(&GEO_TRANSFORM, 1010.0, 4990.0), (5.0, 5.0));
+        assert_eq!(pixel_to_world(&GEO_TRANSFORM, 5.0, 5.0), (1010.0, 4990.0));
+    }
+
+    #[test]
+    fn affine_converts_inverts_and_composes() {
+        let affine = Affine::from_gdal(&GEO_TRANSFORM);
+        assert_eq!(affine, Affine::new(2.0, 0.0, 1000.0, 0.0, -2.0, 5000.0));
+        assert_eq!(affine.to_gdal(), GEO_TRANSFORM);
+        assert_eq!(affine.apply(5.0, 5.0), (1010.0, 4990.0));
+        let inverse = affine.invert().unwrap();
+        assert_eq!(inverse.apply(1010.0, 4990.0), (5.0, 5.0));
+        assert_eq!(affine * inverse, Affine::identity());
+        // the origin 4 pixels to the left
+        let shifted = affine.compose(&Affine::translation(-4.0, 0.0));
+        assert_eq!(shifted.to_gdal(), [992.0, 2.0, 0.0, 5000.0, 0.0, -2.0]);
+        assert_eq!(
+            (Affine::scale(2.0, 3.0) * Affine::translation(1.0, 1.0)).apply(0.0, 0.0),
+            (2.0, 3.0)
+        );
+        assert!(Affine::scale(0.0, 1.0).invert().is_none());
+    }
+
+    #[test]
+    fn margin_is_validated_against_the_reference_and_gdal_limits() {
+        assert_eq!(
+            Margin::Uniform(100)
+                .validate((100, 50))
+                .unwrap_err()
+                .maximum,
+            (100, 50)
+        );
+        assert!(Margin::PerAxis { x: 100, y: 50 }
+            .validate((100, 50))
+            .is_ok());
+        assert_eq!(Margin::maximum((usize::MAX, 10)), (MAX_RASTER_SIZE / 2, 10));
+        assert!(check_padded_size((16, 16), Margin::Uniform(MAX_RASTER_SIZE / 2)).is_err());
+        assert!(check_padded_size((16, 16), Margin::Uniform(4)).is_ok());
+    }
+
+    #[test]
+    fn flipped_rasters_become_north_up() {
+        let south_up = [1000.0, 2.0, 0.0, 4900.0, 0.0, 2.0];
+        assert!(!is_north_up(&south_up));
+        assert_eq!(north_up_geotransform(&south_up, (10, 50)), GEO_TRANSFORM);
+        let west = [1020.0, -2.0, 0.0, 5000.0, 0.0, -2.0];
+        assert_eq!(north_up_geotransform(&west, (10, 50)), GEO_TRANSFORM);
+        assert!(is_north_up(&north_up_geotransform(&west, (10, 50))));
+    }
+
+    #[test]
+    fn window_for_extent_of_flipped_rasters() {
+        let extent = GeoExtent::new(1004.0, 4980.0, 1010.0, 4990.0);
+        let south_up = [1000.0, 2.0, 0.0, 4900.0, 0.0, 2.0];
+        let window = window_for_extent(&south_up, &extent, Snapping::RoundHalfUp).unwrap();
+        assert_eq!(window, PixelWindow::new(2, 40, 3, 5));
+        let west = [1020.0, -2.0, 0.0, 5000.0, 0.0, -2.0];
+        let window = window_for_extent(&west, &extent, Snapping::RoundHalfUp).unwrap();
+        assert_eq!(window, PixelWindow::new(5, 5, 3, 5));
+    }
+
+    #[test]
+    fn padded_geotransform_moves_origin_up_and_left() {
+        let padded = padded_geotransform(&GEO_TRANSFORM, Margin::Uniform(10));
+        assert_eq!(padded, [980.0, 2.0, 0.0, 5020.0, 0.0, -2.0]);
+    }
+
+    #[test]
+    fn window_for_margin_inside_reference() {
+        let tile = [1200.0, 2.0, 0.0, 4800.0, 0.0, -2.0];
+        let window = window_for_margin(
+            &padded_geotransform(&tile, Margin::Uniform(10)),
+            (50, 50),
+            Margin::Uniform(10),
+            &GEO_TRANSFORM,
+            (1000, 1000),
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(90, 90, 70, 70));
+    }
+
+    #[test]
+    fn window_for_margin_per_axis() {
+        let tile = [1200.0, 1.0, 0.0, 4800.0, 0.0, -2.0];
+        let margin = Margin::PerAxis { x: 20, y: 10 };
+        let padded = padded_geotransform(&tile, margin);
+        assert_eq!(padded, [1180.0, 1.0, 0.0, 4820.0, 0.0, -2.0]);
+        let reference = [1000.0, 1.0, 0.0, 5000.0, 0.0, -2.0];
+        let window = window_for_margin(
+            &padded,
+            (50, 50),
+            margin,
+            &reference,
+            (1000, 1000),
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(180, 90, 90, 70));
+    }
+
+    #[test]
+    fn window_for_margin_on_coarser_reference() {
+        // 1 m tile pixels against a 2 m reference
+        let tile = [1200.0, 1.0, 0.0, 4800.0, 0.0, -1.0];
+        let padded = padded_geotransform(&tile, Margin::Uniform(10));
+        let window = window_for_margin(
+            &padded,
+            (50, 50),
+            Margin::Uniform(10),
+            &GEO_TRANSFORM,
+            (1000, 1000),
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(95, 95, 35, 35));
+        assert_eq!(resampled_size(&window, &padded, &GEO_TRANSFORM), (70, 70));
+    }
+
+    #[test]
+    fn window_for_margin_on_finer_reference() {
+        // 10 m tile pixels against a 1 m reference, the window covers ten times the pixels
+        let tile = [1000.0, 10.0, 0.0, 5000.0, 0.0, -10.0];
+        let reference = [0.0, 1.0, 0.0, 10000.0, 0.0, -1.0];
+        let padded = padded_geotransform(&tile, Margin::Uniform(5));
+        let window = window_for_margin(
+            &padded,
+            (50, 50),
+            Margin::Uniform(5),
+            &reference,
+            (10000, 10000),
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(950, 4950, 600, 600));
+        assert_eq!(resampled_size(&window, &padded, &reference), (60, 60));
+    }
+
+    #[test]
+    fn window_for_margin_clamps_to_reference() {
+        let window = window_for_margin(
+            &padded_geotransform(&GEO_TRANSFORM, Margin::Uniform(10)),
+            (50, 50),
+            Margin::Uniform(10),
+            &GEO_TRANSFORM,
+            (60, 1000),
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(0, 0, 60, 70));
+    }
+
+    #[test]
+    fn padded_window_extends_past_upper_left() {
+        let window = padded_window(
+            &padded_geotransform(&GEO_TRANSFORM, Margin::Uniform(10)),
+            (50, 50),
+            Margin::Uniform(10),
+            &GEO_TRANSFORM,
+            Snapping::default(),
+        )
+        .unwrap();
+        assert_eq!(window, PixelWindow::new(-10, -10, 70, 70));
+    }
+
+    #[test]
+    fn hilbert_index_visits_neighbors_in_turn() {
+        let order: Vec<u64> = [(0, 0), (0, 1), (1, 1), (1, 0)]
+            .iter()
+            .map(|&(x, y)| hilbert_index(2, x, y))
+            .collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+        assert_eq!(hilbert_index(4, 3, 0), 15);
+    }
+
+    #[test]
+    fn margin_strips_cover_window_around_center() {
+        let strips = margin_strips((70, 60), &PixelWindow::new(10, 0, 50, 50));
+        assert_eq!(
+            strips,
+            vec![
+                PixelWindow::new(0, 50, 70, 10),
+                PixelWindow::new(0, 0, 10, 50),
+                PixelWindow::new(60, 0, 10, 50),
+            ]
+        );
+        let area: usize = strips.iter().map(|strip| strip.cols * strip.rows).sum();
+        assert_eq!(area, 70 * 60 - 50 * 50);
+    }
+
+    #[test]
+    fn window_for_extent_of_inner_tile() {
+        let extent = GeoExtent::new(1020.0, 4880.0, 1120.0, 4980.0);
+        let window = window_for_extent(&GEO_TRANSFORM, &extent, Snapping::default()).unwrap();
+        assert_eq!(window, PixelWindow::new(10, 10, 50, 50));
+        assert!(window.is_within((60, 60)));
+        assert!(!window.is_within((59, 60)));
+    }
+
+    #[test]
+    fn window_for_extent_with_rectangular_pixels() {
+        // 0.5 map units wide and 1 map unit tall
+        let geo_transform = [1000.0, 0.5, 0.0, 5000.0, 0.0, -1.0];
+        let extent = GeoExtent::new(1010.0, 4960.0, 1035.0, 4990.0);
+        let window = window_for_extent(&geo_transform, &extent, Snapping::default()).unwrap();
+        assert_eq!(window, PixelWindow::new(20, 10, 50, 30));
+    }
+
+    #[test]
+    fn padded_grid_extent_with_rectangular_pixels() {
+        let geo_transform = [1000.0, 0.5, 0.0, 5000.0, 0.0, -1.0];
+        let extent = padded_grid_extent(&geo_transform, (40, 20), Margin::Uniform(4));
+        assert_eq!(extent, GeoExtent::new(998.0, 4976.0, 1022.0, 5004.0));
+    }
+
+    #[test]
+    fn snapping_modes() {
+        assert_eq!(Snapping::Floor.snap(9.9999), Ok(9));
+        assert_eq!(Snapping::RoundHalfUp.snap(9.9999), Ok(10));
+        assert_eq!(Snapping::RoundHalfUp.snap(9.5), Ok(10));
+        assert_eq!(Snapping::RoundHalfUp.snap(-0.5), Ok(0));
+        let strict = Snapping::ErrorIfMisaligned { tolerance: 0.01 };
+        assert_eq!(strict.snap(10.001), Ok(10));
+        assert!(strict.snap(10.5).is_err());
+    }
+
+    #[test]
+    fn half_pixel_origin_is_deterministic() {
+        // the tile origin sits half a pixel right of and below the reference grid
+        let tile = [1201.0, 2.0, 0.0, 4799.0, 0.0, -2.0];
+        let padded = padded_geotransform(&tile, Margin::Uniform(10));
+        let window = |snapping| {
+            window_for_margin(
+                &padded,
+                (50, 50),
+                Margin::Uniform(10),
+                &GEO_TRANSFORM,
+                (1000, 1000),
+                snapping,
+            )
+        };
+        assert_eq!(window(Snapping::Floor).unwrap().offset(), (90, 90));
+        assert_eq!(window(Snapping::RoundHalfUp).unwrap().offset(), (91, 91));
+        let strict = Snapping::ErrorIfMisaligned { tolerance: 0.1 };
+        assert!(window(strict).is_err());
+    }
+
+    #[test]
+    fn snapping_from_string() {
+        assert_eq!("floor".parse(), Ok(Snapping::Floor));
+        assert_eq!("round".parse(), Ok(Snapping::RoundHalfUp));
+        assert_eq!(
+            "error:0.01".parse(),
+            Ok(Snapping::ErrorIfMisaligned { tolerance: 0.01 })
+        );
+        assert!("error:abc".parse::<Snapping>().is_err());
+    }
+
+    #[test]
+    fn extent_from_bbox_string() {
+        let extent: GeoExtent = "0,1,10,11".parse().unwrap();
+        assert_eq!(extent, GeoExtent::new(0.0, 1.0, 10.0, 11.0));
+        assert!("10,1,0,11".parse::<GeoExtent>().is_err());
+        assert!("0,1,10".parse::<GeoExtent>().is_err());
+    }
+
+    #[test]
+    fn extents_touching_do_not_intersect() {
+        let a = GeoExtent::new(0.0, 0.0, 10.0, 10.0);
+        assert!(a.intersects(&GeoExtent::new(5.0, 5.0, 15.0, 15.0)));
+        assert!(!a.intersects(&GeoExtent::new(10.0, 0.0, 20.0, 10.0)));
+    }
+
+    #[test]
+    fn padded_extent_as_wkt() {
+        let padded = padded_geotransform(&GEO_TRANSFORM, Margin::PerAxis { x: 10, y: 5 });
+        let extent = GeoExtent::from_geotransform(&padded, (120, 110));
+        assert_eq!(extent, GeoExtent::new(980.0, 4790.0, 1220.0, 5010.0));
+        assert_eq!(
+            extent.to_wkt(),
+            "POLYGON ((980 4790, 1220 4790, 1220 5010, 980 5010, 980 4790))"
+        );
+    }
+
+    #[cfg(feature = "geo")]
+    #[test]
+    fn extent_from_geo_types() {
+        let extent = GeoExtent::new(980.0, 4790.0, 1220.0, 5010.0);
+        let rect = geo_types::Rect::from(extent);
+        assert_eq!(GeoExtent::from(rect), extent);
+
+        let polygon = geo_types::Polygon::new(
+            vec![(1000.0, 4800.0), (1200.0, 4900.0), (1100.0, 5000.0)].into(),
+            vec![],
+        );
+        assert_eq!(
+            GeoExtent::try_from(&polygon),
+            Ok(GeoExtent::new(1000.0, 4800.0, 1200.0, 5000.0))
+        );
+        assert_eq!(
+            GeoExtent::try_from(&geo_types::Polygon::from(extent)),
+            Ok(extent)
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! the raster tables of geopackages as tiles.
+//!
+//! every raster table of a geopackage is a tile of its own. a table is opened with the gdal
+//! connection string `GPKG:<file>:<table>`, which stands in for the path of the tile wherever the
+//! crate takes one, and the tile is named after its table, so the tables `a` and `b` of
+//! `tiles.gpkg` are padded to `a.tif` and `b.tif`.
+use crate::path::{last_error, open_vector_dataset};
+use gdal_sys::{
+    GDALDatasetExecuteSQL, GDALDatasetReleaseResultSet, OGR_F_Destroy, OGR_F_GetFieldAsString,
+    OGR_L_GetNextFeature,
+};
+use std::{
+    error::Error,
+    ffi::{CStr, CString, OsStr, OsString},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+const PREFIX: &str = "GPKG:";
+
+/// tiled rasters and gridded coverages are both raster tables
+const RASTER_TABLES: &str = "SELECT table_name FROM gpkg_contents \
+     WHERE data_type IN ('tiles', '2d-gridded-coverage') ORDER BY table_name";
+
+pub(crate) fn is_geopackage(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gpkg"))
+}
+
+/// the path that opens `table` of the geopackage `file`
+pub(crate) fn table_path(file: &Path, table: &str) -> PathBuf {
+    let mut path = OsString::from(PREFIX);
+    path.push(file.as_os_str());
+    path.push(":");
+    path.push(table);
+    PathBuf::from(path)
+}
+
+/// the geopackage and the table of a path built by `table_path`
+pub(crate) fn split_table_path(path: &Path) -> Option<(&Path, &str)> {
+    let (file, table) = path.to_str()?.strip_prefix(PREFIX)?.rsplit_once(':')?;
+    Some((Path::new(file), table))
+}
+
+/// the file a tile is stored in, the geopackage for a table
+pub(crate) fn file_of(path: &Path) -> &Path {
+    split_table_path(path).map_or(path, |(file, _)| file)
+}
+
+/// the file name of a tile, `<table>.gpkg` for a table of a geopackage
+pub(crate) fn tile_file_name(path: &Path) -> Option<OsString> {
+    match split_table_path(path) {
+        Some((_, table)) => Some(OsString::from(format!("{}.gpkg", table))),
+        None => path.file_name().map(OsStr::to_os_string),
+    }
+}
+
+/// the paths of the raster tables of the geopackage `file`, only those named in `tables` unless
+/// it is empty
+pub(crate) fn table_paths(file: &Path, tables: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    Ok(raster_tables(file)?
+        .into_iter()
+        .filter(|table| tables.is_empty() || tables.contains(table))
+        .map(|table| table_path(file, &table))
+        .collect())
+}
+
+fn raster_tables(file: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let ds = open_vector_dataset(file)?;
+    let sql = CString::new(RASTER_TABLES)?;
+    let result = unsafe {
+        GDALDatasetExecuteSQL(ds.c_dataset(), sql.as_ptr(), ptr::null_mut(), ptr::null())
+    };
+    if result.is_null() {
+        return Err(last_error(&format!(
+            "could not list the raster tables of {:?}",
+            file
+        )));
+    }
+
+    let mut tables = Vec::new();
+    loop {
+        let feature = unsafe { OGR_L_GetNextFeature(result) };
+        if feature.is_null() {
+            break;
+        }
+        let table = unsafe { CStr::from_ptr(OGR_F_GetFieldAsString(feature, 0)) };
+        tables.push(table.to_string_lossy().into_owned());
+        unsafe { OGR_F_Destroy(feature) };
+    }
+    unsafe { GDALDatasetReleaseResultSet(ds.c_dataset(), result) };
+    Ok(tables)
+}
@@ -0,0 +1,145 @@
+//! padded tiles of a mosaic that is not split into tiles yet, e.g. a single large raster or a vrt
+//! of scenes on a common grid, for chunking a mosaic into overlapping pieces.
+//!
+//! the mosaic is split into a grid of tiles of `tile_size` pixels that starts at its upper left
+//! corner, the tiles of the last row and column are cut at the edge of the mosaic. every tile is
+//! a small vrt over the mosaic in gdal's in memory file system that is padded like a tile of a
+//! directory, the outputs are named `tile_<row>_<col>`.
+use crate::{
+    absolute_path, check_driver, data_type_name,
+    geometry::{pixel_to_world, GeoTransform, Margin, PixelWindow},
+    messages::message,
+    pad_tiles,
+    palette::copy_palettes,
+    path::open_raster_for_update,
+    virtual_tile::{virtual_tile_xml, VirtualBand},
+    BatchReport, GeoExtent, OutputFormat, PadOptions, Resampling, VrtSource,
+};
+use gdal::{
+    vsi::{create_mem_file, unlink_mem_file},
+    Dataset,
+};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// numbers the in memory directories of the grid tiles of this process
+static GRIDS: AtomicUsize = AtomicUsize::new(0);
+
+/// splits `reference` into tiles of `tile_size` pixels and pads each of them from `reference`
+/// into `output_dir`, like `vrt_buffer_with_options` with a directory of such tiles. only the
+/// tiles that intersect `PadOptions::aoi` are padded when it is set.
+/// margin: a number of pixels or a `Margin`
+/// options: see `PadOptions`, the tiles can not be written as virtual vrts or resumed
+pub fn vrt_buffer_grid(
+    reference: &Path,
+    output_dir: &Path,
+    tile_size: usize,
+    margin: impl Into<Margin>,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    if tile_size == 0 {
+        return Err("the tile size must be at least 1 pixel".into());
+    }
+    if options.output.format == OutputFormat::VirtualVrt || options.output.resume {
+        return Err("the tiles of a grid can not be written as virtual vrts or resumed".into());
+    }
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+
+    let vrt = VrtSource::open(reference)?;
+    let mem_dir = format!(
+        "/vsimem/vrt_buffer_{}_grid_{}",
+        std::process::id(),
+        GRIDS.fetch_add(1, Ordering::Relaxed)
+    );
+    let tiles =
+        vrt.with_dataset(|ds| write_grid_tiles(ds, reference, &mem_dir, tile_size, options))?;
+
+    let source = reference.display().to_string();
+    let report = pad_tiles(
+        &tiles,
+        None,
+        output_dir,
+        &vrt,
+        &source,
+        margin.into(),
+        options,
+    );
+    for tile in &tiles {
+        if let Err(e) = unlink_mem_file(tile) {
+            message(&format!("Warning: could not remove {:?}: {}", tile, e));
+        }
+    }
+    report
+}
+
+/// writes a vrt of every tile of the grid over `ds` that intersects the area of interest to
+/// `mem_dir` and returns their paths
+fn write_grid_tiles(
+    ds: &Dataset,
+    reference: &Path,
+    mem_dir: &str,
+    tile_size: usize,
+    options: &PadOptions,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let projection = ds.projection();
+    let source = absolute_path(reference)?;
+    let (cols, rows) = ds.raster_size();
+
+    let mut tiles = Vec::new();
+    for row in 0..rows.div_ceil(tile_size) {
+        for col in 0..cols.div_ceil(tile_size) {
+            let (x, y) = (col * tile_size, row * tile_size);
+            let size = (tile_size.min(cols - x), tile_size.min(rows - y));
+            let tile_geotransform = tile_geo_transform(&geo_transform, (x, y));
+            if let Some(aoi) = &options.aoi {
+                if !GeoExtent::from_geotransform(&tile_geotransform, size).intersects(aoi) {
+                    continue;
+                }
+            }
+
+            let mut bands = Vec::new();
+            for band_index in 1..=ds.raster_count() {
+                let band = match ds.rasterband(band_index) {
+                    Ok(band) => band,
+                    Err(e) => return Err(Box::new(e)),
+                };
+                bands.push(VirtualBand {
+                    source: source.clone(),
+                    source_band: band_index,
+                    window: PixelWindow::new(x as isize, y as isize, size.0, size.1),
+                    data_type: data_type_name(&band),
+                    nodata: band.no_data_value(),
+                    resampling: Resampling::Nearest,
+                });
+            }
+            let xml =
+                virtual_tile_xml(size, &tile_geotransform, &projection, &bands, (0, 0), size)?;
+            let tile = Path::new(mem_dir).join(format!("tile_{}_{}.vrt", row, col));
+            if let Err(e) = create_mem_file(&tile, xml.into_bytes()) {
+                return Err(Box::new(e));
+            }
+            // the vrt driver writes the palettes into the vrt when the dataset is closed
+            let all_bands: Vec<isize> = (1..=ds.raster_count()).collect();
+            copy_palettes(ds, &open_raster_for_update(&tile)?, &all_bands)?;
+            tiles.push(tile);
+        }
+    }
+    Ok(tiles)
+}
+
+/// the geotransform of the tile whose upper left pixel is `(x, y)` of the mosaic
+fn tile_geo_transform(geo_transform: &GeoTransform, (x, y): (usize, usize)) -> GeoTransform {
+    let (origin_x, origin_y) = pixel_to_world(geo_transform, x as f64, y as f64);
+    let mut tile_geotransform = *geo_transform;
+    tile_geotransform[0] = origin_x;
+    tile_geotransform[3] = origin_y;
+    tile_geotransform
+}
@@ -0,0 +1,146 @@
+//! an index of the grids of the tiles of a directory, kept between runs.
+//!
+//! reading the geotransforms of thousands of tiles means opening every one of them, which is
+//! slow on network storage. the index keeps the grid of every tile in `.vrt_buffer_index.json`
+//! in the tile directory and only opens the tiles whose file changed since it was written, so
+//! filtering by an area of interest, finding the neighbors of a tile and `info` do not open the
+//! tiles again.
+use crate::{
+    geometry::{padded_grid_extent, GeoExtent, GeoTransform, Margin},
+    gpkg,
+    messages::message,
+    path::open_dataset,
+    raster_files,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// the name of the index file in the tile directory
+pub const INDEX_FILE_NAME: &str = ".vrt_buffer_index.json";
+
+/// the grid of a tile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedTile {
+    pub path: PathBuf,
+    /// the modification time of the file of the tile when it was indexed
+    modified_ns: u64,
+    pub geo_transform: GeoTransform,
+    /// (cols, rows)
+    pub size: (usize, usize),
+    pub bands: isize,
+    /// the wkt of the crs, empty when the tile has none
+    pub projection: String,
+}
+
+impl IndexedTile {
+    pub fn extent(&self) -> GeoExtent {
+        GeoExtent::from_geotransform(&self.geo_transform, self.size)
+    }
+
+    /// the extent of the tile padded by `margin`
+    pub fn padded_extent(&self, margin: Margin) -> GeoExtent {
+        padded_grid_extent(&self.geo_transform, self.size, margin)
+    }
+
+    fn read(path: &Path, modified_ns: u64) -> Result<Self, Box<dyn Error>> {
+        let ds = open_dataset(path, &[])?;
+        let geo_transform = match ds.geo_transform() {
+            Ok(geo_transform) => geo_transform,
+            Err(e) => return Err(Box::new(e)),
+        };
+        Ok(IndexedTile {
+            path: path.to_path_buf(),
+            modified_ns,
+            geo_transform,
+            size: ds.raster_size(),
+            bands: ds.raster_count(),
+            projection: ds.projection(),
+        })
+    }
+}
+
+/// the grids of the tiles of a directory, sorted by path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TileIndex {
+    tiles: Vec<IndexedTile>,
+}
+
+impl TileIndex {
+    /// the index of the rasters of `dir`, see `raster_files`. the tiles whose file did not change
+    /// since the index file of `dir` was written are taken from it, the others are opened. tiles
+    /// that can not be opened are left out with a warning.
+    /// persist: write the index file of `dir` when it is missing or out of date
+    pub fn load(dir: &Path, tables: &[String], persist: bool) -> Result<Self, Box<dyn Error>> {
+        let index_path = dir.join(INDEX_FILE_NAME);
+        // an unreadable index is rebuilt
+        let cached: TileIndex = fs::read_to_string(&index_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+
+        let mut tiles = Vec::new();
+        for path in raster_files(dir, tables)? {
+            let modified_ns = match modified_ns(&path) {
+                Ok(modified_ns) => modified_ns,
+                Err(e) => {
+                    message(&format!("Warning: could not index {:?}: {}", path, e));
+                    continue;
+                }
+            };
+            match cached.get(&path) {
+                Some(tile) if tile.modified_ns == modified_ns => tiles.push(tile.clone()),
+                _ => match IndexedTile::read(&path, modified_ns) {
+                    Ok(tile) => tiles.push(tile),
+                    Err(e) => message(&format!("Warning: could not index {:?}: {}", path, e)),
+                },
+            }
+        }
+
+        let index = TileIndex { tiles };
+        if persist && index != cached {
+            if let Err(e) = fs::write(&index_path, serde_json::to_string(&index)?) {
+                message(&format!(
+                    "Warning: could not write the index {:?}: {}",
+                    index_path, e
+                ));
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn tiles(&self) -> &[IndexedTile] {
+        &self.tiles
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&IndexedTile> {
+        self.tiles
+            .binary_search_by(|tile| tile.path.as_path().cmp(path))
+            .ok()
+            .map(|i| &self.tiles[i])
+    }
+
+    /// the tiles that intersect `extent`
+    pub fn intersecting(&self, extent: &GeoExtent) -> Vec<&IndexedTile> {
+        self.tiles
+            .iter()
+            .filter(|tile| tile.extent().intersects(extent))
+            .collect()
+    }
+
+    /// the tiles the margin of `tile` is read from, the ones that intersect its padded extent
+    /// including `tile` itself
+    pub fn neighbors(&self, tile: &IndexedTile, margin: Margin) -> Vec<&IndexedTile> {
+        self.intersecting(&tile.padded_extent(margin))
+    }
+}
+
+/// the modification time of the file of a tile, the geopackage for a table
+fn modified_ns(path: &Path) -> Result<u64, Box<dyn Error>> {
+    let modified = fs::metadata(gpkg::file_of(path))?.modified()?;
+    Ok(modified.duration_since(UNIX_EPOCH)?.as_nanos() as u64)
+}
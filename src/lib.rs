@@ -9,7 +9,7 @@
 //!
 //! ## Example
 //!
-//! ```
+//! ```no_run
 //! use std::path::Path;
 //! use vrt_buffer::vrt_buffer;
 //! use vrt_buffer::crop_down_to_size;
@@ -32,8 +32,153 @@
 //! - `vrt_buffer`: Adds a margin to geotiff files using a VRT file as a reference.
 //! - `crop_down_to_size`: Crops the buffered files back to the original size.
 //!
+//! `neighbor_buffer` pads the geotiff files from their neighbors in the same directory when there
+//! is no vrt file.
+//!
+//! Both return a `BatchReport` with the status of every tile, tiles that failed or were skipped
+//! (e.g. because they are outside of the vrt) do not stop the run.
+//!
+//! Outputs are written to a `.partial` file next to their final name and renamed once they are
+//! complete, so a file under the final name is never truncated by a crash.
+//!
+//! `analyze` checks a tile set against its vrt before a long run: projections, resolutions,
+//! misaligned tiles, tiles outside of the vrt and the expected output sizes.
+//!
+//! `TileIndex` keeps the grids of the tiles of a directory between runs, `PadOptions::aoi` uses it
+//! to pad only the tiles in an area of interest.
+//!
+//! `vrt_buffer_pyramid` pads the tiles at several resolutions at once, e.g. full, half and quarter
+//! resolution, for algorithms that work on an image pyramid.
+//!
+//! `vrt_buffer_grid` splits a mosaic into tiles of a given size and pads those, so no tiles have
+//! to exist beforehand.
+//!
+//! `pad_into_dataset` pads an opened tile into a dataset of the caller, e.g. a MEM dataset.
+//!
+//! `read_vrt_window` reads any extent of a vrt into buffers of a given size and data type.
+//!
+//! `mosaic_tiles` merges the cropped rasters back into a single raster once `check_mosaic` found
+//! no gaps or overlaps between them.
+//!
+//! `crop_to_extent` crops a single raster to any `GeoExtent`, taken from another raster or a bbox.
+//!
+//! With the `geo` feature `GeoExtent` converts to and from `geo_types::Rect` and `Polygon`, so an
+//! area of interest or an extent to crop to can come from the geo crates.
+//!
+//! `describe` returns the size, bands, data type, crs, geotransform, nodata value and block size
+//! of a raster, like `gdalinfo`, to check the outputs of a run.
+//!
+//! `diff_dirs` compares the rasters of two directories pixel by pixel within a tolerance, e.g. the
+//! cropped outputs with their originals.
+//!
+//! `Affine` wraps a geotransform with `invert`, `apply` and `compose` like python's `affine`
+//! package, the pads and crops compute their geotransforms with it.
+//!
+//! `Margin::validate` fails with `InvalidMargin` when a margin is larger than the reference, the
+//! pads check it before reading any tile.
+//!
+//! padded outputs record their margin under `MARGIN_METADATA_KEY`, `crop_fixed` fails the tiles
+//! that were padded with another margin.
+//!
+//! `compute_stats` returns the band statistics of a raster, the same statistics can be embedded in
+//! every output through `OutputOptions::statistics`.
+//!
+//! `OutputOptions::manifest` writes the sha256 of every output to `manifest.sha256` in the output
+//! directory, `verify_manifest` checks the files against it.
+//!
+//! `OutputOptions::archive` appends the outputs to a tar archive instead of leaving them as loose
+//! files.
+//!
+//! the directories and rasters of a run may be on gdal's in memory file system, e.g.
+//! `/vsimem/tiles`, so tests and embedded users can pad and crop without touching the disk.
+//!
+//! `OutputOptions::provenance` writes a `<name>.provenance.json` next to every output with its
+//! inputs and their sha256, the reference, the margin, the versions and the timings.
+//!
+//! `OutputOptions::copy_non_raster_files` copies the other files of the input directory, e.g. xml
+//! metadata and reports, to the output directory.
+//!
+//! `OutputFormat::Zarr` writes every output as a zarr store holding it in a single chunk, for
+//! machine learning pipelines that read the overlapping patches directly.
+//!
+//! `OutputOptions::tile_id` names the outputs by a `TileIdPattern` taken from the file names and
+//! pairs buffered rasters with their originals by it.
+//!
+//! `OutputOptions::duplicate_names` renames the outputs of inputs of the same name, e.g. tables of
+//! the same name in several geopackages, instead of failing all but the first.
+//!
+//! `OutputOptions::bands` writes a subset of the bands, e.g. the red and near infrared bands of a
+//! multispectral tile set.
+//!
+//! `OutputOptions::band_policy` gives single bands their own nodata value and `FillStrategy`,
+//! e.g. reflectance bands filled with 0 and a qa band filled with 255.
+//!
+//! `PadOptions::margin_overrides` pads single tiles by their own margin, e.g. from a csv or json
+//! file read by `read_margin_overrides`.
+//!
+//! `PadOptions::metadata_only` writes vrt stubs with the size and georeferencing of the padded
+//! tiles but no pixels, to check the footprints of a run before the real one.
+//!
+//! `PadOptions::fallback_vrt` fills the nodata margin pixels of the reference from a second
+//! mosaic, e.g. a coarser dem where the neighbor tiles are missing.
+//!
+//! `PadOptions::max_tiles` and `PadOptions::max_duration` bound a batch to a slice of the tiles,
+//! the same options exist on `CropOptions`.
+//!
+//! `PadOptions::file_list` and `CropOptions::file_list` restrict a batch to a list of rasters of
+//! the input directory, e.g. the tiles that changed since the last run.
+//!
+//! `TileListener::channel` sends a `TileEvent` to an mpsc channel when a tile is started, finished
+//! or failed, for progress displays and metrics.
+//!
+//! `PadOptions::tile_timeout` and `CropOptions::tile_timeout` fail a tile that runs too long with
+//! `TimedOut` and go on with the next one.
+//!
+//! `ErrorPolicy` stops a batch at the first failed tile or after a number of them instead of
+//! going on, see `PadOptions::error_policy` and `CropOptions::error_policy`.
+//!
+//! `CropOptions::chunk_rows` bounds the memory a crop needs for rasters that do not fit in memory.
+//!
+//! south up tiles and tiles whose columns run west are padded as north up tiles, the reference
+//! must be north up, see `geometry::is_north_up`.
+//!
+//! `CropOptions::fill_shortfall` fills the part of the original extent a buffered raster written
+//! by another tool does not cover with nodata.
+//!
+//! `GdalConfig` sets gdal configuration options such as `GDAL_CACHEMAX` before a batch and passes
+//! open options to the input rasters, see `PadOptions::gdal` and `CropOptions::gdal`.
+//!
+//! `pad_pairs` and `crop_pairs` take `(input, output)` pairs instead of directories, for callers
+//! that name the outputs or spread them over several directories themselves.
+//!
+//! `pad_process_crop` pads a tile set, runs a processing step on the padded rasters and crops the
+//! results back, with the intermediates in a temp directory of their own for each run.
+//!
+//! `BatchRunner` runs a `TileOperation` on every raster of a directory with the same threads,
+//! retries and report as the batches above, `PadOp` and `CropOp` are the pad and crop steps.
+//!
+//! `VrtSource` can be used to share the reference vrt between threads, each thread lazily opens its
+//! own gdal dataset.
+//!
+//! `ReferenceMosaic::open` reads the georeferencing, bands and nodata of the reference once, the
+//! handle is then passed to `vrt_buffer_with_reference` or `pad_into_dataset` for any number of
+//! runs without asking gdal for them again per tile.
+//!
 //! Refer to the individual function documentation for more details on their usage.
 //!
+//! With the `ffi` feature the crate builds a shared library with a c interface, see the `ffi`
+//! module and `include/vrt_buffer.h`.
+//!
+//! With the `tokio` feature `vrt_buffer_async` and `crop_async` run a batch on the blocking thread
+//! pool of a tokio runtime and stream the report of every tile as it finishes.
+//!
+//! With the `test-utils` feature the `test_utils` module writes synthetic mosaics with known pixel
+//! values for testing pipelines built on the crate, `cargo test --features test-utils` runs the
+//! round trip tests with them.
+//!
+//! With the `python` feature the crate builds a python module with `pad` and `crop`, build it with
+//! `maturin build --release`.
+//!
 //! ### Command line
 //! the clap framwork is also used to provide a command line interface for the crate.
 //! the easiest way to use the CLI is to run
@@ -53,275 +198,2781 @@
 //!
 //! ## Contributing
 //! Pull requests are welcome. KISS and YAGNI principles are followed.
-use gdal::{raster::RasterBand, Dataset, DriverManager};
-use std::{error::Error, fs, path::Path};
+mod analyze;
+mod archive;
+#[cfg(feature = "tokio")]
+mod async_batch;
+mod batch;
+mod calc;
+mod cancel;
+mod convert;
+mod data_type;
+mod describe;
+mod diff;
+mod disk;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod gdal_config;
+pub mod geometry;
+mod gpkg;
+mod grid;
+mod index;
+mod manifest;
+mod margin_metadata;
+mod mask;
+mod messages;
+mod mosaic;
+mod options;
+mod output;
+mod overrides;
+mod palette;
+mod path;
+mod pipeline;
+mod provenance;
+mod pyramid;
+#[cfg(feature = "python")]
+mod python;
+mod report;
+mod shard;
+mod sidecar;
+mod source;
+mod stac;
+mod state;
+mod stats;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+mod throttle;
+mod tile_id;
+mod virtual_tile;
+mod vsi;
+
+pub use analyze::{analyze, analyze_with_index, Analysis, ExpectedOutput};
+#[cfg(feature = "tokio")]
+pub use async_batch::{crop_async, vrt_buffer_async, TileStream};
+pub use batch::{BatchRunner, CropOp, PadOp, TileOperation};
+pub use calc::Calc;
+use cancel::{check_tile_deadline, FailureLimit, RunLimit, TileDeadline};
+pub use cancel::{CancelToken, TimedOut};
+pub use data_type::UnsupportedDataType;
+pub use describe::{describe, RasterDescription};
+pub use diff::{diff_dirs, diff_rasters, DirDiff, RasterDiff};
+pub use gdal_config::{GdalConfig, RetryPolicy};
+pub use geometry::{
+    padded_extent, trimmed_extent, Affine, GeoExtent, InvalidMargin, Margin, MarginBeyondReference,
+    MAX_RASTER_SIZE,
+};
+pub use grid::vrt_buffer_grid;
+pub use index::{IndexedTile, TileIndex, INDEX_FILE_NAME};
+pub use manifest::{verify_manifest, ManifestCheck, MANIFEST_FILE_NAME};
+pub use margin_metadata::MARGIN_METADATA_KEY;
+pub use messages::{set_message_sink, MessageSink};
+pub use mosaic::{check_mosaic, mosaic_tiles, MosaicCheck};
+pub use options::{
+    BandKind, BandPolicy, BigTiff, CompressionThreads, CropOptions, DiskSpaceCheck, DuplicateNames,
+    EdgeFill, ErrorPolicy, FillStrategy, MarginPolicy, MosaicOptions, OutOfRange, OutputFormat,
+    OutputOptions, OutputType, Overviews, PadOptions, PipelineOptions, Resampling, Rounding,
+    TileOrder,
+};
+pub use pipeline::{pad_process_crop, PipelineReport};
+pub use pyramid::vrt_buffer_pyramid;
+pub use report::{
+    BatchReport, BatchTotals, SkipReason, TileEvent, TileListener, TileOutcome, TileReport,
+    TileStatus, TrimmedMargin, ERROR_LOG_FILE_NAME,
+};
+pub use shard::ShardTemplate;
+pub use source::{ReferenceMosaic, VrtSource};
+pub use state::STATE_FILE_NAME;
+pub use stats::{compute_stats, BandStatistics, StatisticsMode};
+pub use tile_id::TileIdPattern;
+
+use archive::TarSink;
+use convert::{replace_nodata, BandConversion};
+use data_type::{check_data_types, data_type_name};
+use disk::{check_free_space, estimate_output_bytes};
+use gdal::{
+    raster::{Buffer, GdalType, RasterBand},
+    Dataset, Metadata,
+};
+use gdal_sys::{GDALCopyWords, GDALDataType};
+use geometry::{
+    check_padded_size, hilbert_index, is_north_up, margin_strips, north_up_geotransform,
+    padded_geotransform, padded_window, pixel_to_world, resampled_size, window_for_extent,
+    window_for_margin, world_to_pixel, GeoTransform, PixelWindow, Snapping,
+};
+use manifest::write_manifest;
+use margin_metadata::{
+    check_fixed_margin, check_trimmed_margin, recorded_margin, write_margin_metadata,
+};
+use mask::{
+    copy_color_interpretation, copy_dataset_mask, has_dataset_mask, is_empty, read_mask,
+    write_dataset_mask,
+};
+use messages::message;
+use output::{
+    check_driver, create_output, create_output_on_disk, finish_output, get_driver, output_bytes,
+    partial_path, remove_partial,
+};
+pub use overrides::read_margin_overrides;
+use overrides::tile_margin;
+use palette::{copy_palettes, is_categorical, output_data_type};
+use path::{create_copy, open_dataset};
+use provenance::write_provenance;
+use report::write_error_log;
+use sidecar::{copy_non_raster_files, copy_sidecars};
+use source::write_vrt;
+use state::StateFile;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    ffi::{c_void, OsStr},
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use throttle::IoLimit;
+use virtual_tile::{stub_tile_xml, virtual_tile_xml, write_virtual_tile, VirtualBand};
 
 /// adds a margin to the geotiff files in the input directory and saves them to the output directory.
-/// The margin is added by using the vrt file as a reference.
+/// The margin is read from the reference mosaic.
 /// input_dir: directory of the original files
 /// output_dir: directory to save the buffered files
-/// vrt_file: vrt file of the original files
-/// margin: size of the margin to add to the files
+/// reference: the mosaic of the original files, a vrt or any raster gdal can read, e.g. a large
+/// cog, an erdas .img or a gti tile index
+/// margin: size of the margin to add to the files, 0 rewrites the files with the output options
+/// without reading the vrt
 pub fn vrt_buffer(
     input_dir: &Path,
     output_dir: &Path,
-    vrt_file: &Path,
+    reference: &Path,
     margin: usize,
-) -> Result<(), Box<dyn Error>> {
-    // check if output directory exists and create it if not
-    fs::create_dir_all(output_dir)?;
-
-    // Load VRT once for efficiency
-    let vrt_ds = Dataset::open(vrt_file)?;
-    let vrt_band = vrt_ds.rasterband(1)?;
+) -> Result<BatchReport, Box<dyn Error>> {
+    vrt_buffer_with_options(
+        input_dir,
+        output_dir,
+        reference,
+        margin,
+        &PadOptions::default(),
+    )
+}
 
-    // Get the list of geotiff files in the input directory
-    let paths = fs::read_dir(input_dir)?;
+/// same as `vrt_buffer` but with control over how the padded files are written.
+/// margin: a number of pixels or a `Margin`, e.g. `Margin::PerAxis` for rasters whose pixels are
+/// not square
+/// options: see `PadOptions`
+pub fn vrt_buffer_with_options(
+    input_dir: &Path,
+    output_dir: &Path,
+    reference: &Path,
+    margin: impl Into<Margin>,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    options.gdal.apply()?;
+    // Open the reference once up front so a bad path fails the whole run, each worker thread
+    // then opens its own handle
+    let vrt = VrtSource::open(reference)?;
+    vrt_buffer_with_reference(input_dir, output_dir, &vrt, margin, options)
+}
 
-    // For each file in the directory, add margins and save to the output directory
+/// same as `vrt_buffer_with_options` but with a reference that is already open, for callers that
+/// pad several directories from the same mosaic without reading its georeferencing again
+/// reference: see `ReferenceMosaic::open`
+pub fn vrt_buffer_with_reference(
+    input_dir: &Path,
+    output_dir: &Path,
+    reference: &ReferenceMosaic,
+    margin: impl Into<Margin>,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    check_separate_dirs(input_dir, output_dir, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+    check_virtual_output(options)?;
 
-    for path in paths {
-        let path = match path {
-            Ok(path) => path.path(),
-            Err(_) => {
-                eprintln!("Error processing path. Skipping...");
-                continue;
-            }
-        };
-        if let Some(extension) = path.extension().and_then(std::ffi::OsStr::to_str) {
-            if extension == "tif" || extension == "tiff" {
-                let output_file_name = match path.file_name() {
-                    Some(file_name) => file_name,
-                    None => {
-                        eprintln!(
-                            "Could not compose a output file name based on {:?}. Skipping...",
-                            path
-                        );
-                        continue;
-                    }
-                };
-                let output_path = Path::new(output_dir).join(output_file_name);
-                match add_margin_to_geotiff(&path, &output_path, margin, &vrt_band, &vrt_ds) {
-                    Ok(_) => (),
-                    Err(_) => eprintln!("Error adding margin to geotiff. Skipping..."),
-                }
-            }
-        }
+    let (tiles, index) = select_tiles(input_dir, options)?;
+    let source = reference.path().display().to_string();
+    let report = pad_tiles(
+        &tiles,
+        index.as_ref(),
+        output_dir,
+        reference,
+        &source,
+        margin.into(),
+        options,
+    )?;
+    if options.output.copy_non_raster_files {
+        copy_non_raster_files(input_dir, output_dir)?;
     }
+    Ok(report)
+}
 
-    Ok(())
+/// pads the input of every `(input, output)` pair from `reference` into its output, for callers
+/// that map the inputs to the outputs themselves, e.g. across several directories or to renamed
+/// outputs. the pairs are padded in their order, the area of interest, file list and order of
+/// `options` do not apply and the outputs are not renamed.
+/// options: see `PadOptions`, resuming, stac items, error logs, manifests and archives need an
+/// output directory and fail
+pub fn pad_pairs(
+    pairs: impl IntoIterator<Item = (PathBuf, PathBuf)>,
+    reference: &Path,
+    margin: impl Into<Margin>,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    let pairs: Vec<(PathBuf, PathBuf)> = pairs.into_iter().collect();
+    check_pairs(&pairs, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+    check_virtual_output(options)?;
+
+    let margin = margin.into();
+    let vrt = VrtSource::open(reference)?;
+    let inputs: Vec<PathBuf> = pairs.iter().map(|(input, _)| input.clone()).collect();
+    check_margin(&inputs, &vrt, margin, options)?;
+    let source = reference.display().to_string();
+    pad_tile_pairs(pairs, None, &vrt, &source, margin, options)
 }
-/// takes a directory of the original directory with the tif files that where buffered and
-/// uses them as the reference to trim the buffered files to the original size
-/// org_dir: directory of the original files
-/// input_dir: directory of the buffered files
-/// output_dir: directory to save the trimmed files
-pub fn crop_down_to_size(
-    org_dir: &Path,
+
+/// adds a margin to the geotiff files in the input directory by reading it from their neighbors
+/// in the same directory, without a vrt file.
+/// the tiles are mosaicked into a vrt in gdal's in memory file system for the duration of the run.
+/// input_dir: directory of the original files
+/// output_dir: directory to save the buffered files
+/// margin: size of the margin to add to the files
+pub fn neighbor_buffer(
     input_dir: &Path,
     output_dir: &Path,
-) -> Result<(), Box<dyn Error>> {
-    fs::create_dir_all(output_dir)?;
-    let paths = fs::read_dir(input_dir)?;
-
-    for path in paths {
-        let path = match path {
-            Ok(path) => path.path(),
-            Err(_) => {
-                eprintln!("Error processing path. Skipping...");
-                continue;
-            }
-        };
-        if let Some(extension) = path.extension().and_then(std::ffi::OsStr::to_str) {
-            if extension == "tif" || extension == "tiff" {
-                let file_name = match path.file_name() {
-                    Some(file_name) => file_name,
-                    None => {
-                        eprintln!("Could not retrieve file name from {:?}. Skipping...", path);
-                        continue;
-                    }
-                };
-                let input_path = org_dir.join(file_name);
-                let output_path = output_dir.join(file_name);
-                match trim_buffered_to_size(&input_path, &path, &output_path) {
-                    Ok(_) => (),
-                    Err(_) => eprintln!("Error trimming buffered size. Skipping..."),
-                }
+    margin: usize,
+) -> Result<BatchReport, Box<dyn Error>> {
+    neighbor_buffer_with_options(input_dir, output_dir, margin, &PadOptions::default())
+}
+
+/// same as `neighbor_buffer` but with control over how the padded files are written.
+/// options: see `PadOptions`
+pub fn neighbor_buffer_with_options(
+    input_dir: &Path,
+    output_dir: &Path,
+    margin: impl Into<Margin>,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    check_separate_dirs(input_dir, output_dir, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+    check_virtual_output(options)?;
+    // the vrt of the neighbors only lives as long as the run, stubs do not read from it
+    if options.output.format == OutputFormat::VirtualVrt && !options.metadata_only {
+        return Err("virtual vrt outputs need a reference mosaic".into());
+    }
+
+    let margin = margin.into();
+    let (tiles, index) = select_tiles(input_dir, options)?;
+    if tiles.is_empty() {
+        return Err(format!("no rasters in {:?}", input_dir).into());
+    }
+    // with an area of interest the vrt only holds the tiles the margins are read from
+    let vrt_tiles: Vec<PathBuf> = match (&index, &options.aoi) {
+        (Some(index), Some(_)) => {
+            let mut neighbors = BTreeSet::new();
+            for tile in tiles.iter().filter_map(|tile| index.get(tile)) {
+                neighbors.extend(
+                    index
+                        .neighbors(
+                            tile,
+                            tile_margin(&options.margin_overrides, &tile.path, margin),
+                        )
+                        .into_iter()
+                        .map(|neighbor| neighbor.path.clone()),
+                );
             }
+            neighbors.into_iter().collect()
         }
+        // the listed tiles read their margins from every tile of the directory
+        _ if options.file_list.is_some() => raster_files(input_dir, &options.gdal.tables)?,
+        _ => tiles.clone(),
+    };
+    let vrt = VrtSource::in_memory(&vrt_tiles)?;
+    let report = pad_tiles(
+        &tiles,
+        index.as_ref(),
+        output_dir,
+        &vrt,
+        "neighbors",
+        margin,
+        options,
+    );
+    vrt.remove()?;
+    let report = report?;
+    if options.output.copy_non_raster_files {
+        copy_non_raster_files(input_dir, output_dir)?;
     }
+    Ok(report)
+}
 
-    Ok(())
+/// pads an opened tile from `vrt` into `target`, e.g. a MEM dataset, without opening or writing
+/// any files.
+/// target: must have the bands of the tile and the size of the padded tile, which is the tile
+/// plus `margin` on each side, smaller at the edges of the vrt with the default
+/// `MarginPolicy::Clamp`
+pub fn pad_into_dataset(
+    tile: &Dataset,
+    vrt: &VrtSource,
+    margin: impl Into<Margin>,
+    target: &mut Dataset,
+) -> Result<(), Box<dyn Error>> {
+    pad_into_dataset_with_options(tile, vrt, margin, target, &PadOptions::default())
 }
 
-fn add_margin_to_geotiff(
-    file_path: &Path,
-    output_path: &Path,
-    margin: usize,
-    vrt_band: &RasterBand,
-    vrt_ds: &Dataset,
+/// same as `pad_into_dataset` but with control over how the margin is read.
+/// of the output options of `PadOptions` only `bands` applies, the batch options do not
+pub fn pad_into_dataset_with_options(
+    tile: &Dataset,
+    vrt: &VrtSource,
+    margin: impl Into<Margin>,
+    target: &mut Dataset,
+    options: &PadOptions,
 ) -> Result<(), Box<dyn Error>> {
-    // Open the geotiff file
-    let ds = match Dataset::open(file_path) {
-        Ok(ds) => ds,
-        Err(e) => return Err(Box::new(e)),
-    };
+    // the description of a dataset opened from a file is its path
+    let description = tile.description().unwrap_or_default();
+    check_data_types(tile, Path::new(&description))?;
+    let mut band_vrts = Vec::new();
+    for (band_index, band_vrt_file) in &options.band_vrts {
+        band_vrts.push((*band_index, VrtSource::open(band_vrt_file)?));
+    }
+    let margin = margin.into();
+    margin.validate(vrt.size())?;
+    check_north_up_reference(vrt)?;
+    let north_up = north_up_copy(tile)?;
+    let tile = north_up.as_ref().unwrap_or(tile);
+    let padded = read_padded_tile(tile, vrt, &band_vrts, margin, options)?;
+    write_padded_tile(tile, &padded, target)
+}
 
-    // Get the original geotiff's data and metadata
-    let geotransform = match ds.geo_transform() {
-        Ok(geotransform) => geotransform,
-        Err(e) => return Err(Box::new(e)),
-    };
-    let projection = ds.projection();
+/// reads the pixels of `extent` from every band of `vrt`, resampled to `out_size`, e.g. the
+/// window around a field sample. the extent is snapped to the grid of the vrt like a padded tile
+/// and the part beyond the edge of the vrt follows the default `MarginPolicy::Clamp`, the buffers
+/// are then smaller than `out_size`.
+/// T: the data type the pixels are read as, gdal converts them
+pub fn read_vrt_window<T: GdalType + Copy + Default>(
+    vrt: &Path,
+    extent: GeoExtent,
+    out_size: (usize, usize),
+) -> Result<Vec<Buffer<T>>, Box<dyn Error>> {
+    read_vrt_window_with_options(vrt, extent, out_size, &PadOptions::default())
+}
 
-    // Compute expanded geotransform
-    let mut new_geotransform = geotransform;
-    new_geotransform[0] -= (margin as f64) * geotransform[1]; // x_origin
-    new_geotransform[3] -= (margin as f64) * geotransform[5]; // y_origin
+/// same as `read_vrt_window` but with the snapping, resampling, band kinds and margin policy of
+/// `options`
+pub fn read_vrt_window_with_options<T: GdalType + Copy + Default>(
+    vrt: &Path,
+    extent: GeoExtent,
+    out_size: (usize, usize),
+    options: &PadOptions,
+) -> Result<Vec<Buffer<T>>, Box<dyn Error>> {
+    read_window_of(&options.gdal.open(vrt)?, vrt, extent, out_size, options)
+}
 
-    // Read data from the VRT
+/// reads the pixels of `extent` from every band of the opened `vrt_ds` of `vrt`, see
+/// `read_vrt_window_with_options`
+fn read_window_of<T: GdalType + Copy + Default>(
+    vrt_ds: &Dataset,
+    vrt: &Path,
+    extent: GeoExtent,
+    out_size: (usize, usize),
+    options: &PadOptions,
+) -> Result<Vec<Buffer<T>>, Box<dyn Error>> {
     let vrt_geotransform = match vrt_ds.geo_transform() {
         Ok(vrt_geotransform) => vrt_geotransform,
         Err(e) => return Err(Box::new(e)),
     };
-    let xoff = ((new_geotransform[0] - vrt_geotransform[0]) / vrt_geotransform[1])
-        .max(0.0)
-        .floor() as isize;
-    let yoff = ((vrt_geotransform[3] - new_geotransform[3]) / vrt_geotransform[5].abs())
-        .max(0.0)
-        .floor() as isize;
-
-    // Make sure we don't exceed the raster dimensions
-    let cols =
-        (vrt_ds.raster_size().0 as isize - xoff).min((ds.raster_size().0 + 2 * margin) as isize);
-    let rows =
-        (vrt_ds.raster_size().1 as isize - yoff).min((ds.raster_size().1 + 2 * margin) as isize);
-
-    let new_data = match vrt_band.read_as::<f32>(
-        (xoff, yoff),
-        (cols as usize, rows as usize),
-        (cols as usize, rows as usize),
-        None,
-    ) {
-        Ok(new_data) => new_data,
-        Err(e) => return Err(Box::new(e)),
-    };
+    let requested = window_for_extent(&vrt_geotransform, &extent, options.snapping)?;
+    if requested.cols == 0 || requested.rows == 0 || out_size.0 == 0 || out_size.1 == 0 {
+        return Err(format!("the window of {:?} is empty", extent).into());
+    }
 
-    // Create a new geotiff file
-    let driver = match DriverManager::get_driver_by_name("GTiff") {
-        Ok(driver) => driver,
-        Err(e) => return Err(Box::new(e)),
-    };
+    // the part of the requested window inside of the vrt
+    let (vrt_cols, vrt_rows) = vrt_ds.raster_size();
+    let x_start = requested.x_offset.clamp(0, vrt_cols as isize);
+    let y_start = requested.y_offset.clamp(0, vrt_rows as isize);
+    let x_end = (requested.x_offset + requested.cols as isize).clamp(0, vrt_cols as isize);
+    let y_end = (requested.y_offset + requested.rows as isize).clamp(0, vrt_rows as isize);
+    if x_end <= x_start || y_end <= y_start {
+        return Err(format!("{:?} is outside of {:?}", extent, vrt).into());
+    }
+    let window = PixelWindow::new(
+        x_start,
+        y_start,
+        (x_end - x_start) as usize,
+        (y_end - y_start) as usize,
+    );
 
-    let mut new_ds = match driver.create_with_band_type::<f32, _>(
-        output_path.to_str().unwrap(),
-        cols as isize,
-        rows as isize,
-        1,
-    ) {
-        Ok(new_ds) => new_ds,
-        Err(e) => return Err(Box::new(e)),
+    // sizes in vrt pixels scaled to pixels of the output
+    let scaled = |cols: usize, rows: usize| {
+        (
+            (cols * out_size.0) as f64 / requested.cols as f64,
+            (rows * out_size.1) as f64 / requested.rows as f64,
+        )
     };
+    let (cols, rows) = scaled(window.cols, window.rows);
+    let buffer_size = (cols.round() as usize, rows.round() as usize);
+    let (x_before, y_before) = scaled(
+        (x_start - requested.x_offset) as usize,
+        (y_start - requested.y_offset) as usize,
+    );
+    let offset = (x_before.round() as usize, y_before.round() as usize);
+    if window != requested && options.margin_policy == MarginPolicy::Error {
+        return Err(Box::new(MarginBeyondReference {
+            reference: vrt.to_path_buf(),
+        }));
+    }
 
-    if let Err(e) = new_ds.set_geo_transform(&new_geotransform) {
-        return Err(Box::new(e));
-    };
+    let mut buffers = Vec::new();
+    for band_index in 1..=vrt_ds.raster_count() {
+        let band = match vrt_ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let resampling = if buffer_size == window.size() {
+            None
+        } else {
+            Some(band_resampling(vrt_ds, band_index, options)?.algorithm())
+        };
+        let data = options.gdal.retry.read_as::<T>(
+            &band,
+            window.offset(),
+            window.size(),
+            buffer_size,
+            resampling,
+        )?;
+        match &options.margin_policy {
+            MarginPolicy::Fill(edge_fill) if window != requested => {
+                let nodata = match options.output.band_policy_of(band_index) {
+                    Some(policy) => policy.nodata_value(band.no_data_value()),
+                    None => band.no_data_value(),
+                };
+                let fill = options.output.fill_value(band_index, nodata, edge_fill);
+                buffers.push(fill_around(&data, out_size, offset, convert_value(fill)));
+            }
+            _ => buffers.push(data),
+        }
+    }
+    Ok(buffers)
+}
 
-    if let Err(e) = new_ds.set_projection(&projection) {
-        return Err(Box::new(e));
+/// `value` converted to `T` by gdal, rounded and clamped to the range of `T`
+fn convert_value<T: GdalType + Copy + Default>(value: f64) -> T {
+    let mut converted = T::default();
+    unsafe {
+        GDALCopyWords(
+            &value as *const f64 as *const c_void,
+            GDALDataType::GDT_Float64,
+            0,
+            &mut converted as *mut T as *mut c_void,
+            T::gdal_ordinal(),
+            0,
+            1,
+        )
     };
+    converted
+}
 
-    let mut new_band = match new_ds.rasterband(1) {
-        Ok(new_band) => new_band,
-        Err(e) => return Err(Box::new(e)),
+/// the tiles of `input_dir` to pad and their index, which is only loaded when the options need
+/// it. with a file list or an area of interest only the tiles in it are padded, tiles missing
+/// from the index are kept so they are reported when they fail
+fn select_tiles(
+    input_dir: &Path,
+    options: &PadOptions,
+) -> Result<(Vec<PathBuf>, Option<TileIndex>), Box<dyn Error>> {
+    let tiles = listed_files(
+        raster_files(input_dir, &options.gdal.tables)?,
+        options.file_list.as_deref(),
+    )?;
+    if !options.index && options.aoi.is_none() && options.order != TileOrder::Hilbert {
+        return Ok((tiles, None));
+    }
+    let index = TileIndex::load(input_dir, &options.gdal.tables, options.index)?;
+    let tiles = match &options.aoi {
+        Some(aoi) => tiles
+            .into_iter()
+            .filter(|tile| {
+                index
+                    .get(tile)
+                    .is_none_or(|indexed| indexed.extent().intersects(aoi))
+            })
+            .collect(),
+        None => tiles,
     };
+    Ok((tiles, Some(index)))
+}
 
-    if let Err(e) = new_band.write((0, 0), (cols as usize, rows as usize), &new_data) {
-        return Err(Box::new(e));
-    };
+/// pads every tile from the vrt and saves it to the output directory.
+/// index: the grids of the tiles, needed to order them along a hilbert curve
+/// source: describes the vrt in the state file
+fn pad_tiles(
+    tiles: &[PathBuf],
+    index: Option<&TileIndex>,
+    output_dir: &Path,
+    vrt: &VrtSource,
+    source: &str,
+    margin: Margin,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    check_margin(tiles, vrt, margin, options)?;
+    check_run_files(output_dir, &options.output)?;
 
-    Ok(())
+    // check if output directory exists and create it if not
+    vsi::create_dir_all(output_dir)?;
+    let tiles = order_tiles(tiles, options.order, index);
+
+    // Pair each geotiff with the path it will be saved to
+    let mut tiles_and_outputs: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in &tiles {
+        let output_path = match options.output.output_path(output_dir, path) {
+            Ok(output_path) => output_path,
+            Err(e) => {
+                message(&format!(
+                    "Could not compose a output file name based on {:?}: {}. Skipping...",
+                    path, e
+                ));
+                continue;
+            }
+        };
+        tiles_and_outputs.push((path.clone(), output_path));
+    }
+    rename_duplicates(
+        &mut tiles_and_outputs,
+        |(path, output_path)| (path.as_path(), output_path),
+        options.output.duplicate_names,
+    );
+    pad_tile_pairs(
+        tiles_and_outputs,
+        Some(output_dir),
+        vrt,
+        source,
+        margin,
+        options,
+    )
 }
-fn trim_buffered_to_size(
-    org_raster: &Path,
-    buffered_raster: &Path,
-    output_raster: &Path,
+
+/// fails when `margin` or one of the margin overrides is wider than the reference, and warns when
+/// the margin is wider than the first tile as it is likely given in map units instead of pixels
+fn check_margin(
+    tiles: &[PathBuf],
+    vrt: &VrtSource,
+    margin: Margin,
+    options: &PadOptions,
 ) -> Result<(), Box<dyn Error>> {
-    let dso = match Dataset::open(org_raster) {
-        Ok(dso) => dso,
-        Err(e) => return Err(Box::new(e)),
-    };
+    let reference_size = vrt.size();
+    margin.validate(reference_size)?;
+    check_north_up_reference(vrt)?;
+    for tile_margin in options.margin_overrides.values() {
+        tile_margin.validate(reference_size)?;
+    }
+    if let Some(Ok(ds)) = tiles.first().map(|tile| options.gdal.open(tile)) {
+        let (cols, rows) = ds.raster_size();
+        if margin.x() > cols || margin.y() > rows {
+            message(&format!(
+                "Warning: the margin of {} by {} pixels is larger than the {}x{} pixels of {:?}",
+                margin.x(),
+                margin.y(),
+                cols,
+                rows,
+                tiles[0]
+            ));
+        }
+    }
+    Ok(())
+}
 
-    let dsb = match Dataset::open(buffered_raster) {
-        Ok(dsb) => dsb,
-        Err(e) => return Err(Box::new(e)),
-    };
+/// fails when the reference is south up or its columns run west, the margins are read from north
+/// up references only. vrts built by gdal are always north up
+fn check_north_up_reference(vrt: &VrtSource) -> Result<(), Box<dyn Error>> {
+    if !is_north_up(vrt.geo_transform()) {
+        return Err(format!(
+            "the reference {:?} is not north up, warp it to north up with gdalwarp first",
+            vrt.path()
+        )
+        .into());
+    }
+    Ok(())
+}
 
-    let projo = dso.projection();
+/// pads every tile of `tiles_and_outputs` from the vrt and saves it to its output.
+/// output_dir: where the state file, the stac items, the error log and the manifest of the run
+/// are written, runs of pairs have none and can not write them
+fn pad_tile_pairs(
+    tiles_and_outputs: Vec<(PathBuf, PathBuf)>,
+    output_dir: Option<&Path>,
+    vrt: &VrtSource,
+    source: &str,
+    margin: Margin,
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    let io = IoLimit::new(options.io_threads);
 
-    let geo_transform_o = match dso.geo_transform() {
-        Ok(geo_transform) => geo_transform,
-        Err(e) => return Err(Box::new(e)),
-    };
+    let mut band_vrts = Vec::new();
+    for (band_index, band_vrt_file) in &options.band_vrts {
+        band_vrts.push((*band_index, VrtSource::open(band_vrt_file)?));
+    }
 
-    let geo_transform_b = match dsb.geo_transform() {
-        Ok(geo_transform) => geo_transform,
-        Err(e) => return Err(Box::new(e)),
-    };
+    let (tiles_and_outputs, outputs) = claim_outputs(tiles_and_outputs, |(tile, output)| {
+        (tile.as_path(), output.as_path())
+    })?;
 
-    let x_offset = ((geo_transform_o[0] - geo_transform_b[0]) / geo_transform_b[1]) as usize;
-    let y_offset = ((geo_transform_o[3] - geo_transform_b[3]) / geo_transform_b[5]) as usize;
+    if options.disk_check != DiskSpaceCheck::Off {
+        let tiles: Vec<PathBuf> = tiles_and_outputs
+            .iter()
+            .map(|(tile, _)| tile.clone())
+            .collect();
+        let required = estimate_output_bytes(&tiles, margin, &options.output, |ds| {
+            padded_data_type(ds, &output_bands(ds, &options.output)?, options)
+        });
+        // pairs are checked on the directory of their first output
+        let dir = output_dir.or_else(|| {
+            tiles_and_outputs
+                .first()
+                .and_then(|(_, output)| output.parent())
+        });
+        if let Some(dir) = dir {
+            check_free_space(dir, required, options.disk_check)?;
+        }
+    }
 
-    let band = match dsb.rasterband(1) {
-        Ok(band) => band,
-        Err(e) => return Err(Box::new(e)),
-    };
+    // everything that changes the padded output, except the pixels of the neighbors
+    let parameters = format!(
+        "pad margin={:?} overrides={:?} source={} band_vrts={:?} band_map={:?} fallback={:?} \
+         snapping={:?} resampling={:?} band_kinds={:?} fill={:?} calc={:?} center_from_tile={} \
+         skip_empty={} pass_through_outside={} metadata_only={} output={:?}",
+        margin,
+        options.margin_overrides,
+        source,
+        options.band_vrts,
+        options.band_map,
+        options.fallback_vrt,
+        options.snapping,
+        options.resampling,
+        options.band_kinds,
+        options.margin_policy,
+        options.calc,
+        options.center_from_tile,
+        options.skip_empty,
+        options.pass_through_outside,
+        options.metadata_only,
+        options.output
+    );
+    let state = StateFile::load(output_dir, options.output.resume)?;
+    if options.output.archive.is_some() && options.mosaic_vrt.is_some() {
+        return Err("an archive can not be combined with a mosaic vrt of the outputs".into());
+    }
+    let archive = TarSink::create(&options.output)?;
+    let limit = RunLimit::new(options.max_tiles, options.max_duration);
+    let failures = FailureLimit::new(options.error_policy);
 
-    let buffered_data = match band.read_as::<f32>(
-        (x_offset as isize, y_offset as isize),
-        (dso.raster_size().0, dso.raster_size().1),
-        (dso.raster_size().0, dso.raster_size().1),
-        None,
-    ) {
-        Ok(buffered_data) => buffered_data,
-        Err(e) => return Err(Box::new(e)),
-    };
+    // For each tile, add margins and save to the output directory
+    let mut reports = run_parallel(&tiles_and_outputs, options.jobs, |(path, output_path)| {
+        let error_message = "Error adding margin to geotiff. Skipping...";
+        let margin = tile_margin(&options.margin_overrides, path, margin);
+        let mut report = run_tile(
+            path,
+            output_path,
+            error_message,
+            (&options.cancel, &failures, options.tile_timeout),
+            &options.listener,
+            || {
+                if options.output.resume && state.is_unchanged(output_path, &[path], &parameters) {
+                    return Ok(TileOutcome::skipped(SkipReason::Unchanged));
+                }
+                if !limit.start() {
+                    return Ok(TileOutcome::skipped(SkipReason::Limit));
+                }
+                add_margin_to_geotiff(path, output_path, margin, vrt, &band_vrts, &io, options)
+            },
+        );
+        if report.status == TileStatus::Written {
+            report.margin = Some(margin);
+        }
+        if options.output.provenance {
+            write_provenance(&mut report, &[path], Some((source, vrt.sha256())));
+        }
+        if let (Some(archive), Some(output_dir)) = (&archive, output_dir) {
+            archive.archive(output_dir, &mut report);
+        }
+        state.record(&mut report, &[path], &parameters);
+        options.listener.notify(&report);
+        report
+    });
+    reports.extend(outputs.collisions(&options.listener));
 
-    let driver = match DriverManager::get_driver_by_name("GTiff") {
-        Ok(driver) => driver,
-        Err(e) => return Err(Box::new(e)),
+    if let Some(archive) = archive {
+        archive.finish()?;
+    }
+    let mosaic = match &options.mosaic_vrt {
+        Some(mosaic_vrt) => write_mosaic(&reports, mosaic_vrt)?,
+        None => None,
     };
-
-    let mut dso_out = match driver.create_with_band_type::<f32, _>(
-        output_raster.to_str().unwrap(),
-        dso.raster_size().0 as isize,
-        dso.raster_size().1 as isize,
-        1,
-    ) {
-        Ok(dso_out) => dso_out,
-        Err(e) => return Err(Box::new(e)),
+    let output_dir = match output_dir {
+        Some(output_dir) => output_dir,
+        None => return Ok(BatchReport::new(reports, mosaic)),
     };
 
-    if let Err(e) = dso_out.set_geo_transform(&geo_transform_o) {
-        return Err(Box::new(e));
-    };
+    if options.output.stac {
+        stac::write_catalog(output_dir, &reports)?;
+    }
+    if options.output.error_log {
+        write_error_log(output_dir, &reports, |report| {
+            let margin = tile_margin(&options.margin_overrides, &report.input, margin);
+            padded_extent(&report.input, margin).ok()
+        })?;
+    }
+    if options.output.manifest {
+        write_manifest(output_dir, &reports, options.jobs)?;
+    }
 
-    if let Err(e) = dso_out.set_projection(&projo) {
-        return Err(Box::new(e));
-    };
+    Ok(BatchReport::new(reports, mosaic))
+}
 
-    let mut band_out = match dso_out.rasterband(1) {
-        Ok(band_out) => band_out,
-        Err(e) => return Err(Box::new(e)),
-    };
+/// drops the items whose output is already the output of an earlier item, they are reported by
+/// the returned `Outputs`, and creates the directories of the outputs.
+/// paths: the input and the output of an item
+fn claim_outputs<T>(
+    items: Vec<T>,
+    paths: impl Fn(&T) -> (&Path, &Path),
+) -> Result<(Vec<T>, Outputs), Box<dyn Error>> {
+    let mut outputs = Outputs::default();
+    let mut claimed = Vec::new();
+    for item in items {
+        let (input, output) = paths(&item);
+        if !outputs.claim(input, output) {
+            continue;
+        }
+        if let Some(parent) = output.parent() {
+            vsi::create_dir_all(parent)?;
+        }
+        claimed.push(item);
+    }
+    Ok((claimed, outputs))
+}
 
-    if let Err(e) = band_out.write(
-        (0, 0),
-        (dso.raster_size().0, dso.raster_size().1),
-        &buffered_data,
-    ) {
-        return Err(Box::new(e));
+/// renames the outputs that several items share following `duplicates`, e.g. of two `tile.tif` in
+/// different directories. outputs that still collide fail when they are claimed.
+/// paths: the input and the output of an item
+fn rename_duplicates<T>(
+    items: &mut [T],
+    paths: impl Fn(&mut T) -> (&Path, &mut PathBuf),
+    duplicates: DuplicateNames,
+) {
+    if duplicates == DuplicateNames::Error {
+        return;
+    }
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for item in items.iter_mut() {
+        *counts.entry(paths(item).1.clone()).or_default() += 1;
+    }
+    for item in items.iter_mut() {
+        let (input, output) = paths(item);
+        if counts[output.as_path()] > 1 {
+            *output = duplicates.rename(input, output);
+        }
+    }
+}
+
+/// writes a vrt of every output of the run that exists, including outputs that were up to date,
+/// and returns its path. no vrt is written when there are no outputs
+fn write_mosaic(reports: &[TileReport], path: &Path) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let outputs: Vec<PathBuf> = reports
+        .iter()
+        .filter(|report| !matches!(report.status, TileStatus::Failed(_)))
+        .map(|report| report.output.clone())
+        .filter(|output| vsi::exists(output))
+        .collect();
+    if outputs.is_empty() {
+        return Ok(None);
+    }
+    write_vrt(&outputs, path)?;
+    Ok(Some(path.to_path_buf()))
+}
+
+/// the .tif and .tiff files in a directory and the raster tables of its .gpkg files, sorted by
+/// name. tables: the raster tables read from the geopackages, all of them when empty
+pub fn raster_files(dir: &Path, tables: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for path in vsi::read_dir(dir)? {
+        if gpkg::is_geopackage(&path) {
+            files.extend(gpkg::table_paths(&path, tables)?);
+            continue;
+        }
+        if let Some(extension) = path.extension().and_then(std::ffi::OsStr::to_str) {
+            if extension == "tif" || extension == "tiff" {
+                files.push(path);
+            }
+        }
+    }
+    // the order of read_dir depends on the file system
+    files.sort();
+    Ok(files)
+}
+
+/// the `rasters` that `file_list` names, all of them without a list. listed files that are not
+/// among the rasters are skipped with a warning
+fn listed_files(
+    rasters: Vec<PathBuf>,
+    file_list: Option<&[PathBuf]>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let file_list = match file_list {
+        Some(file_list) => file_list,
+        None => return Ok(rasters),
     };
+    let mut listed = BTreeSet::new();
+    for file in file_list {
+        listed.insert(absolute_path(file)?);
+    }
+    let mut selected = Vec::new();
+    for raster in rasters {
+        if listed.remove(&absolute_path(&raster)?) {
+            selected.push(raster);
+        }
+    }
+    for file in listed {
+        message(&format!(
+            "Warning: {:?} is not a raster of the input directory. Skipping...",
+            file
+        ));
+    }
+    Ok(selected)
+}
+
+/// fails when `output_dir` is `input_dir` or inside of it, unless the output options allow it.
+/// outputs written next to the inputs replace the inputs of the same name
+fn check_separate_dirs(
+    input_dir: &Path,
+    output_dir: &Path,
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    if output_options.allow_overlapping_dirs {
+        return Ok(());
+    }
+    let input = absolute_path(input_dir)?;
+    let output = absolute_path(output_dir)?;
+    if output.starts_with(&input) {
+        let relation = if output == input { "is" } else { "is inside" };
+        return Err(format!(
+            "the output directory {:?} {} the input directory {:?}, use a separate directory",
+            output_dir, relation, input_dir
+        )
+        .into());
+    }
+    Ok(())
+}
 
+/// fails for the output options that write files of a whole run into the output directory when
+/// it is on one of gdal's virtual file systems, they are only written to directories on disk
+fn check_run_files(
+    output_dir: &Path,
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    if vsi::is_virtual(output_dir)
+        && (output_options.resume
+            || output_options.stac
+            || output_options.provenance
+            || output_options.error_log
+            || output_options.manifest
+            || output_options.archive.is_some())
+    {
+        return Err(format!(
+            "resuming, STAC items, provenance, error logs, manifests and archives need an output \
+             directory on disk, {:?} is not",
+            output_dir
+        )
+        .into());
+    }
     Ok(())
 }
+
+/// fails for the output options that write files of a whole run into the output directory, which
+/// a run of pairs does not have, and for a pair whose output is its input unless the output
+/// options allow overlapping directories
+fn check_pairs(
+    pairs: &[(PathBuf, PathBuf)],
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    if output_options.resume
+        || output_options.stac
+        || output_options.error_log
+        || output_options.manifest
+        || output_options.archive.is_some()
+    {
+        return Err(
+            "resuming, STAC items, error logs, manifests and archives need an output directory, \
+             they can not be combined with pairs"
+                .into(),
+        );
+    }
+    if output_options.allow_overlapping_dirs {
+        return Ok(());
+    }
+    for (input, output) in pairs {
+        if absolute_path(input)? == absolute_path(output)? {
+            return Err(format!("the output of {:?} is the raster itself", input).into());
+        }
+    }
+    Ok(())
+}
+
+/// the absolute path with the symlinks of the part of it that exists resolved
+fn absolute_path(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    if let Ok(path) = fs::canonicalize(path) {
+        return Ok(path);
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            Ok(absolute_path(parent)?.join(name))
+        }
+        (_, Some(name)) => Ok(fs::canonicalize(std::env::current_dir()?)?.join(name)),
+        _ => Err(format!("can not resolve {:?}", path).into()),
+    }
+}
+
+/// the tiles in the processing order, sorted by name first so tiles with the same position on
+/// the hilbert curve, and tiles missing from the index, keep a stable order
+fn order_tiles(tiles: &[PathBuf], order: TileOrder, index: Option<&TileIndex>) -> Vec<PathBuf> {
+    let mut tiles = tiles.to_vec();
+    tiles.sort();
+    if order == TileOrder::Name {
+        return tiles;
+    }
+
+    let centers: Vec<Option<(f64, f64)>> = tiles
+        .iter()
+        .map(|tile| {
+            let extent = index?.get(tile)?.extent();
+            Some((
+                (extent.min_x + extent.max_x) / 2.0,
+                (extent.min_y + extent.max_y) / 2.0,
+            ))
+        })
+        .collect();
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for (x, y) in centers.iter().flatten() {
+        (min_x, min_y) = (min_x.min(*x), min_y.min(*y));
+        (max_x, max_y) = (max_x.max(*x), max_y.max(*y));
+    }
+
+    // the centers are scaled to a grid of 2^16 cells a side, tiles missing from the index go last
+    let cells = 1u32 << 16;
+    let scale = |value: f64, min: f64, max: f64| {
+        let fraction = if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.0
+        };
+        (fraction * (cells - 1) as f64).round() as u32
+    };
+    let mut keyed: Vec<(u64, PathBuf)> = tiles
+        .into_iter()
+        .zip(centers)
+        .map(|(tile, center)| match center {
+            Some((x, y)) => (
+                hilbert_index(cells, scale(x, min_x, max_x), scale(y, min_y, max_y)),
+                tile,
+            ),
+            None => (u64::MAX, tile),
+        })
+        .collect();
+    keyed.sort_by_key(|(index, _)| *index);
+    keyed.into_iter().map(|(_, tile)| tile).collect()
+}
+/// takes a directory of the original directory with the tif files that where buffered and
+/// uses them as the reference to trim the buffered files to the original size
+/// org_dir: directory of the original files
+/// input_dir: directory of the buffered files
+/// output_dir: directory to save the trimmed files
+pub fn crop_down_to_size(
+    org_dir: &Path,
+    input_dir: &Path,
+    output_dir: &Path,
+) -> Result<BatchReport, Box<dyn Error>> {
+    crop_down_to_size_with_options(org_dir, input_dir, output_dir, &CropOptions::default())
+}
+
+/// same as `crop_down_to_size` but with control over how the trimmed files are written.
+/// options: see `CropOptions`
+pub fn crop_down_to_size_with_options(
+    org_dir: &Path,
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &CropOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    check_separate_dirs(org_dir, output_dir, &options.output)?;
+    let parameters = format!(
+        "crop snapping={:?} output={:?}",
+        options.snapping, options.output
+    );
+    let originals = Originals::new(
+        org_dir,
+        &options.gdal.tables,
+        options.output.tile_id.as_ref(),
+    )?;
+    crop_tiles(input_dir, output_dir, options, &parameters, |file_name| {
+        originals.trim_to(file_name)
+    })
+}
+
+/// the original rasters a buffered raster is cropped back to.
+/// the originals are paired with the buffered files by stem, or by tile id with a
+/// `TileIdPattern`, so a table of a geopackage and a geotiff of the same name are the same tile
+struct Originals {
+    org_dir: PathBuf,
+    tile_id: Option<TileIdPattern>,
+    by_id: HashMap<String, PathBuf>,
+}
+
+impl Originals {
+    fn new(
+        org_dir: &Path,
+        tables: &[String],
+        tile_id: Option<&TileIdPattern>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut originals = Originals {
+            org_dir: org_dir.to_path_buf(),
+            tile_id: tile_id.cloned(),
+            by_id: HashMap::new(),
+        };
+        for original in raster_files(org_dir, tables)? {
+            let id = originals.id(&original);
+            if let Some(first) = originals.by_id.get(&id) {
+                message(&format!(
+                    "Warning: {:?} and {:?} are both the original of tile {:?}, using the first",
+                    first, original, id
+                ));
+                continue;
+            }
+            originals.by_id.insert(id, original);
+        }
+        Ok(originals)
+    }
+
+    fn id(&self, path: &Path) -> String {
+        match &self.tile_id {
+            Some(pattern) => pattern.tile_id(path),
+            None => {
+                let file_name = gpkg::tile_file_name(path).unwrap_or_default();
+                let stem = Path::new(&file_name).file_stem().unwrap_or_default();
+                stem.to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    /// the original of the buffered file named `file_name`
+    fn trim_to(&self, file_name: &OsStr) -> TrimTo {
+        match self.by_id.get(&self.id(Path::new(file_name))) {
+            Some(original) => TrimTo::Original(original.clone()),
+            None => TrimTo::Original(self.org_dir.join(file_name)),
+        }
+    }
+}
+
+/// trims `margin` pixels off each side of the buffered files, using the geotransform of each
+/// buffered file instead of the originals, for when the margin is known and the originals are
+/// gone.
+/// input_dir: directory of the buffered files
+/// output_dir: directory to save the trimmed files
+/// margin: the margin the files were buffered with
+pub fn crop_fixed(
+    input_dir: &Path,
+    output_dir: &Path,
+    margin: usize,
+) -> Result<BatchReport, Box<dyn Error>> {
+    crop_fixed_with_options(input_dir, output_dir, margin, &CropOptions::default())
+}
+
+/// same as `crop_fixed` but with control over how the trimmed files are written.
+/// margin: a number of pixels or a `Margin`
+/// options: see `CropOptions`
+pub fn crop_fixed_with_options(
+    input_dir: &Path,
+    output_dir: &Path,
+    margin: impl Into<Margin>,
+    options: &CropOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    let margin = margin.into();
+    let parameters = format!(
+        "crop margin={:?} snapping={:?} output={:?}",
+        margin, options.snapping, options.output
+    );
+    crop_tiles(input_dir, output_dir, options, &parameters, |_| {
+        TrimTo::Margin(margin)
+    })
+}
+
+/// trims `margin` pixels off the buffered input of every `(input, output)` pair and saves it to
+/// its output, like `crop_fixed_with_options` for callers that map the inputs to the outputs
+/// themselves. the pairs are cropped in their order, the file list of `options` does not apply
+/// and the outputs are not renamed.
+/// options: see `CropOptions`, resuming, stac items, error logs, manifests and archives need an
+/// output directory and fail
+pub fn crop_pairs(
+    pairs: impl IntoIterator<Item = (PathBuf, PathBuf)>,
+    margin: impl Into<Margin>,
+    options: &CropOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    let pairs: Vec<(PathBuf, PathBuf)> = pairs.into_iter().collect();
+    check_pairs(&pairs, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+
+    let margin = margin.into();
+    let parameters = format!(
+        "crop margin={:?} snapping={:?} output={:?}",
+        margin, options.snapping, options.output
+    );
+    let tiles = pairs
+        .into_iter()
+        .map(|(input, output)| (input, TrimTo::Margin(margin), output))
+        .collect();
+    crop_tile_triples(tiles, None, options, &parameters)
+}
+
+/// what a buffered file is trimmed back to
+enum TrimTo {
+    /// the extent of the original file
+    Original(PathBuf),
+    /// the buffered file less this margin
+    Margin(Margin),
+}
+
+/// trims every buffered file of `input_dir` to what `trim_to` returns for its file name
+fn crop_tiles(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &CropOptions,
+    parameters: &str,
+    trim_to: impl Fn(&OsStr) -> TrimTo,
+) -> Result<BatchReport, Box<dyn Error>> {
+    check_separate_dirs(input_dir, output_dir, &options.output)?;
+    check_run_files(output_dir, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+    vsi::create_dir_all(output_dir)?;
+
+    // Pair each buffered geotiff with what it is trimmed to and the path it will be saved to
+    let mut tiles: Vec<(PathBuf, TrimTo, PathBuf)> = Vec::new();
+    let rasters = listed_files(
+        raster_files(input_dir, &options.gdal.tables)?,
+        options.file_list.as_deref(),
+    )?;
+    for path in rasters {
+        let file_name = match gpkg::tile_file_name(&path) {
+            Some(file_name) => file_name,
+            None => {
+                message(&format!(
+                    "Could not retrieve file name from {:?}. Skipping...",
+                    path
+                ));
+                continue;
+            }
+        };
+        let trim_to = trim_to(&file_name);
+        let output_path = match options.output.output_path(output_dir, &path) {
+            Ok(output_path) => output_path,
+            Err(e) => {
+                message(&format!(
+                    "Could not compose the output path of {:?}: {}. Skipping...",
+                    path, e
+                ));
+                continue;
+            }
+        };
+        tiles.push((path, trim_to, output_path));
+    }
+    rename_duplicates(
+        &mut tiles,
+        |(path, _, output_path)| (path.as_path(), output_path),
+        options.output.duplicate_names,
+    );
+    let report = crop_tile_triples(tiles, Some(output_dir), options, parameters)?;
+    if options.output.copy_non_raster_files {
+        copy_non_raster_files(input_dir, output_dir)?;
+    }
+    Ok(report)
+}
+
+/// trims every buffered file of `tiles` to what it is paired with and saves it to its output.
+/// output_dir: where the state file, the stac items, the error log and the manifest of the run
+/// are written, runs of pairs have none and can not write them
+fn crop_tile_triples(
+    tiles: Vec<(PathBuf, TrimTo, PathBuf)>,
+    output_dir: Option<&Path>,
+    options: &CropOptions,
+    parameters: &str,
+) -> Result<BatchReport, Box<dyn Error>> {
+    let (tiles, outputs) = claim_outputs(tiles, |(path, _, output_path)| {
+        (path.as_path(), output_path.as_path())
+    })?;
+
+    // the buffered size is an upper bound of the cropped size
+    if options.disk_check != DiskSpaceCheck::Off {
+        let paths: Vec<PathBuf> = tiles.iter().map(|(path, _, _)| path.clone()).collect();
+        let required = estimate_output_bytes(&paths, Margin::Uniform(0), &options.output, |ds| {
+            cropped_data_type(ds, &output_bands(ds, &options.output)?, &options.output)
+        });
+        // pairs are checked on the directory of their first output
+        let dir = output_dir.or_else(|| tiles.first().and_then(|(_, _, output)| output.parent()));
+        if let Some(dir) = dir {
+            check_free_space(dir, required, options.disk_check)?;
+        }
+    }
+
+    let state = StateFile::load(output_dir, options.output.resume)?;
+    let archive = TarSink::create(&options.output)?;
+
+    // the original is an input of the output as much as the buffered file
+    let inputs = |path: &PathBuf, trim_to: &TrimTo| -> Vec<PathBuf> {
+        match trim_to {
+            TrimTo::Original(original) => vec![path.clone(), original.clone()],
+            TrimTo::Margin(_) => vec![path.clone()],
+        }
+    };
+    let limit = RunLimit::new(options.max_tiles, options.max_duration);
+    let failures = FailureLimit::new(options.error_policy);
+    let mut reports = run_parallel(&tiles, options.jobs, |(path, trim_to, output_path)| {
+        let error_message = "Error trimming buffered size. Skipping...";
+        let mut report = run_tile(
+            path,
+            output_path,
+            error_message,
+            (&options.cancel, &failures, options.tile_timeout),
+            &options.listener,
+            || {
+                let inputs = inputs(path, trim_to);
+                let inputs: Vec<&Path> = inputs.iter().map(PathBuf::as_path).collect();
+                if options.output.resume && state.is_unchanged(output_path, &inputs, parameters) {
+                    return Ok(TileOutcome::skipped(SkipReason::Unchanged));
+                }
+                if !limit.start() {
+                    return Ok(TileOutcome::skipped(SkipReason::Limit));
+                }
+                trim_buffered(path, trim_to, output_path, options)
+            },
+        );
+        let inputs = inputs(path, trim_to);
+        let inputs: Vec<&Path> = inputs.iter().map(PathBuf::as_path).collect();
+        if options.output.provenance {
+            write_provenance(&mut report, &inputs, None);
+        }
+        if let (Some(archive), Some(output_dir)) = (&archive, output_dir) {
+            archive.archive(output_dir, &mut report);
+        }
+        state.record(&mut report, &inputs, parameters);
+        options.listener.notify(&report);
+        report
+    });
+    reports.extend(outputs.collisions(&options.listener));
+
+    if let Some(archive) = archive {
+        archive.finish()?;
+    }
+    warn_inconsistent_margins(&reports);
+    let output_dir = match output_dir {
+        Some(output_dir) => output_dir,
+        None => return Ok(BatchReport::new(reports, None)),
+    };
+
+    if options.output.stac {
+        stac::write_catalog(output_dir, &reports)?;
+    }
+    if options.output.error_log {
+        write_error_log(output_dir, &reports, |report| {
+            let (_, trim_to, _) = tiles.iter().find(|(path, _, _)| *path == report.input)?;
+            match trim_to {
+                TrimTo::Original(original) => GeoExtent::from_raster(original).ok(),
+                TrimTo::Margin(margin) => trimmed_extent(&report.input, *margin).ok(),
+            }
+        })?;
+    }
+    if options.output.manifest {
+        write_manifest(output_dir, &reports, options.jobs)?;
+    }
+
+    Ok(BatchReport::new(reports, None))
+}
+
+/// warns about the rasters whose trimmed margin differs from the margin most rasters of the batch
+/// were buffered with, a sign that a processing step changed their grid. sides without a margin
+/// are left out, the margin stops there at the edge of the reference
+fn warn_inconsistent_margins(reports: &[TileReport]) {
+    let trimmed: Vec<(&Path, TrimmedMargin)> = reports
+        .iter()
+        .filter_map(|report| Some((report.input.as_path(), report.trimmed?)))
+        .collect();
+    // the most common margin of the sides along an axis
+    let common = |sides: &dyn Fn(&TrimmedMargin) -> [usize; 2]| {
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for (_, margin) in &trimmed {
+            for side in sides(margin).into_iter().filter(|side| *side > 0) {
+                *counts.entry(side).or_default() += 1;
+            }
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(side, _)| side)
+    };
+    let x = common(&|margin| [margin.left, margin.right]);
+    let y = common(&|margin| [margin.top, margin.bottom]);
+    for (input, margin) in &trimmed {
+        let differs = |sides: [usize; 2], common: Option<usize>| {
+            sides.iter().any(|side| *side > 0 && Some(*side) != common)
+        };
+        if differs([margin.left, margin.right], x) || differs([margin.top, margin.bottom], y) {
+            message(&format!(
+                "Warning: the margin trimmed off {:?} is {:?}, most rasters were buffered by {} \
+                 by {} pixels, a processing step may have changed its grid",
+                input,
+                margin,
+                x.unwrap_or(0),
+                y.unwrap_or(0)
+            ));
+        }
+    }
+}
+
+/// crops the buffered files of `input_dir` and replaces the originals in `org_dir` with them.
+/// each output is written next to its original and renamed over it once complete, so an
+/// original is never left half written.
+/// org_dir: directory of the original files, the trimmed files are saved over them
+/// input_dir: directory of the buffered files
+pub fn crop_in_place(org_dir: &Path, input_dir: &Path) -> Result<BatchReport, Box<dyn Error>> {
+    crop_in_place_with_options(org_dir, input_dir, &CropOptions::default())
+}
+
+/// same as `crop_in_place` but with control over how the trimmed files are written, see
+/// `CropOptions::backup` for keeping the originals.
+/// only the gtiff and cog formats keep the names of the originals, and a shard template would
+/// move the outputs away from them, so both are rejected.
+pub fn crop_in_place_with_options(
+    org_dir: &Path,
+    input_dir: &Path,
+    options: &CropOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    if options.output.archive.is_some() {
+        return Err("can not crop in place into an archive, the originals would be removed".into());
+    }
+    if !matches!(
+        options.output.format,
+        OutputFormat::GTiff | OutputFormat::Cog
+    ) {
+        return Err(format!(
+            "can not crop in place to {:?}, the outputs would not replace the originals",
+            options.output.format
+        )
+        .into());
+    }
+    if options.output.shard.is_some() {
+        return Err("can not crop in place into shard subdirectories".into());
+    }
+    let originals = raster_files(org_dir, &options.gdal.tables)?;
+    if let Some(table) = originals
+        .iter()
+        .find(|path| gpkg::split_table_path(path).is_some())
+    {
+        return Err(format!(
+            "can not crop in place, {:?} is a geopackage table and not a file",
+            table
+        )
+        .into());
+    }
+    // the outputs are meant to land next to the originals
+    let options = CropOptions {
+        output: options.output.clone().allow_overlapping_dirs(true),
+        ..options.clone()
+    };
+    crop_down_to_size_with_options(org_dir, input_dir, org_dir, &options)
+}
+
+fn add_margin_to_geotiff(
+    file_path: &Path,
+    output_path: &Path,
+    margin: Margin,
+    vrt: &VrtSource,
+    band_vrts: &[(isize, VrtSource)],
+    io: &IoLimit,
+    options: &PadOptions,
+) -> Result<TileOutcome, Box<dyn Error>> {
+    let output_options = &options.output;
+
+    // Open the geotiff file
+    let ds = options.gdal.open(file_path)?;
+    check_data_types(&ds, file_path)?;
+
+    if options.skip_empty && is_empty(&ds)? {
+        return Ok(TileOutcome::skipped(SkipReason::Empty));
+    }
+
+    // A margin of 0 only rewrites the tile with the output options, the vrt is not read
+    let is_virtual = output_options.format == OutputFormat::VirtualVrt;
+    if margin.is_zero() && options.calc.is_none() && !is_virtual {
+        let copy_options = CropOptions::default()
+            .output(output_options.clone())
+            .gdal(options.gdal.clone());
+        let extent = GeoExtent::from_dataset(&ds)?;
+        let window = crop_raster_to_extent(file_path, output_path, extent, &copy_options)?;
+        if output_options.sidecars {
+            copy_sidecars(file_path, output_path)?;
+        }
+        return Ok(TileOutcome::written(window));
+    }
+
+    // Flipped tiles are padded as north up tiles, the padded outputs are north up
+    let north_up = north_up_copy(&ds)?;
+    let ds = north_up.as_ref().unwrap_or(&ds);
+
+    // A tile that does not touch the VRT has no margin to read
+    let tile_extent = GeoExtent::from_dataset(ds)?;
+    for source in std::iter::once(vrt).chain(band_vrts.iter().map(|(_, source)| source)) {
+        let vrt_extent = GeoExtent::from_geotransform(source.geo_transform(), source.size());
+        if !tile_extent.intersects(&vrt_extent) {
+            if options.pass_through_outside {
+                message(&format!(
+                    "Warning: {:?} is outside of {:?}, copying it unpadded",
+                    file_path,
+                    source.path()
+                ));
+                if gpkg::split_table_path(file_path).is_some() {
+                    // a table can not be copied as a file, it is rewritten instead
+                    let copy_options = CropOptions::default()
+                        .output(output_options.clone())
+                        .gdal(options.gdal.clone());
+                    crop_raster_to_extent(file_path, output_path, tile_extent, &copy_options)?;
+                } else {
+                    vsi::copy(file_path, &partial_path(output_path))?;
+                    vsi::rename(&partial_path(output_path), output_path)?;
+                }
+                if output_options.sidecars {
+                    copy_sidecars(file_path, output_path)?;
+                }
+            }
+            return Ok(TileOutcome::skipped(SkipReason::OutsideVrt));
+        }
+    }
+
+    if is_virtual {
+        return write_virtual_padded_tile(ds, output_path, margin, vrt, band_vrts, options);
+    }
+
+    // Reading is throttled by the io limit, compressing and writing the output is not
+    let mut padded = io.run(|| read_padded_tile(ds, vrt, band_vrts, margin, options))?;
+    let (cols, rows) = padded.data[0].size;
+
+    let data_type = padded_data_type(ds, &padded.bands, options)?;
+    let mut output_nodata = Vec::new();
+    for (&band_index, data) in padded.bands.iter().zip(padded.data.iter_mut()) {
+        let nodata = match ds.rasterband(band_index) {
+            Ok(band) => band.no_data_value(),
+            Err(e) => return Err(Box::new(e)),
+        };
+        if let Some(calc) = &options.calc {
+            calc.apply_to(&mut data.data, nodata);
+        }
+        let policy_nodata = output_options
+            .band_policy_of(band_index)
+            .and_then(|policy| policy.nodata);
+        if let Some(policy_nodata) = policy_nodata {
+            replace_nodata(&mut data.data, nodata, policy_nodata);
+        }
+        match BandConversion::new(output_options, policy_nodata.or(nodata))? {
+            Some(conversion) => {
+                conversion.convert(&mut data.data);
+                output_nodata.push(conversion.nodata);
+            }
+            None => output_nodata.push(policy_nodata),
+        }
+    }
+
+    // Create a new geotiff file
+    let mut new_ds = create_output(
+        output_path,
+        (cols, rows),
+        padded.data.len() as isize,
+        data_type,
+        output_options,
+    )?;
+    write_padded_tile(ds, &padded, &mut new_ds)?;
+    set_nodata_values(&new_ds, &output_nodata)?;
+    write_margin_metadata(&mut new_ds, margin, padded.clamped, vrt)?;
+
+    finish_output(new_ds, output_path, output_options)?;
+
+    if output_options.sidecars {
+        copy_sidecars(file_path, output_path)?;
+    }
+
+    if padded.clamped {
+        message(&format!(
+            "Warning: the margin of {:?} stops at the edge of {:?}",
+            file_path,
+            vrt.path()
+        ));
+    }
+
+    Ok(TileOutcome {
+        status: TileStatus::Written,
+        window: padded.window,
+        bytes_read: padded.bytes_read,
+        clamped: padded.clamped,
+        trimmed: None,
+    })
+}
+
+/// the pixels of a padded tile and where they are
+struct PaddedTile {
+    geo_transform: GeoTransform,
+    projection: String,
+    data: Vec<Buffer<f64>>,
+    /// the bands of the tile in the order of `data`
+    bands: Vec<isize>,
+    mask: Option<Buffer<u8>>,
+    /// the window read from the main vrt
+    window: Option<PixelWindow>,
+    /// the decoded bytes read from the vrts
+    bytes_read: u64,
+    /// the margin stops at the edge of the vrt
+    clamped: bool,
+}
+
+/// fails for options that need the pixels of the padded tiles when the outputs are virtual vrts
+fn check_virtual_output(options: &PadOptions) -> Result<(), Box<dyn Error>> {
+    if options.metadata_only && options.output.format != OutputFormat::VirtualVrt {
+        return Err("metadata only outputs are vrts, they need OutputFormat::VirtualVrt".into());
+    }
+    if options.output.format != OutputFormat::VirtualVrt {
+        return Ok(());
+    }
+    if options.calc.is_some() || options.output.output_type.is_some() || options.skip_empty {
+        return Err(
+            "virtual vrt outputs do not hold pixels, they can not be combined with a \
+                    calc expression, an output type or skipping empty tiles"
+                .into(),
+        );
+    }
+    if options.pass_through_outside {
+        return Err(
+            "virtual vrt outputs can not pass tiles outside of the reference through".into(),
+        );
+    }
+    if options.fallback_vrt.is_some() {
+        return Err("virtual vrt outputs can not fill the margins from a fallback mosaic".into());
+    }
+    if matches!(
+        options.margin_policy,
+        MarginPolicy::Fill(EdgeFill::Values(_))
+    ) {
+        return Err("virtual vrt outputs can only fill the margin with nodata".into());
+    }
+    if !options.output.band_policies.is_empty() {
+        return Err("virtual vrt outputs can not change the nodata value or fill of bands".into());
+    }
+    Ok(())
+}
+
+/// writes the padded tile of `ds` as a vrt that reads the padded window from the vrts, the part
+/// of the margin beyond the edge of the vrt follows the margin policy like `read_padded_tile`.
+/// with `options.metadata_only` the vrt has no sources
+fn write_virtual_padded_tile(
+    ds: &Dataset,
+    output_path: &Path,
+    margin: Margin,
+    vrt: &VrtSource,
+    band_vrts: &[(isize, VrtSource)],
+    options: &PadOptions,
+) -> Result<TileOutcome, Box<dyn Error>> {
+    check_padded_size(ds.raster_size(), margin)?;
+    let geotransform = match ds.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let new_geotransform = padded_geotransform(&geotransform, margin);
+
+    let mut bands = Vec::new();
+    let mut window_size = None;
+    for band_index in output_bands(ds, &options.output)? {
+        let (source, source_band) = source_band(band_index, vrt, band_vrts, options);
+        let (window, buffer_size) = vrt_margin_window(
+            source,
+            &new_geotransform,
+            ds.raster_size(),
+            margin,
+            options.snapping,
+        )?;
+        if *window_size.get_or_insert(buffer_size) != buffer_size {
+            return Err(format!(
+                "the window of band {} read from {:?} does not match the first band",
+                band_index,
+                source.path()
+            )
+            .into());
+        }
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        bands.push(VirtualBand {
+            source: absolute_path(source.path())?,
+            source_band,
+            window,
+            data_type: data_type_name(&band),
+            nodata: band.no_data_value(),
+            resampling: band_resampling(ds, band_index, options)?,
+        });
+    }
+    let window_size = window_size.unwrap_or_default();
+
+    let full_size = (
+        ds.raster_size().0 + 2 * margin.x(),
+        ds.raster_size().1 + 2 * margin.y(),
+    );
+    let offset = edge_fill_offset(
+        vrt,
+        &new_geotransform,
+        ds.raster_size(),
+        margin,
+        options.snapping,
+    )?;
+    let mut geo_transform = new_geotransform;
+    let (mut size, mut window_offset, mut clamped) = (full_size, offset, false);
+    if offset != (0, 0) || window_size != full_size {
+        match &options.margin_policy {
+            MarginPolicy::Error => {
+                return Err(Box::new(MarginBeyondReference {
+                    reference: vrt.path().to_path_buf(),
+                }))
+            }
+            MarginPolicy::Clamp => {
+                geo_transform[0] += offset.0 as f64 * new_geotransform[1];
+                geo_transform[3] += offset.1 as f64 * new_geotransform[5];
+                (size, window_offset, clamped) = (window_size, (0, 0), true);
+            }
+            // the part of the vrt without a source is nodata
+            MarginPolicy::Fill(_) => {}
+        }
+    }
+
+    let xml = match options.metadata_only {
+        true => stub_tile_xml(size, &geo_transform, &ds.projection(), &bands)?,
+        false => virtual_tile_xml(
+            size,
+            &geo_transform,
+            &ds.projection(),
+            &bands,
+            window_offset,
+            window_size,
+        )?,
+    };
+    write_virtual_tile(output_path, &xml)?;
+    if clamped {
+        message(&format!(
+            "Warning: the margin of {:?} stops at the edge of {:?}",
+            output_path,
+            vrt.path()
+        ));
+    }
+    Ok(TileOutcome {
+        status: TileStatus::Written,
+        window: bands.first().map(|band| band.window),
+        bytes_read: 0,
+        clamped,
+        trimmed: None,
+    })
+}
+
+/// the vrt and its band that band `band_index` of a tile is padded from: the first band of its
+/// own vrt, the band of the main vrt given by the band map or the same band of the main vrt
+fn source_band<'a>(
+    band_index: isize,
+    vrt: &'a VrtSource,
+    band_vrts: &'a [(isize, VrtSource)],
+    options: &PadOptions,
+) -> (&'a VrtSource, isize) {
+    if let Some((_, band_vrt)) = band_vrts.iter().find(|(band, _)| *band == band_index) {
+        return (band_vrt, 1);
+    }
+    match options
+        .band_map
+        .iter()
+        .find(|(band, _)| *band == band_index)
+    {
+        Some((_, vrt_band)) => (vrt, *vrt_band),
+        None => (vrt, band_index),
+    }
+}
+
+/// reads the bands of `ds` padded by `margin` from the vrts
+fn read_padded_tile(
+    ds: &Dataset,
+    vrt: &VrtSource,
+    band_vrts: &[(isize, VrtSource)],
+    margin: Margin,
+    options: &PadOptions,
+) -> Result<PaddedTile, Box<dyn Error>> {
+    check_padded_size(ds.raster_size(), margin)?;
+
+    // Get the original geotiff's data and metadata
+    let geotransform = match ds.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let projection = ds.projection();
+
+    // Compute expanded geotransform
+    let new_geotransform = padded_geotransform(&geotransform, margin);
+
+    // Read each band from its VRT, bands without their own VRT read the same band of the main VRT
+    let bands = output_bands(ds, &options.output)?;
+    let mut new_data: Vec<Buffer<f64>> = Vec::new();
+    let mut window = None;
+    let mut bytes_read = 0;
+    for &band_index in &bands {
+        check_tile_deadline()?;
+        let (source, source_band) = source_band(band_index, vrt, band_vrts, options);
+        let (band_window, data, pixels_read) = read_margin_window(
+            source,
+            source_band,
+            &new_geotransform,
+            margin,
+            options,
+            (ds, band_index),
+        )?;
+        if let Some(first) = new_data.first() {
+            if first.size != data.size {
+                return Err(format!(
+                    "the window of band {} read from {:?} is {:?} pixels but the first band is {:?}",
+                    band_index,
+                    source.path(),
+                    data.size,
+                    first.size
+                )
+                .into());
+            }
+        }
+        new_data.push(data);
+        window.get_or_insert(band_window);
+        bytes_read += (pixels_read * std::mem::size_of::<f64>()) as u64;
+    }
+
+    // An internal mask of the tile is padded from the mask of the main VRT, which is all valid
+    // when the VRT has no mask of its own
+    let mut new_mask = if has_dataset_mask(ds)? {
+        let (window, buffer_size) = vrt_margin_window(
+            vrt,
+            &new_geotransform,
+            ds.raster_size(),
+            margin,
+            options.snapping,
+        )?;
+        bytes_read += (window.cols * window.rows) as u64;
+        Some(vrt.with_dataset(|vrt_ds| read_mask(vrt_ds, 1, &window, buffer_size))?)
+    } else {
+        None
+    };
+
+    // Where the margin extends beyond the edge of the VRT the margin policy decides whether the
+    // tile fails, stops at the edge of the VRT or is filled per band
+    let full_size = (
+        ds.raster_size().0 + 2 * margin.x(),
+        ds.raster_size().1 + 2 * margin.y(),
+    );
+    let offset = edge_fill_offset(
+        vrt,
+        &new_geotransform,
+        ds.raster_size(),
+        margin,
+        options.snapping,
+    )?;
+    let beyond_vrt = offset != (0, 0) || new_data[0].size != full_size;
+    let mut geo_transform = new_geotransform;
+    let mut clamped = false;
+    if beyond_vrt {
+        match &options.margin_policy {
+            MarginPolicy::Error => {
+                return Err(Box::new(MarginBeyondReference {
+                    reference: vrt.path().to_path_buf(),
+                }))
+            }
+            MarginPolicy::Clamp => {
+                // the output starts where the window read from the VRT starts
+                geo_transform[0] += offset.0 as f64 * new_geotransform[1];
+                geo_transform[3] += offset.1 as f64 * new_geotransform[5];
+                clamped = true;
+            }
+            MarginPolicy::Fill(edge_fill) => {
+                for (&band_index, data) in bands.iter().zip(new_data.iter_mut()) {
+                    let nodata = match ds.rasterband(band_index) {
+                        Ok(band) => band.no_data_value(),
+                        Err(e) => return Err(Box::new(e)),
+                    };
+                    let nodata = match options.output.band_policy_of(band_index) {
+                        Some(policy) => policy.nodata_value(nodata),
+                        None => nodata,
+                    };
+                    let fill = options.output.fill_value(band_index, nodata, edge_fill);
+                    *data = fill_around(data, full_size, offset, fill);
+                }
+                if let Some(mask) = &new_mask {
+                    new_mask = Some(fill_around(mask, full_size, offset, 0));
+                }
+            }
+        }
+    }
+
+    if let Some(fallback) = &options.fallback_vrt {
+        fill_from_fallback(ds, fallback, &bands, &geo_transform, &mut new_data, options)?;
+    }
+
+    Ok(PaddedTile {
+        geo_transform,
+        projection,
+        data: new_data,
+        bands,
+        mask: new_mask,
+        window,
+        bytes_read,
+        clamped,
+    })
+}
+
+/// fills the margin pixels of the padded `data` of `ds` that are nodata of the tile from the
+/// `fallback` mosaic, where it has pixels. the pixels of the tile itself are kept and the fallback
+/// is only read when the margins have nodata pixels
+fn fill_from_fallback(
+    ds: &Dataset,
+    fallback: &Path,
+    bands: &[isize],
+    geo_transform: &GeoTransform,
+    data: &mut [Buffer<f64>],
+    options: &PadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let tile_geotransform = match ds.geo_transform() {
+        Ok(tile_geotransform) => tile_geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let (x, y) = world_to_pixel(geo_transform, tile_geotransform[0], tile_geotransform[3]);
+    let (cols, rows) = ds.raster_size();
+    let (x, y) = (x.round() as isize, y.round() as isize);
+    let size = data[0].size;
+    let in_margin = |i: usize| {
+        let (col, row) = ((i % size.0) as isize, (i / size.0) as isize);
+        col < x || col >= x + cols as isize || row < y || row >= y + rows as isize
+    };
+    let is_missing = |value: f64, nodata: Option<f64>| value.is_nan() || nodata == Some(value);
+
+    let mut nodata = Vec::new();
+    let mut holes = false;
+    for (&band_index, band_data) in bands.iter().zip(data.iter()) {
+        let band_nodata = match ds.rasterband(band_index) {
+            Ok(band) => band.no_data_value(),
+            Err(e) => return Err(Box::new(e)),
+        };
+        let mut pixels = band_data.data.iter().enumerate();
+        holes |= pixels.any(|(i, &value)| is_missing(value, band_nodata) && in_margin(i));
+        nodata.push(band_nodata);
+    }
+    if !holes {
+        return Ok(());
+    }
+
+    let fallback_ds = options.gdal.open(fallback)?;
+    let extent = GeoExtent::from_geotransform(geo_transform, size);
+    if !GeoExtent::from_dataset(&fallback_ds)?.intersects(&extent) {
+        return Ok(());
+    }
+    // the part beyond the edge of the fallback is nan, which is never filled in, so the band
+    // policies are left out of its read
+    let fill_options = PadOptions {
+        margin_policy: MarginPolicy::Fill(EdgeFill::Values(vec![f64::NAN])),
+        output: OutputOptions {
+            band_policies: Vec::new(),
+            ..options.output.clone()
+        },
+        ..options.clone()
+    };
+    let fills = read_window_of::<f64>(&fallback_ds, fallback, extent, size, &fill_options)?;
+    for ((&band_index, band_data), band_nodata) in bands.iter().zip(data.iter_mut()).zip(nodata) {
+        let (fill, fill_nodata) = match (
+            usize::try_from(band_index - 1)
+                .ok()
+                .and_then(|index| fills.get(index)),
+            fallback_ds.rasterband(band_index),
+        ) {
+            (Some(fill), Ok(band)) => (fill, band.no_data_value()),
+            _ => {
+                return Err(
+                    format!("the fallback {:?} has no band {}", fallback, band_index).into(),
+                )
+            }
+        };
+        for (i, value) in band_data.data.iter_mut().enumerate() {
+            if is_missing(*value, band_nodata)
+                && in_margin(i)
+                && !is_missing(fill.data[i], fill_nodata)
+            {
+                *value = fill.data[i];
+            }
+        }
+    }
+    Ok(())
+}
+
+/// writes a padded tile of `ds` to `target`, which must have the size and bands of the padded tile
+fn write_padded_tile(
+    ds: &Dataset,
+    padded: &PaddedTile,
+    target: &mut Dataset,
+) -> Result<(), Box<dyn Error>> {
+    let (cols, rows) = padded.data[0].size;
+    if target.raster_size() != (cols, rows) || target.raster_count() != padded.data.len() as isize {
+        return Err(format!(
+            "the target is {:?} pixels with {} bands, the padded tile is {:?} pixels with {} bands",
+            target.raster_size(),
+            target.raster_count(),
+            (cols, rows),
+            padded.data.len()
+        )
+        .into());
+    }
+
+    if let Err(e) = target.set_geo_transform(&padded.geo_transform) {
+        return Err(Box::new(e));
+    };
+
+    if let Err(e) = target.set_projection(&padded.projection) {
+        return Err(Box::new(e));
+    };
+
+    for (band_index, data) in (1..).zip(&padded.data) {
+        let mut new_band = match target.rasterband(band_index) {
+            Ok(new_band) => new_band,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        if let Err(e) = new_band.write((0, 0), (cols, rows), data) {
+            return Err(Box::new(e));
+        };
+    }
+
+    copy_color_interpretation(ds, target, &padded.bands)?;
+    copy_palettes(ds, target, &padded.bands)?;
+    if let Some(mask) = &padded.mask {
+        write_dataset_mask(target, mask)?;
+    }
+    Ok(())
+}
+
+/// the window of the vrt that covers the padded tile and the size it has on the grid of the tile
+fn vrt_margin_window(
+    vrt: &VrtSource,
+    new_geotransform: &GeoTransform,
+    tile_size: (usize, usize),
+    margin: Margin,
+    snapping: Snapping,
+) -> Result<(PixelWindow, (usize, usize)), Box<dyn Error>> {
+    let window = window_for_margin(
+        new_geotransform,
+        tile_size,
+        margin,
+        vrt.geo_transform(),
+        vrt.size(),
+        snapping,
+    )?;
+    let buffer_size = resampled_size(&window, new_geotransform, vrt.geo_transform());
+    Ok((window, buffer_size))
+}
+
+/// the data type of the padded output of `bands` of `ds`
+fn padded_data_type(
+    ds: &Dataset,
+    bands: &[isize],
+    options: &PadOptions,
+) -> Result<GDALDataType::Type, Box<dyn Error>> {
+    // The calculated values are no longer classes of a palette, f64 inputs stay f64
+    match (options.output.output_type, &options.calc) {
+        (Some(output_type), _) => Ok(output_type.gdal_type()),
+        (None, Some(_)) => match output_data_type(ds, bands)? {
+            GDALDataType::GDT_Float64 => Ok(GDALDataType::GDT_Float64),
+            _ => Ok(GDALDataType::GDT_Float32),
+        },
+        (None, None) => output_data_type(ds, bands),
+    }
+}
+
+/// the data type of the cropped output of `bands` of `ds`
+fn cropped_data_type(
+    ds: &Dataset,
+    bands: &[isize],
+    output_options: &OutputOptions,
+) -> Result<GDALDataType::Type, Box<dyn Error>> {
+    match output_options.output_type {
+        Some(output_type) => Ok(output_type.gdal_type()),
+        None => output_data_type(ds, bands),
+    }
+}
+
+/// the bands of `ds` that are written to its output, in output order: `OutputOptions::bands` or
+/// every band
+fn output_bands(
+    ds: &Dataset,
+    output_options: &OutputOptions,
+) -> Result<Vec<isize>, Box<dyn Error>> {
+    if output_options.bands.is_empty() {
+        return Ok((1..=ds.raster_count()).collect());
+    }
+    let missing = output_options
+        .bands
+        .iter()
+        .find(|&&band| band < 1 || band > ds.raster_count());
+    if let Some(band) = missing {
+        return Err(format!(
+            "the raster has no band {}, it has {}",
+            band,
+            ds.raster_count()
+        )
+        .into());
+    }
+    Ok(output_options.bands.clone())
+}
+
+/// the resampling of band `band_index` of the tile, nearest neighbor for categorical bands
+fn band_resampling(
+    ds: &Dataset,
+    band_index: isize,
+    options: &PadOptions,
+) -> Result<Resampling, Box<dyn Error>> {
+    let kind = options
+        .band_kinds
+        .iter()
+        .find(|(band, _)| *band == band_index)
+        .map_or(BandKind::Auto, |(_, kind)| *kind);
+    let categorical = match kind {
+        BandKind::Auto => is_categorical(ds, band_index)?,
+        BandKind::Continuous => false,
+        BandKind::Categorical => true,
+    };
+    if categorical {
+        Ok(Resampling::Nearest)
+    } else {
+        Ok(options.resampling)
+    }
+}
+
+/// reads the window of the padded tile from a band of the vrt.
+/// with `options.center_from_tile` set, only the margin strips are read from the vrt and the
+/// pixels of the tile itself are copied from its band, which avoids decoding the tile a second
+/// time through the vrt.
+/// a vrt with a different resolution than the tile is resampled to the grid of the tile.
+/// tile: the tile and the index of the band that is padded
+/// returns the window, its pixels and the number of pixels read from the vrt
+fn read_margin_window(
+    vrt: &VrtSource,
+    band_index: isize,
+    new_geotransform: &GeoTransform,
+    margin: Margin,
+    options: &PadOptions,
+    tile: (&Dataset, isize),
+) -> Result<(PixelWindow, Buffer<f64>, usize), Box<dyn Error>> {
+    let (tile_ds, tile_band_index) = tile;
+    let snapping = options.snapping;
+    let (window, buffer_size) = vrt_margin_window(
+        vrt,
+        new_geotransform,
+        tile_ds.raster_size(),
+        margin,
+        snapping,
+    )?;
+    let center = match options.center_from_tile {
+        true => tile_in_window(vrt.geo_transform(), tile_ds, &window, snapping)?,
+        false => None,
+    };
+
+    vrt.with_dataset(|vrt_ds| {
+        let vrt_band = match vrt_ds.rasterband(band_index) {
+            Ok(vrt_band) => vrt_band,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        if let Some(center) = center {
+            let tile_band = match tile_ds.rasterband(tile_band_index) {
+                Ok(tile_band) => tile_band,
+                Err(e) => return Err(Box::new(e)),
+            };
+            let data =
+                read_around_center(&vrt_band, &tile_band, &window, &center, &options.gdal.retry)?;
+            let pixels_read = window.cols * window.rows - center.cols * center.rows;
+            return Ok((window, data, pixels_read));
+        }
+
+        let resampling = if buffer_size == window.size() {
+            None
+        } else {
+            Some(band_resampling(tile_ds, tile_band_index, options)?.algorithm())
+        };
+        let data = options.gdal.retry.read_as::<f64>(
+            &vrt_band,
+            window.offset(),
+            window.size(),
+            buffer_size,
+            resampling,
+        )?;
+        Ok((window, data, window.cols * window.rows))
+    })
+}
+
+/// where the tile lies in the window read from the vrt, in pixels of the window.
+/// `None` when the tile is not on the grid of the vrt at the same resolution or not completely
+/// inside the window, the whole window is then read from the vrt
+fn tile_in_window(
+    vrt_geotransform: &GeoTransform,
+    tile_ds: &Dataset,
+    window: &PixelWindow,
+    snapping: Snapping,
+) -> Result<Option<PixelWindow>, Box<dyn Error>> {
+    let tile_geotransform = match tile_ds.geo_transform() {
+        Ok(tile_geotransform) => tile_geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    if tile_geotransform[1..3] != vrt_geotransform[1..3]
+        || tile_geotransform[4..6] != vrt_geotransform[4..6]
+    {
+        return Ok(None);
+    }
+
+    let (col, row) = world_to_pixel(vrt_geotransform, tile_geotransform[0], tile_geotransform[3]);
+    let (cols, rows) = tile_ds.raster_size();
+    let center = PixelWindow::new(
+        snapping.snap(col)? - window.x_offset,
+        snapping.snap(row)? - window.y_offset,
+        cols,
+        rows,
+    );
+    if center.is_within(window.size()) {
+        Ok(Some(center))
+    } else {
+        Ok(None)
+    }
+}
+
+/// where the window read from the vrt starts in the padded tile, in pixels of the tile. it is not
+/// 0 when the padded tile extends past the upper left corner of the vrt
+fn edge_fill_offset(
+    vrt: &VrtSource,
+    new_geotransform: &GeoTransform,
+    tile_size: (usize, usize),
+    margin: Margin,
+    snapping: Snapping,
+) -> Result<(usize, usize), Box<dyn Error>> {
+    let padded = padded_window(
+        new_geotransform,
+        tile_size,
+        margin,
+        vrt.geo_transform(),
+        snapping,
+    )?;
+    let (window, _) = vrt_margin_window(vrt, new_geotransform, tile_size, margin, snapping)?;
+    // the part of the padded tile before the window, scaled to the grid of the tile
+    let before = PixelWindow::new(
+        0,
+        0,
+        (window.x_offset - padded.x_offset) as usize,
+        (window.y_offset - padded.y_offset) as usize,
+    );
+    Ok(resampled_size(
+        &before,
+        new_geotransform,
+        vrt.geo_transform(),
+    ))
+}
+
+/// a buffer of `size` filled with `fill` and the pixels of `data` placed at `offset`, the part of
+/// `data` that does not fit is dropped
+fn fill_around<T: GdalType + Copy>(
+    data: &Buffer<T>,
+    size: (usize, usize),
+    offset: (usize, usize),
+    fill: T,
+) -> Buffer<T> {
+    let mut filled = vec![fill; size.0 * size.1];
+    let cols = data.size.0.min(size.0.saturating_sub(offset.0));
+    let rows = data.size.1.min(size.1.saturating_sub(offset.1));
+    for row in 0..rows {
+        let source = row * data.size.0;
+        let target = (offset.1 + row) * size.0 + offset.0;
+        filled[target..target + cols].copy_from_slice(&data.data[source..source + cols]);
+    }
+    Buffer { size, data: filled }
+}
+
+/// fills the window with the margin strips read from the vrt band and the center read from the
+/// tile band, reads that fail are tried again with `retry`
+fn read_around_center(
+    vrt_band: &RasterBand,
+    tile_band: &RasterBand,
+    window: &PixelWindow,
+    center: &PixelWindow,
+    retry: &RetryPolicy,
+) -> Result<Buffer<f64>, Box<dyn Error>> {
+    let mut data = vec![0f64; window.cols * window.rows];
+
+    let tile_data = retry.read_as::<f64>(tile_band, (0, 0), center.size(), center.size(), None)?;
+    paste(&mut data, window.cols, center, &tile_data.data);
+
+    for strip in margin_strips(window.size(), center) {
+        let offset = (
+            window.x_offset + strip.x_offset,
+            window.y_offset + strip.y_offset,
+        );
+        let strip_data =
+            retry.read_as::<f64>(vrt_band, offset, strip.size(), strip.size(), None)?;
+        paste(&mut data, window.cols, &strip, &strip_data.data);
+    }
+
+    Ok(Buffer {
+        size: window.size(),
+        data,
+    })
+}
+
+/// a north up copy of `ds` in memory when it is south up or its columns run west, see
+/// `is_north_up`, so the margins of such tiles are read like the margins of any other tile
+fn north_up_copy(ds: &Dataset) -> Result<Option<Dataset>, Box<dyn Error>> {
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    if is_north_up(&geo_transform) {
+        return Ok(None);
+    }
+    let size = ds.raster_size();
+    let flip = (geo_transform[1] < 0.0, geo_transform[5] > 0.0);
+    let mut copy = create_copy(ds, &get_driver("MEM")?, Path::new(""), &[])?;
+    for band_index in 1..=copy.raster_count() {
+        let mut band = match copy.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        flip_band(&mut band, size, flip)?;
+    }
+    if has_dataset_mask(&copy)? {
+        let band = match copy.rasterband(1) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let mut mask = match band.open_mask_band() {
+            Ok(mask) => mask,
+            Err(e) => return Err(Box::new(e)),
+        };
+        flip_band(&mut mask, size, flip)?;
+    }
+    if let Err(e) = copy.set_geo_transform(&north_up_geotransform(&geo_transform, size)) {
+        return Err(Box::new(e));
+    }
+    Ok(Some(copy))
+}
+
+/// reverses the columns of `band` when `flip.0` is set and its rows when `flip.1` is set
+fn flip_band(
+    band: &mut RasterBand,
+    size: (usize, usize),
+    flip: (bool, bool),
+) -> Result<(), Box<dyn Error>> {
+    let data = match band.read_as::<f64>((0, 0), size, size, None) {
+        Ok(data) => data.data,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let (cols, rows) = size;
+    let mut flipped = Vec::with_capacity(data.len());
+    for row in 0..rows {
+        let row = if flip.1 { rows - 1 - row } else { row };
+        let pixels = &data[row * cols..(row + 1) * cols];
+        if flip.0 {
+            flipped.extend(pixels.iter().rev());
+        } else {
+            flipped.extend_from_slice(pixels);
+        }
+    }
+    let buffer = Buffer {
+        size,
+        data: flipped,
+    };
+    match band.write((0, 0), size, &buffer) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// copies the row major pixels of `part` into `data`, a row major buffer `cols` pixels wide
+fn paste(data: &mut [f64], cols: usize, part: &PixelWindow, part_data: &[f64]) {
+    let x = part.x_offset as usize;
+    for (row, part_row) in part_data.chunks(part.cols).enumerate() {
+        let start = (part.y_offset as usize + row) * cols + x;
+        data[start..start + part.cols].copy_from_slice(part_row);
+    }
+}
+
+fn trim_buffered(
+    buffered_raster: &Path,
+    trim_to: &TrimTo,
+    output_raster: &Path,
+    options: &CropOptions,
+) -> Result<TileOutcome, Box<dyn Error>> {
+    // the sidecars of the original are the reference, the buffered file may have lost them
+    let (extent, sidecar_source) = match trim_to {
+        TrimTo::Original(original) => (GeoExtent::from_raster(original)?, original.as_path()),
+        TrimTo::Margin(margin) => (trimmed_extent(buffered_raster, *margin)?, buffered_raster),
+    };
+    let (recorded, (cols, rows)) = {
+        let ds = options.gdal.open(buffered_raster)?;
+        (recorded_margin(&ds)?, ds.raster_size())
+    };
+    if let TrimTo::Margin(margin) = trim_to {
+        check_fixed_margin(recorded, buffered_raster, *margin)?;
+    }
+    if options.backup && output_raster.is_file() {
+        backup_raster(output_raster)?;
+    }
+    let window = crop_raster_to_extent(buffered_raster, output_raster, extent, options)?;
+    // a window that falls short of the buffered raster trims nothing off that side
+    let trimmed = TrimmedMargin {
+        left: window.x_offset.max(0) as usize,
+        top: window.y_offset.max(0) as usize,
+        right: (cols as isize - window.x_offset - window.cols as isize).max(0) as usize,
+        bottom: (rows as isize - window.y_offset - window.rows as isize).max(0) as usize,
+    };
+    if let TrimTo::Original(_) = trim_to {
+        check_trimmed_margin(recorded, buffered_raster, &trimmed);
+    }
+
+    // an output that replaced its original already sits next to its sidecars
+    if options.output.sidecars && sidecar_source != output_raster {
+        copy_sidecars(sidecar_source, output_raster)?;
+    }
+
+    if options.cleanup {
+        remove_cropped_input(buffered_raster, output_raster, &window, &options.output)?;
+    }
+
+    Ok(TileOutcome {
+        trimmed: Some(trimmed),
+        ..TileOutcome::written(window)
+    })
+}
+
+/// keeps `path` as `<name>.bak`. the backup is a hard link where the file system allows it, the
+/// output is renamed over `path` so the backup keeps the old contents
+fn backup_raster(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    let backup = path.with_file_name(file_name);
+    if backup.exists() {
+        fs::remove_file(&backup)?;
+    }
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+    Ok(())
+}
+
+/// crops a raster to the given extent and saves it as a new geotiff.
+/// the extent is snapped to the pixel grid of the input raster and must be fully contained in it.
+/// input: the raster to crop
+/// output: the path to save the cropped raster to
+/// extent: the extent to crop to, see `GeoExtent` for building it from another raster or a bbox,
+/// or `rect.into()` of a `geo_types::Rect` with the `geo` feature
+pub fn crop_to_extent(
+    input: &Path,
+    output: &Path,
+    extent: GeoExtent,
+) -> Result<(), Box<dyn Error>> {
+    crop_to_extent_with_options(input, output, extent, &CropOptions::default())
+}
+
+/// same as `crop_to_extent` but with control over the grid snapping and how the cropped file is
+/// written.
+pub fn crop_to_extent_with_options(
+    input: &Path,
+    output: &Path,
+    extent: GeoExtent,
+    options: &CropOptions,
+) -> Result<(), Box<dyn Error>> {
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+    let window = crop_raster_to_extent(input, output, extent, options)?;
+
+    if options.output.sidecars {
+        copy_sidecars(input, output)?;
+    }
+
+    if options.cleanup {
+        remove_cropped_input(input, output, &window, &options.output)?;
+    }
+
+    Ok(())
+}
+
+/// deletes the input of a crop once the output has been checked to open with the size of the
+/// window and the bands of the input that are written
+fn remove_cropped_input(
+    input: &Path,
+    output: &Path,
+    window: &PixelWindow,
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    let input_bands = output_bands(&open_dataset(input, &[])?, output_options)?.len() as isize;
+    let ds = open_dataset(output, &[])?;
+    if ds.raster_size() != window.size() || ds.raster_count() != input_bands {
+        return Err(format!(
+            "{:?} is {:?} pixels with {} bands, expected {:?} pixels with {} bands. \
+             keeping {:?}",
+            output,
+            ds.raster_size(),
+            ds.raster_count(),
+            window.size(),
+            input_bands,
+            input
+        )
+        .into());
+    }
+    drop(ds);
+
+    if gpkg::split_table_path(input).is_some() {
+        return Err(format!("{:?} is a geopackage table, keeping it", input).into());
+    }
+    fs::remove_file(input)?;
+    Ok(())
+}
+
+fn crop_raster_to_extent(
+    input: &Path,
+    output: &Path,
+    extent: GeoExtent,
+    options: &CropOptions,
+) -> Result<PixelWindow, Box<dyn Error>> {
+    let output_options = &options.output;
+
+    let ds = options.gdal.open(input)?;
+    check_data_types(&ds, input)?;
+
+    let projection = ds.projection();
+
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    // the pixel window of the extent on the grid of the input raster, rasters warped by other
+    // tools may fall short of it by a row or column
+    let window = window_for_extent(&geo_transform, &extent, options.snapping)?;
+    let read_window = match window.clamped_to(ds.raster_size()) {
+        Some(read_window) if read_window == window || options.fill_shortfall => read_window,
+        Some(read_window) => {
+            return Err(format!(
+                "the extent {} is not contained in {:?}, it is short by {} pixels on the left, {} \
+                 on the top, {} on the right and {} on the bottom. fill_shortfall fills them with \
+                 nodata",
+                extent,
+                input,
+                read_window.x_offset - window.x_offset,
+                read_window.y_offset - window.y_offset,
+                (window.x_offset + window.cols as isize)
+                    - (read_window.x_offset + read_window.cols as isize),
+                (window.y_offset + window.rows as isize)
+                    - (read_window.y_offset + read_window.rows as isize)
+            )
+            .into())
+        }
+        None => return Err(format!("the extent {} is outside of {:?}", extent, input).into()),
+    };
+    let target_offset = (
+        read_window.x_offset - window.x_offset,
+        read_window.y_offset - window.y_offset,
+    );
+    let (cols, rows) = window.size();
+
+    let mut new_geo_transform = geo_transform;
+    (new_geo_transform[0], new_geo_transform[3]) = pixel_to_world(
+        &geo_transform,
+        window.x_offset as f64,
+        window.y_offset as f64,
+    );
+
+    let bands = output_bands(&ds, output_options)?;
+    let data_type = cropped_data_type(&ds, &bands, output_options)?;
+    // a cog staged in memory would hold the whole output, streamed crops stage it on disk
+    let create = match options.chunk_rows {
+        Some(_) => create_output_on_disk,
+        None => create_output,
+    };
+    let mut ds_out = create(
+        output,
+        (cols, rows),
+        bands.len() as isize,
+        data_type,
+        output_options,
+    )?;
+
+    if let Err(e) = ds_out.set_geo_transform(&new_geo_transform) {
+        return Err(Box::new(e));
+    };
+
+    if let Err(e) = ds_out.set_projection(&projection) {
+        return Err(Box::new(e));
+    };
+
+    let mut output_nodata = Vec::new();
+    for (band_out_index, &band_index) in (1..).zip(&bands) {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        let mut band_out = match ds_out.rasterband(band_out_index) {
+            Ok(band_out) => band_out,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        let source_nodata = band.no_data_value();
+        let policy = output_options.band_policy_of(band_index);
+        let policy_nodata = policy.and_then(|policy| policy.nodata);
+        let conversion = BandConversion::new(output_options, policy_nodata.or(source_nodata))?;
+        let nodata = match &conversion {
+            Some(conversion) => conversion.nodata,
+            None => policy_nodata.or(source_nodata),
+        };
+        if read_window != window {
+            // bands without a nodata value are filled with 0
+            let fill = output_options.fill_value(band_index, nodata, &EdgeFill::Nodata);
+            if let Err(e) = band_out.fill(fill, None) {
+                return Err(Box::new(e));
+            }
+        }
+        copy_window_by_blocks(
+            &band,
+            &mut band_out,
+            &read_window,
+            target_offset,
+            (options.chunk_rows, &options.gdal.retry),
+            |data| {
+                if let Some(policy_nodata) = policy_nodata {
+                    replace_nodata(data, source_nodata, policy_nodata);
+                }
+                if let Some(conversion) = &conversion {
+                    conversion.convert(data);
+                }
+            },
+        )?;
+        if conversion.is_some() || policy_nodata.is_some() || read_window != window {
+            output_nodata.push(nodata);
+        } else {
+            output_nodata.push(None);
+        }
+    }
+    set_nodata_values(&ds_out, &output_nodata)?;
+
+    copy_color_interpretation(&ds, &ds_out, &bands)?;
+    copy_palettes(&ds, &ds_out, &bands)?;
+    if has_dataset_mask(&ds)? {
+        copy_dataset_mask(
+            &ds,
+            &ds_out,
+            &read_window,
+            target_offset,
+            options.chunk_rows,
+        )?;
+    }
+
+    finish_output(ds_out, output, output_options)?;
+
+    Ok(window)
+}
+
+/// copies `window` of `band` to `band_out` one strip of rows at a time, so only a strip of the
+/// window is held in memory however large the raster is
+/// target_offset: the pixel of `band_out` the upper left pixel of the window is copied to
+/// chunk_rows: the rows of a strip, the block height of `band` when not set
+/// retry: how often a strip whose read failed is read again
+/// convert: applied to every strip on the way, e.g. to convert the pixels to the type of
+/// `band_out`
+fn copy_window_by_blocks(
+    band: &RasterBand,
+    band_out: &mut RasterBand,
+    window: &PixelWindow,
+    target_offset: (isize, isize),
+    (chunk_rows, retry): (Option<usize>, &RetryPolicy),
+    convert: impl Fn(&mut [f64]),
+) -> Result<(), Box<dyn Error>> {
+    let block_rows = chunk_rows.unwrap_or(band.block_size().1).max(1);
+    let (cols, rows) = window.size();
+    let mut row = 0;
+    while row < rows {
+        check_tile_deadline()?;
+        let strip_rows = block_rows.min(rows - row);
+        let offset = (window.x_offset, window.y_offset + row as isize);
+        let mut data =
+            retry.read_as::<f64>(band, offset, (cols, strip_rows), (cols, strip_rows), None)?;
+        convert(&mut data.data);
+        let target_row = (target_offset.0, target_offset.1 + row as isize);
+        if let Err(e) = band_out.write(target_row, (cols, strip_rows), &data) {
+            return Err(Box::new(e));
+        };
+        row += strip_rows;
+    }
+    Ok(())
+}
+
+/// sets the nodata value of each band of an output, in band order, bands without a value are left
+/// as they are
+fn set_nodata_values(ds: &Dataset, nodata_values: &[Option<f64>]) -> Result<(), Box<dyn Error>> {
+    for (band_index, nodata) in (1..).zip(nodata_values) {
+        if nodata.is_none() {
+            continue;
+        }
+        let mut band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        if let Err(e) = band.set_no_data_value(*nodata) {
+            return Err(Box::new(e));
+        };
+    }
+    Ok(())
+}
+
+/// the output paths claimed by the tiles of a batch. a tile whose output an earlier tile claimed,
+/// e.g. a tile with the same id or `a.tif` next to `a.img`, is not processed so it can not
+/// overwrite that output, it fails instead
+#[derive(Default)]
+struct Outputs {
+    claimed: HashMap<PathBuf, PathBuf>,
+    collisions: Vec<TileReport>,
+}
+
+impl Outputs {
+    /// claims `output` for `input`, false when another tile claimed it before
+    fn claim(&mut self, input: &Path, output: &Path) -> bool {
+        let first = match self.claimed.get(output) {
+            Some(first) => first,
+            None => {
+                self.claimed
+                    .insert(output.to_path_buf(), input.to_path_buf());
+                return true;
+            }
+        };
+        let error = format!("{:?} is also the output of {:?}", output, first);
+        message(&format!("{}. Skipping {:?}...", error, input));
+        self.collisions.push(TileReport {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+            status: TileStatus::Failed(error),
+            window: None,
+            seconds: 0.0,
+            bytes_read: 0,
+            bytes_written: 0,
+            clamped: false,
+            margin: None,
+            trimmed: None,
+        });
+        false
+    }
+
+    /// the reports of the tiles that were not processed, passed to `listener`
+    fn collisions(self, listener: &TileListener) -> Vec<TileReport> {
+        for report in &self.collisions {
+            listener.notify(report);
+        }
+        self.collisions
+    }
+}
+
+/// runs the work of a single tile, unless the batch was cancelled or stopped after failed tiles,
+/// and times it, `error_message` is logged when the work fails. `listener` is told when the work
+/// starts
+fn run_tile(
+    input: &Path,
+    output: &Path,
+    error_message: &str,
+    (cancel, failures, timeout): (&CancelToken, &FailureLimit, Option<Duration>),
+    listener: &TileListener,
+    f: impl FnOnce() -> Result<TileOutcome, Box<dyn Error>>,
+) -> TileReport {
+    let start = Instant::now();
+    let result = if cancel.is_cancelled() {
+        Ok(TileOutcome::skipped(SkipReason::Cancelled))
+    } else if failures.is_reached() {
+        Ok(TileOutcome::skipped(SkipReason::Aborted))
+    } else {
+        listener.started(input, output);
+        let _deadline = TileDeadline::start(timeout, cancel);
+        f()
+    };
+    let (status, window, bytes_read, clamped, trimmed) = match result {
+        Ok(outcome) => (
+            outcome.status,
+            outcome.window,
+            outcome.bytes_read,
+            outcome.clamped,
+            outcome.trimmed,
+        ),
+        Err(e) => {
+            message(error_message);
+            failures.fail();
+            // the output is only renamed once it is complete, drop what was written of it
+            remove_partial(output);
+            (TileStatus::Failed(e.to_string()), None, 0, false, None)
+        }
+    };
+    let bytes_written = match status {
+        TileStatus::Written => output_bytes(output),
+        _ => 0,
+    };
+
+    TileReport {
+        input: input.to_path_buf(),
+        output: output.to_path_buf(),
+        status,
+        window,
+        seconds: start.elapsed().as_secs_f64(),
+        bytes_read,
+        bytes_written,
+        clamped,
+        margin: None,
+        trimmed,
+    }
+}
+
+/// calls `f` for every item, spreading the items over `jobs` worker threads, and returns the
+/// results in the order of the items.
+/// a `jobs` value of 0 or 1 processes the items in order on the calling thread.
+fn run_parallel<T: Sync, R: Send>(items: &[T], jobs: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    if jobs <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        match items.get(index) {
+                            Some(item) => results.push((index, f(item))),
+                            None => return results,
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
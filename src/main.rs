@@ -1,22 +1,691 @@
 mod args;
-use args::BufferCliArgs;
-use clap::Parser;
-use vrt_buffer::{crop_down_to_size, vrt_buffer};
+mod config;
+use args::{
+    BufferCliArgs, CompletionsCommand, DiffCommand, ExtentCommand, GdalArgs, InfoCommand,
+    MosaicCommand, OutputArgs, RoundTripCommand, VerifyCommand,
+};
+use clap::{CommandFactory, Parser};
+use signal_hook::consts::SIGINT;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitCode},
+    sync::mpsc,
+    thread::{self, JoinHandle},
+};
+use vrt_buffer::{
+    analyze_with_index, crop_down_to_size_with_options, crop_fixed_with_options,
+    crop_in_place_with_options, diff_dirs, mosaic_tiles, neighbor_buffer_with_options,
+    pad_process_crop, padded_extent, raster_files, read_margin_overrides, set_message_sink,
+    verify_manifest, vrt_buffer_grid, vrt_buffer_pyramid, vrt_buffer_with_options, BatchReport,
+    CancelToken, CropOptions, ErrorPolicy, GdalConfig, MessageSink, MosaicOptions, OutputFormat,
+    OutputOptions, PadOptions, PipelineOptions, TileEvent, TileIndex, TileListener, TileStatus,
+};
 
-fn main() {
-    let cli_args = BufferCliArgs::parse();
-    match cli_args.subcmd {
+/// some rasters failed but the batch ran to the end
+const EXIT_TILES_FAILED: u8 = 2;
+/// the batch could not be started or was aborted
+const EXIT_FATAL: u8 = 3;
+/// the batch was stopped with ctrl-c, the tiles that were started are complete
+const EXIT_CANCELLED: u8 = 130;
+
+/// cancels the batch on the first ctrl-c and exits right away on the second
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::new();
+    // the shutdown is registered first so it only fires when the flag was already set
+    let registered = signal_hook::flag::register_conditional_shutdown(
+        SIGINT,
+        EXIT_CANCELLED as i32,
+        cancel.flag(),
+    )
+    .and_then(|_| signal_hook::flag::register(SIGINT, cancel.flag()));
+    if let Err(e) = registered {
+        eprintln!("Warning: could not install the ctrl-c handler: {}", e);
+    }
+    cancel
+}
+
+fn output_options(output_args: &OutputArgs) -> OutputOptions {
+    let mut output_options = OutputOptions::default()
+        .overviews(output_args.overviews.clone(), &output_args.resampling)
+        .sidecars(output_args.sidecars)
+        .statistics(output_args.stats)
+        .bigtiff(output_args.bigtiff)
+        .format(output_args.format)
+        .resume(output_args.resume)
+        .stac(output_args.stac)
+        .provenance(output_args.provenance)
+        .error_log(output_args.error_log)
+        .manifest(output_args.manifest)
+        .allow_overlapping_dirs(output_args.allow_overlapping_dirs)
+        .copy_non_raster_files(output_args.copy_non_raster_files)
+        .duplicate_names(output_args.duplicate_names)
+        .rounding(output_args.rounding)
+        .out_of_range(output_args.out_of_range)
+        .compression_threads(output_args.threads_compute)
+        .bands(output_args.bands.clone());
+    if let Some(output_type) = output_args.output_type {
+        output_options = output_options.output_type(output_type);
+    }
+    if let Some(archive) = &output_args.archive {
+        output_options = output_options.archive(archive.clone());
+    }
+    if let Some(tile_id) = &output_args.tile_id {
+        output_options = output_options.tile_id(tile_id.clone());
+    }
+    if let Some(nodata) = output_args.ot_nodata {
+        output_options = output_options.output_nodata(nodata);
+    }
+    for (band_index, policy) in &output_args.band_policies {
+        output_options = output_options.band_policy(*band_index, *policy);
+    }
+    match &output_args.shard {
+        Some(template) => output_options.shard(template.clone()),
+        None => output_options,
+    }
+}
+
+fn error_policy(output_args: &OutputArgs) -> ErrorPolicy {
+    match (output_args.fail_fast, output_args.max_failures) {
+        (true, _) => ErrorPolicy::FailFast,
+        (false, Some(failures)) => ErrorPolicy::FailAfterNFailures(failures),
+        (false, None) => ErrorPolicy::ContinueAndReport,
+    }
+}
+
+/// the paths of the --file-list, separated by NUL when the list holds one and by newlines
+/// otherwise. - reads the list from stdin
+fn file_list(output_args: &OutputArgs) -> Result<Option<Vec<PathBuf>>, Box<dyn Error>> {
+    let path = match &output_args.file_list {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        bytes
+    } else {
+        fs::read(path)?
+    };
+    let list = match String::from_utf8(bytes) {
+        Ok(list) => list,
+        Err(_) => return Err(format!("the file list {:?} is not utf-8", path).into()),
+    };
+    let separator = if list.contains('\0') { '\0' } else { '\n' };
+    let files = list
+        .split(separator)
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    Ok(Some(files))
+}
+
+/// a listener that shows the progress of a batch on stderr, and the thread that draws it, which
+/// ends once the listener and its clones are dropped
+fn progress_listener() -> (TileListener, JoinHandle<()>) {
+    let (sender, events) = mpsc::channel();
+    let progress = thread::spawn(move || {
+        let mut running = HashSet::new();
+        let (mut done, mut failed) = (0, 0);
+        for event in events {
+            match event {
+                TileEvent::TileStarted { input, .. } => {
+                    running.insert(input);
+                }
+                TileEvent::TileFinished(report) => {
+                    running.remove(&report.input);
+                    done += 1;
+                }
+                TileEvent::TileFailed(report) => {
+                    running.remove(&report.input);
+                    failed += 1;
+                }
+            }
+            eprint!(
+                "\r{} done, {} failed, {} running",
+                done,
+                failed,
+                running.len()
+            );
+        }
+        eprintln!();
+    });
+    (TileListener::channel(sender), progress)
+}
+
+fn gdal_config(gdal_args: &GdalArgs) -> GdalConfig {
+    let mut gdal_config = GdalConfig::default();
+    for (key, value) in &gdal_args.gdal_config {
+        gdal_config = gdal_config.config_option(key, value);
+    }
+    for (key, value) in &gdal_args.open_options {
+        gdal_config = gdal_config.open_option(key, value);
+    }
+    for table in &gdal_args.tables {
+        gdal_config = gdal_config.table(table);
+    }
+    gdal_config.retry(gdal_args.retries, gdal_args.retry_delay)
+}
+
+fn main() -> ExitCode {
+    let args = match config::args_with_config(std::env::args_os().collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    let cli_args = BufferCliArgs::parse_from(args);
+    let sink = match &cli_args.log_file {
+        Some(path) => match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => MessageSink::File(file),
+            Err(e) => {
+                eprintln!("Error: could not open the log file {:?}: {}", path, e);
+                return ExitCode::from(EXIT_FATAL);
+            }
+        },
+        None if cli_args.quiet => MessageSink::Quiet,
+        None => MessageSink::Stderr,
+    };
+    set_message_sink(sink);
+    let cancel = cancel_on_ctrl_c();
+    let mut progress = None;
+    let result = match cli_args.subcmd {
+        args::Subaction::Info(info_args) => return info(&info_args, cli_args.json),
+        args::Subaction::Extent(extent_args) => return extent(&extent_args),
+        args::Subaction::Verify(verify_args) => return verify(&verify_args, cli_args.json),
+        args::Subaction::Mosaic(mosaic_args) => return mosaic(&mosaic_args, cli_args.json),
+        args::Subaction::Diff(diff_args) => return diff(&diff_args, cli_args.json),
+        args::Subaction::Completions(completions_args) => return completions(&completions_args),
+        args::Subaction::RoundTrip(round_trip_args) => {
+            return round_trip(&round_trip_args, cli_args.json, cli_args.print0, cancel)
+        }
         args::Subaction::Pad(pad_args) => {
-            vrt_buffer(
-                &pad_args.input,
-                &pad_args.output,
-                &pad_args.vrt,
-                pad_args.pad as usize,
-            )
-            .unwrap();
+            let mut options = PadOptions::default()
+                .jobs(pad_args.jobs)
+                .pass_through_outside(pad_args.pass_through_outside)
+                .metadata_only(pad_args.metadata_only)
+                .snapping(pad_args.snap)
+                .center_from_tile(!pad_args.center_from_vrt)
+                .skip_empty(pad_args.skip_empty)
+                .resampling(pad_args.margin_resampling)
+                .order(pad_args.order)
+                .index(pad_args.index)
+                .margin_policy(pad_args.margin_policy.clone())
+                .cancel(cancel.clone());
+            if let Some(threads_io) = pad_args.threads_io {
+                options = options.io_threads(threads_io);
+            }
+            if let Some(calc) = &pad_args.calc {
+                options = options.calc(calc.clone());
+            }
+            if let Some(aoi) = &pad_args.aoi {
+                options = options.aoi(*aoi);
+            }
+            if let Some(fill) = &pad_args.fill {
+                options = options.edge_fill(fill.clone());
+            }
+            if let Some(mosaic_vrt) = &pad_args.mosaic_vrt {
+                options = options.mosaic_vrt(mosaic_vrt.clone());
+            }
+            if let Some(fallback_vrt) = &pad_args.fallback_vrt {
+                options = options.fallback_vrt(fallback_vrt.clone());
+            }
+            if let Some(margin_file) = &pad_args.margin_file {
+                match read_margin_overrides(margin_file) {
+                    Ok(overrides) => options = options.margin_overrides(overrides),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        return ExitCode::from(EXIT_FATAL);
+                    }
+                }
+            }
+            for (band_index, vrt_file) in &pad_args.band_vrts {
+                options = options.band_vrt(*band_index, vrt_file.clone());
+            }
+            for (band_index, vrt_band) in &pad_args.band_map {
+                options = options.band_map(*band_index, *vrt_band);
+            }
+            for (band_index, kind) in &pad_args.band_kinds {
+                options = options.band_kind(*band_index, *kind);
+            }
+            if let Some(max_tiles) = pad_args.output_args.max_tiles {
+                options = options.max_tiles(max_tiles);
+            }
+            if let Some(max_duration) = pad_args.output_args.max_duration {
+                options = options.max_duration(max_duration);
+            }
+            if let Some(tile_timeout) = pad_args.output_args.tile_timeout {
+                options = options.tile_timeout(tile_timeout);
+            }
+            if pad_args.output_args.progress {
+                let (listener, handle) = progress_listener();
+                options = options.listener(listener);
+                progress = Some(handle);
+            }
+            match file_list(&pad_args.output_args) {
+                Ok(Some(files)) => options = options.file_list(files),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::from(EXIT_FATAL);
+                }
+            }
+            let mut output = output_options(&pad_args.output_args);
+            // the stubs are always vrts
+            if pad_args.metadata_only {
+                output = output.format(OutputFormat::VirtualVrt);
+            }
+            let options = options
+                .disk_check(pad_args.output_args.disk_check)
+                .error_policy(error_policy(&pad_args.output_args))
+                .output(output)
+                .gdal(gdal_config(&pad_args.gdal_args));
+            let margin = pad_args.margin();
+            match (&pad_args.reference, &pad_args.input, pad_args.tile_size) {
+                (Some(reference), _, Some(tile_size)) => {
+                    vrt_buffer_grid(reference, &pad_args.output, tile_size, margin, &options)
+                }
+                (None, _, Some(_)) => Err("--tile-size needs --reference".into()),
+                (_, None, None) => Err("--input is needed without --tile-size".into()),
+                (Some(reference), Some(input), None) if !pad_args.levels.is_empty() => {
+                    vrt_buffer_pyramid(
+                        input,
+                        &pad_args.output,
+                        reference,
+                        margin,
+                        &pad_args.levels,
+                        &options,
+                    )
+                }
+                (None, Some(_), None) if !pad_args.levels.is_empty() => {
+                    Err("--levels needs --reference".into())
+                }
+                (Some(reference), Some(input), None) => {
+                    vrt_buffer_with_options(input, &pad_args.output, reference, margin, &options)
+                }
+                (None, Some(input), None) => {
+                    neighbor_buffer_with_options(input, &pad_args.output, margin, &options)
+                }
+            }
         }
         args::Subaction::Crop(crop_args) => {
-            crop_down_to_size(&crop_args.original, &crop_args.input, &crop_args.output).unwrap();
+            let mut options = CropOptions::default()
+                .snapping(crop_args.snap)
+                .cleanup(crop_args.cleanup)
+                .jobs(crop_args.jobs)
+                .backup(crop_args.backup)
+                .fill_shortfall(crop_args.fill_shortfall)
+                .cancel(cancel.clone())
+                .disk_check(crop_args.output_args.disk_check)
+                .error_policy(error_policy(&crop_args.output_args))
+                .output(output_options(&crop_args.output_args))
+                .gdal(gdal_config(&crop_args.gdal_args));
+            if let Some(max_tiles) = crop_args.output_args.max_tiles {
+                options = options.max_tiles(max_tiles);
+            }
+            if let Some(max_duration) = crop_args.output_args.max_duration {
+                options = options.max_duration(max_duration);
+            }
+            if let Some(tile_timeout) = crop_args.output_args.tile_timeout {
+                options = options.tile_timeout(tile_timeout);
+            }
+            if let Some(chunk_rows) = crop_args.chunk_rows {
+                options = options.chunk_rows(chunk_rows);
+            }
+            if crop_args.output_args.progress {
+                let (listener, handle) = progress_listener();
+                options = options.listener(listener);
+                progress = Some(handle);
+            }
+            match file_list(&crop_args.output_args) {
+                Ok(Some(files)) => options = options.file_list(files),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return ExitCode::from(EXIT_FATAL);
+                }
+            }
+            match (&crop_args.original, crop_args.pixels, &crop_args.output) {
+                (_, Some(pixels), Some(output)) => {
+                    crop_fixed_with_options(&crop_args.input, output, pixels as usize, &options)
+                }
+                (Some(original), None, Some(output)) => {
+                    crop_down_to_size_with_options(original, &crop_args.input, output, &options)
+                }
+                (Some(original), None, None) => {
+                    crop_in_place_with_options(original, &crop_args.input, &options)
+                }
+                _ => Err("crop needs --original or --pixels, and --output or --in-place".into()),
+            }
+        }
+    };
+
+    // the options of the batch, and with them the listener, are dropped by now
+    if let Some(progress) = progress {
+        let _ = progress.join();
+    }
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    if cli_args.json {
+        print_json(&report);
+    } else if cli_args.print0 {
+        print0_outputs(&report);
+    } else if let Some(mosaic) = &report.mosaic {
+        println!("{}", mosaic.display());
+    }
+
+    if report.cancelled() {
+        eprintln!("Cancelled, the remaining rasters were skipped");
+        ExitCode::from(EXIT_CANCELLED)
+    } else if report.aborted() {
+        eprintln!("Stopped after failed rasters, the remaining rasters were skipped");
+        ExitCode::from(EXIT_TILES_FAILED)
+    } else if report.failed() > 0 {
+        ExitCode::from(EXIT_TILES_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// prints the analysis of a tile set, exits with 2 when it found problems
+fn info(info_args: &InfoCommand, json: bool) -> ExitCode {
+    let analysis = match TileIndex::load(&info_args.input, &[], info_args.index).and_then(|index| {
+        analyze_with_index(
+            &index,
+            info_args.reference.as_deref(),
+            info_args.pad as usize,
+        )
+    }) {
+        Ok(analysis) => analysis,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
         }
+    };
+    if json {
+        match serde_json::to_string_pretty(&analysis) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing the analysis: {}", e),
+        }
+    } else {
+        println!("{}", analysis);
+    }
+
+    if analysis.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_TILES_FAILED)
+    }
+}
+
+fn verify(verify_args: &VerifyCommand, json: bool) -> ExitCode {
+    let check = match verify_manifest(&verify_args.input, verify_args.jobs) {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&check) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing the check: {}", e),
+        }
+    } else {
+        print!("{}", check);
+    }
+
+    if check.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_TILES_FAILED)
+    }
+}
+
+fn mosaic(mosaic_args: &MosaicCommand, json: bool) -> ExitCode {
+    let options = MosaicOptions::default()
+        .output(
+            OutputOptions::default()
+                .format(mosaic_args.format)
+                .bigtiff(mosaic_args.bigtiff),
+        )
+        .allow_gaps(mosaic_args.allow_gaps);
+    let check = match mosaic_tiles(&mosaic_args.input, &mosaic_args.output, &options) {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&check) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing the check: {}", e),
+        }
+    } else {
+        print!("{}", check);
+    }
+    ExitCode::SUCCESS
+}
+
+fn completions(completions_args: &CompletionsCommand) -> ExitCode {
+    clap_complete::generate(
+        completions_args.shell,
+        &mut BufferCliArgs::command(),
+        "vrt_buffer",
+        &mut std::io::stdout(),
+    );
+    ExitCode::SUCCESS
+}
+
+fn diff(diff_args: &DiffCommand, json: bool) -> ExitCode {
+    let diff = match diff_dirs(
+        &diff_args.first,
+        &diff_args.second,
+        diff_args.tolerance,
+        diff_args.jobs,
+    ) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&diff) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing the diff: {}", e),
+        }
+    } else {
+        print!("{}", diff);
+    }
+
+    if diff.is_identical() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_TILES_FAILED)
+    }
+}
+
+fn extent(extent_args: &ExtentCommand) -> ExitCode {
+    let rasters = if extent_args.input.is_dir() {
+        match raster_files(&extent_args.input, &[]) {
+            Ok(rasters) => rasters,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::from(EXIT_FATAL);
+            }
+        }
+    } else {
+        vec![extent_args.input.clone()]
+    };
+
+    let mut features = Vec::new();
+    for raster in &rasters {
+        let extent = match padded_extent(raster, extent_args.pad as usize) {
+            Ok(extent) => extent,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::from(EXIT_FATAL);
+            }
+        };
+        if extent_args.wkt {
+            println!("{}\t{}", raster.display(), extent.to_wkt());
+            continue;
+        }
+        let ring: Vec<[f64; 2]> = extent.ring().iter().map(|&(x, y)| [x, y]).collect();
+        features.push(serde_json::json!({
+            "type": "Feature",
+            "properties": { "path": raster },
+            "geometry": { "type": "Polygon", "coordinates": [ring] },
+        }));
+    }
+    if !extent_args.wkt {
+        let collection = serde_json::json!({ "type": "FeatureCollection", "features": features });
+        println!("{}", collection);
+    }
+    ExitCode::SUCCESS
+}
+
+/// pads, runs the --exec command and crops, exits like the other subcommands
+fn round_trip(args: &RoundTripCommand, json: bool, print0: bool, cancel: CancelToken) -> ExitCode {
+    let gdal = gdal_config(&args.gdal_args);
+    let mut pad = PadOptions::default()
+        .jobs(args.jobs)
+        .cancel(cancel.clone())
+        .error_policy(error_policy(&args.output_args))
+        .gdal(gdal.clone());
+    // the crop only sees the rasters that were padded
+    if let Some(max_tiles) = args.output_args.max_tiles {
+        pad = pad.max_tiles(max_tiles);
+    }
+    if let Some(max_duration) = args.output_args.max_duration {
+        pad = pad.max_duration(max_duration);
+    }
+    if let Some(tile_timeout) = args.output_args.tile_timeout {
+        pad = pad.tile_timeout(tile_timeout);
+    }
+    match file_list(&args.output_args) {
+        Ok(Some(files)) => pad = pad.file_list(files),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    }
+    let mut crop = CropOptions::default()
+        .jobs(args.jobs)
+        .cancel(cancel)
+        .error_policy(error_policy(&args.output_args))
+        .output(output_options(&args.output_args))
+        .gdal(gdal);
+    if let Some(tile_timeout) = args.output_args.tile_timeout {
+        crop = crop.tile_timeout(tile_timeout);
+    }
+    let mut progress = None;
+    if args.output_args.progress {
+        let (listener, handle) = progress_listener();
+        pad = pad.listener(listener.clone());
+        crop = crop.listener(listener);
+        progress = Some(handle);
+    }
+    let mut options = PipelineOptions::default()
+        .pad(pad)
+        .crop(crop)
+        .keep_temp(args.keep_temp)
+        .skip_disk_check(args.skip_disk_check);
+    if let Some(temp_dir) = &args.temp_dir {
+        options = options.temp_root(temp_dir.clone());
+    }
+
+    let report = pad_process_crop(
+        &args.input,
+        args.reference.as_deref(),
+        &args.output,
+        args.pad as usize,
+        |input, output| run_command(&args.exec, input, output),
+        &options,
+    );
+    drop(options);
+    if let Some(progress) = progress {
+        let _ = progress.join();
+    }
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(EXIT_FATAL);
+        }
+    };
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing the report: {}", e),
+        }
+    } else if print0 {
+        print0_outputs(&report.crop);
+    } else if let Some(temp_dir) = &report.temp_dir {
+        println!("{}", temp_dir.display());
+    }
+
+    if report.cancelled() {
+        eprintln!("Cancelled, the remaining rasters were skipped");
+        ExitCode::from(EXIT_CANCELLED)
+    } else if report.aborted() {
+        eprintln!("Stopped after failed rasters, the remaining rasters were skipped");
+        ExitCode::from(EXIT_TILES_FAILED)
+    } else if report.failed() > 0 {
+        ExitCode::from(EXIT_TILES_FAILED)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// runs `command` through the shell with {input} and {output} replaced by the directories
+fn run_command(command: &str, input: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    let command = command
+        .replace("{input}", &input.display().to_string())
+        .replace("{output}", &output.display().to_string());
+    #[cfg(windows)]
+    let status = Command::new("cmd").args(["/C", &command]).status()?;
+    #[cfg(not(windows))]
+    let status = Command::new("sh").args(["-c", &command]).status()?;
+    if !status.success() {
+        return Err(format!("{:?} failed with {}", command, status).into());
+    }
+    Ok(())
+}
+
+/// prints the outputs of the run that exist, including those that were up to date, each
+/// followed by a NUL
+fn print0_outputs(report: &BatchReport) {
+    let mut stdout = std::io::stdout().lock();
+    for tile in &report.tiles {
+        if matches!(tile.status, TileStatus::Failed(_)) || !tile.output.exists() {
+            continue;
+        }
+        let mut path = tile.output.as_os_str().as_encoded_bytes().to_vec();
+        path.push(0);
+        if let Err(e) = stdout.write_all(&path) {
+            eprintln!("Error writing the outputs: {}", e);
+            return;
+        }
+    }
+}
+
+fn print_json(report: &BatchReport) {
+    match serde_json::to_string_pretty(report) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Error serializing the report: {}", e),
     }
 }
@@ -0,0 +1,149 @@
+//! fixity information for the outputs of a batch.
+//!
+//! `manifest.sha256` in the output directory holds a line per output in the format of
+//! `sha256sum`: the sha256 of the file and its path relative to the output directory, so
+//! `sha256sum -c manifest.sha256` checks it as well as `verify_manifest`. the entries of outputs
+//! of earlier runs into the same directory are kept as long as their files exist.
+use crate::{run_parallel, TileReport, TileStatus};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// the name of the manifest in the output directory
+pub const MANIFEST_FILE_NAME: &str = "manifest.sha256";
+
+/// the outcome of checking the files of a directory against its manifest.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ManifestCheck {
+    /// the number of files whose sha256 matches
+    pub verified: usize,
+    /// the files whose sha256 differs from the manifest
+    pub mismatched: Vec<PathBuf>,
+    /// the files of the manifest that do not exist
+    pub missing: Vec<PathBuf>,
+}
+
+impl ManifestCheck {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for ManifestCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} files verified", self.verified)?;
+        for path in &self.mismatched {
+            writeln!(f, "  changed: {}", path.display())?;
+        }
+        for path in &self.missing {
+            writeln!(f, "  missing: {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// checks the files listed in the manifest of `dir` against their sha256, see
+/// `OutputOptions::manifest`.
+/// jobs: the number of files hashed at the same time
+pub fn verify_manifest(dir: &Path, jobs: usize) -> Result<ManifestCheck, Box<dyn Error>> {
+    let path = dir.join(MANIFEST_FILE_NAME);
+    let entries = match fs::read_to_string(&path) {
+        Ok(manifest) => parse(&manifest),
+        Err(e) => return Err(format!("could not read {:?}: {}", path, e).into()),
+    };
+    let entries: Vec<(String, String)> = entries.into_iter().collect();
+    let hashes = run_parallel(&entries, jobs, |(relative, _)| {
+        sha256_file(&dir.join(relative))
+    });
+
+    let mut check = ManifestCheck::default();
+    for ((relative, expected), hash) in entries.iter().zip(hashes) {
+        match hash {
+            Ok(hash) if hash == *expected => check.verified += 1,
+            Ok(_) => check.mismatched.push(PathBuf::from(relative)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                check.missing.push(PathBuf::from(relative))
+            }
+            Err(e) => return Err(format!("could not read {:?}: {}", dir.join(relative), e).into()),
+        }
+    }
+    Ok(check)
+}
+
+/// writes the sha256 of the outputs of `reports` that exist to the manifest of `output_dir`,
+/// replacing their entries and keeping the entries of other files that still exist
+pub(crate) fn write_manifest(
+    output_dir: &Path,
+    reports: &[TileReport],
+    jobs: usize,
+) -> Result<(), Box<dyn Error>> {
+    let path = output_dir.join(MANIFEST_FILE_NAME);
+    let mut entries = match fs::read_to_string(&path) {
+        Ok(manifest) => parse(&manifest),
+        Err(_) => BTreeMap::new(),
+    };
+    entries.retain(|relative, _| output_dir.join(relative).is_file());
+
+    let outputs: Vec<&PathBuf> = reports
+        .iter()
+        .filter(|report| !matches!(report.status, TileStatus::Failed(_)))
+        .map(|report| &report.output)
+        .filter(|output| output.is_file())
+        .collect();
+    let hashes = run_parallel(&outputs, jobs, |output| sha256_file(output));
+    for (output, hash) in outputs.into_iter().zip(hashes) {
+        let hash = match hash {
+            Ok(hash) => hash,
+            Err(e) => return Err(format!("could not hash {:?}: {}", output, e).into()),
+        };
+        entries.insert(relative_name(output_dir, output), hash);
+    }
+
+    let mut manifest = String::new();
+    for (relative, hash) in &entries {
+        manifest.push_str(&format!("{}  {}\n", hash, relative));
+    }
+    fs::write(path, manifest)?;
+    Ok(())
+}
+
+/// the path of `output` relative to `output_dir` with `/` separators, like `sha256sum` prints it
+fn relative_name(output_dir: &Path, output: &Path) -> String {
+    let relative = output.strip_prefix(output_dir).unwrap_or(output);
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    components.join("/")
+}
+
+/// the entries of a manifest by relative path, lines that are not `<sha256>  <path>` are ignored.
+/// the binary mode marker of `sha256sum -b` is accepted
+fn parse(manifest: &str) -> BTreeMap<String, String> {
+    manifest
+        .lines()
+        .filter_map(|line| {
+            let (hash, relative) = line.split_once(' ')?;
+            let relative = relative.strip_prefix([' ', '*'])?;
+            let is_sha256 = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit());
+            is_sha256.then(|| (relative.to_string(), hash.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// the sha256 of the file at `path` as lowercase hex
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
@@ -0,0 +1,124 @@
+//! the margin a padded output was written with, kept in its metadata so a crop can tell a padded
+//! raster from one that was never padded.
+//!
+//! padded outputs that hold pixels get these items in the default metadata domain:
+//! `VRT_BUFFER_MARGIN` the margin in pixels, `x,y` for a margin per axis, `VRT_BUFFER_CLAMPED`
+//! when the margin stops at the edge of the reference, `VRT_BUFFER_SOURCE` the path of the
+//! reference, `VRT_BUFFER_SOURCE_SHA256` the sha256 of a vrt reference, `VRT_BUFFER_VERSION` the
+//! version of the crate and `VRT_BUFFER_CREATED` when the output was written. processing steps
+//! that drop the metadata leave the crop without the check.
+use crate::{geometry::Margin, messages::message, stac::rfc3339, TrimmedMargin, VrtSource};
+use gdal::{Dataset, Metadata};
+use std::{error::Error, path::Path, time::SystemTime};
+
+/// the metadata item that holds the margin of a padded output
+pub const MARGIN_METADATA_KEY: &str = "VRT_BUFFER_MARGIN";
+
+/// writes the margin and where it was read from to the metadata of the padded output `ds`
+pub(crate) fn write_margin_metadata(
+    ds: &mut Dataset,
+    margin: Margin,
+    clamped: bool,
+    vrt: &VrtSource,
+) -> Result<(), Box<dyn Error>> {
+    let margin = match margin {
+        Margin::Uniform(margin) => margin.to_string(),
+        Margin::PerAxis { x, y } => format!("{},{}", x, y),
+    };
+    let mut items = vec![
+        (MARGIN_METADATA_KEY, margin),
+        ("VRT_BUFFER_SOURCE", vrt.path().display().to_string()),
+        ("VRT_BUFFER_VERSION", env!("CARGO_PKG_VERSION").to_string()),
+        ("VRT_BUFFER_CREATED", rfc3339(SystemTime::now())?),
+    ];
+    if let Some(sha256) = vrt.sha256() {
+        items.push(("VRT_BUFFER_SOURCE_SHA256", sha256.to_string()));
+    }
+    if clamped {
+        items.push(("VRT_BUFFER_CLAMPED", "YES".to_string()));
+    }
+    for (key, value) in items {
+        if let Err(e) = ds.set_metadata_item(key, &value, "") {
+            return Err(Box::new(e));
+        }
+    }
+    Ok(())
+}
+
+/// the margin `ds` was padded with and whether it was clamped, `None` when it has no margin
+/// metadata
+pub(crate) fn recorded_margin(ds: &Dataset) -> Result<Option<(Margin, bool)>, String> {
+    let value = match ds.metadata_item(MARGIN_METADATA_KEY, "") {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let invalid = || format!("invalid {} metadata {:?}", MARGIN_METADATA_KEY, value);
+    let parse = |value: &str| value.trim().parse::<usize>().map_err(|_| invalid());
+    let margin = match value.split_once(',') {
+        Some((x, y)) => Margin::PerAxis {
+            x: parse(x)?,
+            y: parse(y)?,
+        },
+        None => Margin::Uniform(parse(&value)?),
+    };
+    let clamped = ds.metadata_item("VRT_BUFFER_CLAMPED", "").as_deref() == Some("YES");
+    Ok(Some((margin, clamped)))
+}
+
+/// checks a fixed `margin` against the `recorded` margin of `buffered`. fails when they differ
+/// or the margin was clamped, and warns when `buffered` has no margin metadata, e.g. because it
+/// was never padded
+pub(crate) fn check_fixed_margin(
+    recorded: Option<(Margin, bool)>,
+    buffered: &Path,
+    margin: Margin,
+) -> Result<(), Box<dyn Error>> {
+    match recorded {
+        Some((recorded, _)) if (recorded.x(), recorded.y()) != (margin.x(), margin.y()) => {
+            Err(format!(
+                "{:?} was padded by {:?}, not by {:?}",
+                buffered, recorded, margin
+            )
+            .into())
+        }
+        Some((_, true)) => Err(format!(
+            "the margin of {:?} stops at the edge of the reference, crop it to its original \
+             instead",
+            buffered
+        )
+        .into()),
+        Some(_) => Ok(()),
+        None => {
+            message(&format!(
+                "Warning: {:?} has no {} metadata, it may not be padded",
+                buffered, MARGIN_METADATA_KEY
+            ));
+            Ok(())
+        }
+    }
+}
+
+/// warns when the margin trimmed off `buffered` to crop it to its original differs from the
+/// `recorded` margin it was padded with
+pub(crate) fn check_trimmed_margin(
+    recorded: Option<(Margin, bool)>,
+    buffered: &Path,
+    trimmed: &TrimmedMargin,
+) {
+    let recorded = match recorded {
+        Some((recorded, false)) => recorded,
+        _ => return,
+    };
+    let expected = TrimmedMargin {
+        left: recorded.x(),
+        top: recorded.y(),
+        right: recorded.x(),
+        bottom: recorded.y(),
+    };
+    if *trimmed != expected {
+        message(&format!(
+            "Warning: {:?} was padded by {:?} but {:?} was trimmed off to crop it to its original",
+            buffered, recorded, trimmed
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+//! mask band and alpha band handling.
+//!
+//! alpha bands are ordinary bands, they are padded and cropped like the others and only need their
+//! color interpretation carried over. internal masks (`.msk` or the internal tiff mask) are stored
+//! outside of the bands, so they are read and written separately.
+use crate::geometry::PixelWindow;
+use gdal::{raster::Buffer, Dataset, Metadata};
+use std::error::Error;
+
+/// true when the dataset carries a mask of its own that is shared by all bands, as opposed to a
+/// mask derived from nodata, an alpha band or the implicit all valid mask
+pub fn has_dataset_mask(ds: &Dataset) -> Result<bool, Box<dyn Error>> {
+    let band = match ds.rasterband(1) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let flags = match band.mask_flags() {
+        Ok(flags) => flags,
+        Err(e) => return Err(Box::new(e)),
+    };
+    Ok(flags.is_per_dataset() && !flags.is_all_valid() && !flags.is_alpha() && !flags.is_nodata())
+}
+
+/// reads a window of the mask of `band_index`, 0 is masked and 255 is valid.
+/// the window is resampled to `buffer_size` with nearest neighbour when the sizes differ
+pub fn read_mask(
+    ds: &Dataset,
+    band_index: isize,
+    window: &PixelWindow,
+    buffer_size: (usize, usize),
+) -> Result<Buffer<u8>, Box<dyn Error>> {
+    let band = match ds.rasterband(band_index) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let mask_band = match band.open_mask_band() {
+        Ok(mask_band) => mask_band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    match mask_band.read_as::<u8>(window.offset(), window.size(), buffer_size, None) {
+        Ok(mask) => Ok(mask),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// creates a mask shared by all bands of `ds` and fills it with `mask`
+pub fn write_dataset_mask(ds: &Dataset, mask: &Buffer<u8>) -> Result<(), Box<dyn Error>> {
+    let mut band = match ds.rasterband(1) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    if let Err(e) = band.create_mask_band(true) {
+        return Err(Box::new(e));
+    };
+    let mut mask_band = match band.open_mask_band() {
+        Ok(mask_band) => mask_band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    if let Err(e) = mask_band.write((0, 0), mask.size, mask) {
+        return Err(Box::new(e));
+    };
+
+    Ok(())
+}
+
+/// copies `window` of the dataset mask of `ds` to a new dataset mask of `target` one strip of
+/// rows at a time
+/// target_offset: the pixel of `target` the upper left pixel of the window is copied to
+/// chunk_rows: the rows of a strip, the block height of the mask when not set
+pub fn copy_dataset_mask(
+    ds: &Dataset,
+    target: &Dataset,
+    window: &PixelWindow,
+    target_offset: (isize, isize),
+    chunk_rows: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let (band, mut target_band) = match (ds.rasterband(1), target.rasterband(1)) {
+        (Ok(band), Ok(target_band)) => (band, target_band),
+        (Err(e), _) | (_, Err(e)) => return Err(Box::new(e)),
+    };
+    if let Err(e) = target_band.create_mask_band(true) {
+        return Err(Box::new(e));
+    };
+    let (mask_band, mut target_mask) = match (band.open_mask_band(), target_band.open_mask_band()) {
+        (Ok(mask_band), Ok(target_mask)) => (mask_band, target_mask),
+        (Err(e), _) | (_, Err(e)) => return Err(Box::new(e)),
+    };
+    // the pixels of the target outside of the window are masked
+    if target.raster_size() != window.size() {
+        if let Err(e) = target_mask.fill(0.0, None) {
+            return Err(Box::new(e));
+        }
+    }
+    let strip_height = chunk_rows.unwrap_or(mask_band.block_size().1).max(1);
+    let (cols, rows) = window.size();
+    let mut row = 0;
+    while row < rows {
+        let strip_rows = strip_height.min(rows - row);
+        let offset = (window.x_offset, window.y_offset + row as isize);
+        let mask =
+            match mask_band.read_as::<u8>(offset, (cols, strip_rows), (cols, strip_rows), None) {
+                Ok(mask) => mask,
+                Err(e) => return Err(Box::new(e)),
+            };
+        let target_row = (target_offset.0, target_offset.1 + row as isize);
+        if let Err(e) = target_mask.write(target_row, (cols, strip_rows), &mask) {
+            return Err(Box::new(e));
+        };
+        row += strip_rows;
+    }
+    Ok(())
+}
+
+/// copies the color interpretation of `bands` of `source` to the bands of `target` in turn, so
+/// alpha bands stay alpha bands
+pub fn copy_color_interpretation(
+    source: &Dataset,
+    target: &Dataset,
+    bands: &[isize],
+) -> Result<(), Box<dyn Error>> {
+    for (target_index, &band_index) in (1..=target.raster_count()).zip(bands) {
+        let source_band = match source.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let mut target_band = match target.rasterband(target_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        if let Err(e) = target_band.set_color_interpretation(source_band.color_interpretation()) {
+            return Err(Box::new(e));
+        };
+    }
+
+    Ok(())
+}
+
+/// true when every pixel of every band equals the nodata value of the band, bands without a nodata
+/// value are never empty.
+/// statistics stored with the raster that report 0 valid pixels are trusted, otherwise the band is
+/// scanned one row of blocks at a time until the first valid pixel
+pub fn is_empty(ds: &Dataset) -> Result<bool, Box<dyn Error>> {
+    for band_index in 1..=ds.raster_count() {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let nodata = match band.no_data_value() {
+            Some(nodata) => nodata,
+            None => return Ok(false),
+        };
+        if band
+            .metadata_item("STATISTICS_VALID_PERCENT", "")
+            .as_deref()
+            == Some("0")
+        {
+            continue;
+        }
+
+        let (cols, rows) = band.size();
+        let block_rows = band.block_size().1.max(1);
+        for y_offset in (0..rows).step_by(block_rows) {
+            let size = (cols, block_rows.min(rows - y_offset));
+            let data = match band.read_as::<f64>((0, y_offset as isize), size, size, None) {
+                Ok(data) => data,
+                Err(e) => return Err(Box::new(e)),
+            };
+            let is_nodata = |value: &f64| *value == nodata || (nodata.is_nan() && value.is_nan());
+            if !data.data.iter().all(is_nodata) {
+                return Ok(false);
+            }
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,39 @@
+//! where the warnings and per tile messages of a batch go.
+//!
+//! they are printed to stderr unless the caller redirects them for the whole process, e.g. the
+//! cli with `--quiet` or `--log-file`. the per tile reports are not affected.
+use std::{
+    fs::File,
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
+
+/// the destination of the messages.
+#[derive(Debug)]
+pub enum MessageSink {
+    Stderr,
+    /// drop the messages
+    Quiet,
+    /// append the messages to a file
+    File(File),
+}
+
+static SINK: Mutex<MessageSink> = Mutex::new(MessageSink::Stderr);
+
+/// sends the messages of every following batch of the process to `sink`.
+pub fn set_message_sink(sink: MessageSink) {
+    *SINK.lock().unwrap_or_else(PoisonError::into_inner) = sink;
+}
+
+/// writes a line to the message sink
+pub(crate) fn message(text: &str) {
+    let mut sink = SINK.lock().unwrap_or_else(PoisonError::into_inner);
+    match &mut *sink {
+        MessageSink::Stderr => eprintln!("{}", text),
+        MessageSink::Quiet => {}
+        MessageSink::File(file) => {
+            // a message that can not be logged is not worth failing a tile over
+            let _ = writeln!(file, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! merging the cropped rasters of a run back into a single raster.
+//!
+//! the rasters must be on the pixel grid of the first one and may not cover the same pixels, the
+//! mosaic is only written when they form a seamless whole. gaps, e.g. between the tiles of an
+//! area of interest, are allowed with `MosaicOptions::allow_gaps`.
+use crate::{
+    geometry::GeoTransform, output::copy_output, path::open_dataset, raster_files,
+    source::write_vrt, MosaicOptions, OutputFormat, VrtSource,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// origins further than this from the grid of the first raster, in pixels, are misaligned
+const ALIGNMENT_TOLERANCE: f64 = 0.01;
+
+/// how the rasters of a mosaic fit together.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MosaicCheck {
+    pub rasters: usize,
+    /// the rasters whose resolution differs from the first raster or that are off its grid
+    pub misaligned: Vec<PathBuf>,
+    /// the pairs of rasters that cover the same pixels
+    pub overlaps: Vec<(PathBuf, PathBuf)>,
+    /// the pixels of the bounding box of the rasters that no raster covers
+    pub gap_pixels: u64,
+}
+
+impl MosaicCheck {
+    /// true when the rasters are on one grid and do not overlap
+    pub fn is_aligned(&self) -> bool {
+        self.misaligned.is_empty() && self.overlaps.is_empty()
+    }
+
+    /// true when the rasters also cover their bounding box without gaps
+    pub fn is_seamless(&self) -> bool {
+        self.is_aligned() && self.gap_pixels == 0
+    }
+}
+
+impl fmt::Display for MosaicCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} rasters", self.rasters)?;
+        for path in &self.misaligned {
+            writeln!(f, "  misaligned: {}", path.display())?;
+        }
+        for (first, second) in &self.overlaps {
+            writeln!(f, "  overlap: {} and {}", first.display(), second.display())?;
+        }
+        if self.gap_pixels > 0 {
+            writeln!(f, "  {} pixels are not covered", self.gap_pixels)?;
+        }
+        Ok(())
+    }
+}
+
+/// checks that `rasters` are on the pixel grid of the first of them, do not overlap and cover
+/// their bounding box
+pub fn check_mosaic(rasters: &[PathBuf]) -> Result<MosaicCheck, Box<dyn Error>> {
+    let mut check = MosaicCheck {
+        rasters: rasters.len(),
+        ..MosaicCheck::default()
+    };
+    let mut grid: Option<GeoTransform> = None;
+    // the pixel rectangles of the aligned rasters on the grid of the first one
+    let mut rects: Vec<(&PathBuf, [i64; 4])> = Vec::new();
+    for raster in rasters {
+        let ds = open_dataset(raster, &[])?;
+        let geo_transform = match ds.geo_transform() {
+            Ok(geo_transform) => geo_transform,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let grid = grid.get_or_insert(geo_transform);
+        // a resolution that differs by more than rounding drifts off the grid across a raster.
+        // the pixels may be taller than wide, so the tolerance follows the larger of the two
+        let pixel_size = grid[1].abs().max(grid[5].abs());
+        let same_resolution = [1, 2, 4, 5]
+            .iter()
+            .all(|&i| (geo_transform[i] - grid[i]).abs() <= 1e-9 * pixel_size);
+        let col = (geo_transform[0] - grid[0]) / grid[1];
+        let row = (geo_transform[3] - grid[3]) / grid[5];
+        if !same_resolution
+            || (col - col.round()).abs() > ALIGNMENT_TOLERANCE
+            || (row - row.round()).abs() > ALIGNMENT_TOLERANCE
+        {
+            check.misaligned.push(raster.clone());
+            continue;
+        }
+        let (cols, rows) = ds.raster_size();
+        let (col, row) = (col.round() as i64, row.round() as i64);
+        rects.push((raster, [col, row, col + cols as i64, row + rows as i64]));
+    }
+
+    // the edges of the rasters split the bounding box into cells that are covered by one raster,
+    // by several or by none
+    let edges = |axis: usize| {
+        let edges: BTreeSet<i64> = rects
+            .iter()
+            .flat_map(|(_, rect)| [rect[axis], rect[axis + 2]])
+            .collect();
+        edges.into_iter().collect::<Vec<i64>>()
+    };
+    let (xs, ys) = (edges(0), edges(1));
+    let index = |edges: &[i64], value: i64| edges.binary_search(&value).unwrap_or_default();
+    let columns = xs.len().saturating_sub(1);
+    let mut owners: Vec<Option<usize>> = vec![None; columns * ys.len().saturating_sub(1)];
+    let mut overlaps = BTreeSet::new();
+    for (i, (_, rect)) in rects.iter().enumerate() {
+        for y in index(&ys, rect[1])..index(&ys, rect[3]) {
+            for x in index(&xs, rect[0])..index(&xs, rect[2]) {
+                match owners[y * columns + x] {
+                    Some(owner) => {
+                        overlaps.insert((owner, i));
+                    }
+                    None => owners[y * columns + x] = Some(i),
+                }
+            }
+        }
+    }
+    check.overlaps = overlaps
+        .into_iter()
+        .map(|(first, second)| (rects[first].0.clone(), rects[second].0.clone()))
+        .collect();
+    for (cell, owner) in owners.iter().enumerate() {
+        if owner.is_none() {
+            let (x, y) = (cell % columns, cell / columns);
+            check.gap_pixels += ((xs[x + 1] - xs[x]) * (ys[y + 1] - ys[y])) as u64;
+        }
+    }
+    Ok(check)
+}
+
+/// merges the rasters of `input_dir`, e.g. the output directory of a crop, into the single
+/// raster `output`. nothing is written when `check_mosaic` finds misaligned or overlapping
+/// rasters, or gaps unless they are allowed.
+/// options: see `MosaicOptions`
+pub fn mosaic_tiles(
+    input_dir: &Path,
+    output: &Path,
+    options: &MosaicOptions,
+) -> Result<MosaicCheck, Box<dyn Error>> {
+    let rasters = raster_files(input_dir, &[])?;
+    if rasters.is_empty() {
+        return Err(format!("{:?} holds no rasters to merge", input_dir).into());
+    }
+    let check = check_mosaic(&rasters)?;
+    if !check.is_aligned() || (check.gap_pixels > 0 && !options.allow_gaps) {
+        return Err(format!(
+            "the rasters of {:?} do not form a seamless mosaic, nothing was written:\n{}",
+            input_dir, check
+        )
+        .into());
+    }
+
+    match options.output.format {
+        OutputFormat::Vrt => write_vrt(&rasters, output)?,
+        _ => {
+            let vrt = VrtSource::in_memory(&rasters)?;
+            let copied = vrt.with_dataset(|ds| copy_output(ds, output, &options.output));
+            vrt.remove()?;
+            copied?;
+        }
+    }
+    Ok(check)
+}
@@ -0,0 +1,1475 @@
+//! option structs that control how the padded and cropped outputs are written.
+use crate::{
+    geometry::{GeoExtent, Margin, Snapping},
+    gpkg, Calc, CancelToken, GdalConfig, ShardTemplate, StatisticsMode, TileIdPattern,
+    TileListener,
+};
+use gdal::{config::get_config_option, raster::ResampleAlg};
+use gdal_sys::GDALDataType;
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// classic tiffs can not hold more than 4 GiB, auto switches to bigtiff a bit before that to leave
+/// room for the tiff structure and overviews
+const BIGTIFF_THRESHOLD_BYTES: u64 = 3_800_000_000;
+
+/// settings for building internal overviews (pyramids) on an output file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overviews {
+    /// the decimation factors, e.g. `[2, 4, 8, 16]`
+    pub levels: Vec<i32>,
+    /// the gdal resampling method used to build the overviews, e.g. `average` or `nearest`
+    pub resampling: String,
+}
+
+/// how the BIGTIFF creation option of the geotiff outputs is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BigTiff {
+    /// use bigtiff when the uncompressed size of the output gets close to 4 GiB
+    #[default]
+    Auto,
+    /// always write bigtiffs
+    Yes,
+    /// never write bigtiffs, large outputs will fail
+    No,
+    /// let gdal decide, assuming the worst case compression ratio
+    IfSafer,
+}
+
+impl BigTiff {
+    /// the value of the BIGTIFF creation option for an output of the estimated uncompressed size,
+    /// `None` leaves the gdal default
+    pub fn creation_value(&self, estimated_bytes: u64) -> Option<&'static str> {
+        match self {
+            BigTiff::Auto if estimated_bytes >= BIGTIFF_THRESHOLD_BYTES => Some("YES"),
+            BigTiff::Auto => None,
+            BigTiff::Yes => Some("YES"),
+            BigTiff::No => Some("NO"),
+            BigTiff::IfSafer => Some("IF_SAFER"),
+        }
+    }
+}
+
+impl FromStr for BigTiff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BigTiff::Auto),
+            "yes" => Ok(BigTiff::Yes),
+            "no" => Ok(BigTiff::No),
+            "if_safer" | "if-safer" => Ok(BigTiff::IfSafer),
+            _ => Err(format!(
+                "unknown bigtiff mode {:?}, expected auto, yes, no or if_safer",
+                s
+            )),
+        }
+    }
+}
+
+/// the threads gdal uses to compress each tiff output, the NUM_THREADS creation option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompressionThreads {
+    /// the GDAL_NUM_THREADS configuration option when it is set, all cores otherwise. gdal
+    /// compresses on one pool of threads per process, so parallel jobs share the cores
+    #[default]
+    Auto,
+    /// all cores
+    AllCpus,
+    /// this many threads
+    Count(usize),
+}
+
+impl CompressionThreads {
+    /// the value of the NUM_THREADS creation option, `None` leaves it to GDAL_NUM_THREADS
+    pub fn creation_value(&self) -> Option<String> {
+        match self {
+            CompressionThreads::Auto => {
+                let configured = get_config_option("GDAL_NUM_THREADS", "").unwrap_or_default();
+                match configured.is_empty() {
+                    true => Some("ALL_CPUS".to_string()),
+                    false => None,
+                }
+            }
+            CompressionThreads::AllCpus => Some("ALL_CPUS".to_string()),
+            CompressionThreads::Count(threads) => Some(threads.to_string()),
+        }
+    }
+}
+
+impl From<usize> for CompressionThreads {
+    fn from(threads: usize) -> Self {
+        CompressionThreads::Count(threads)
+    }
+}
+
+impl FromStr for CompressionThreads {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(CompressionThreads::Auto),
+            "all_cpus" | "all-cpus" | "all" => Ok(CompressionThreads::AllCpus),
+            threads => match threads.parse::<usize>() {
+                Ok(threads) if threads > 0 => Ok(CompressionThreads::Count(threads)),
+                _ => Err(format!(
+                    "invalid compression threads {:?}, expected auto, all_cpus or a number",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// how the window read from the vrt is resampled when its resolution differs from the tile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Resampling {
+    #[default]
+    Nearest,
+    Bilinear,
+    Cubic,
+    /// the mean of the vrt pixels under a tile pixel, for tiles coarser than the vrt
+    Average,
+}
+
+impl Resampling {
+    /// the gdal resampling algorithm
+    pub fn algorithm(&self) -> ResampleAlg {
+        match self {
+            Resampling::Nearest => ResampleAlg::NearestNeighbour,
+            Resampling::Bilinear => ResampleAlg::Bilinear,
+            Resampling::Cubic => ResampleAlg::Cubic,
+            Resampling::Average => ResampleAlg::Average,
+        }
+    }
+}
+
+impl FromStr for Resampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Resampling::Nearest),
+            "bilinear" => Ok(Resampling::Bilinear),
+            "cubic" => Ok(Resampling::Cubic),
+            "average" => Ok(Resampling::Average),
+            _ => Err(format!(
+                "unknown resampling {:?}, expected nearest, bilinear, cubic or average",
+                s
+            )),
+        }
+    }
+}
+
+/// what the values of a band stand for, decides whether the band may be interpolated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BandKind {
+    /// categorical when the band is Byte and has a color table, continuous otherwise
+    #[default]
+    Auto,
+    /// measurements that may be interpolated with `PadOptions::resampling`
+    Continuous,
+    /// class values that are always resampled with nearest neighbor, so no new classes are made
+    /// up at the edges between classes
+    Categorical,
+}
+
+impl FromStr for BandKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(BandKind::Auto),
+            "continuous" => Ok(BandKind::Continuous),
+            "categorical" => Ok(BandKind::Categorical),
+            _ => Err(format!(
+                "unknown band kind {:?}, expected auto, continuous or categorical",
+                s
+            )),
+        }
+    }
+}
+
+/// a data type the outputs are converted to, see `OutputOptions::output_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputType {
+    Byte,
+    UInt16,
+    Int16,
+    UInt32,
+    Int32,
+    Float32,
+    Float64,
+}
+
+impl OutputType {
+    /// the gdal data type
+    pub fn gdal_type(&self) -> GDALDataType::Type {
+        match self {
+            OutputType::Byte => GDALDataType::GDT_Byte,
+            OutputType::UInt16 => GDALDataType::GDT_UInt16,
+            OutputType::Int16 => GDALDataType::GDT_Int16,
+            OutputType::UInt32 => GDALDataType::GDT_UInt32,
+            OutputType::Int32 => GDALDataType::GDT_Int32,
+            OutputType::Float32 => GDALDataType::GDT_Float32,
+            OutputType::Float64 => GDALDataType::GDT_Float64,
+        }
+    }
+
+    /// the smallest and the largest value of the type
+    pub fn range(&self) -> (f64, f64) {
+        match self {
+            OutputType::Byte => (0.0, u8::MAX as f64),
+            OutputType::UInt16 => (0.0, u16::MAX as f64),
+            OutputType::Int16 => (i16::MIN as f64, i16::MAX as f64),
+            OutputType::UInt32 => (0.0, u32::MAX as f64),
+            OutputType::Int32 => (i32::MIN as f64, i32::MAX as f64),
+            OutputType::Float32 => (f32::MIN as f64, f32::MAX as f64),
+            OutputType::Float64 => (f64::MIN, f64::MAX),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, OutputType::Float32 | OutputType::Float64)
+    }
+}
+
+impl FromStr for OutputType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "byte" => Ok(OutputType::Byte),
+            "uint16" => Ok(OutputType::UInt16),
+            "int16" => Ok(OutputType::Int16),
+            "uint32" => Ok(OutputType::UInt32),
+            "int32" => Ok(OutputType::Int32),
+            "float32" => Ok(OutputType::Float32),
+            "float64" => Ok(OutputType::Float64),
+            _ => Err(format!(
+                "unknown output type {:?}, expected Byte, UInt16, Int16, UInt32, Int32, Float32 or \
+                 Float64",
+                s
+            )),
+        }
+    }
+}
+
+/// how values with a fraction are rounded when the output type is an integer type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// to the nearest integer, halves away from zero
+    #[default]
+    Nearest,
+    Floor,
+    /// towards zero
+    Truncate,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(Rounding::Nearest),
+            "floor" => Ok(Rounding::Floor),
+            "truncate" => Ok(Rounding::Truncate),
+            _ => Err(format!(
+                "unknown rounding {:?}, expected nearest, floor or truncate",
+                s
+            )),
+        }
+    }
+}
+
+/// what happens to values the output type can not hold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// the smallest or the largest value of the type
+    #[default]
+    Clamp,
+    /// the nodata value of the output
+    Nodata,
+}
+
+impl FromStr for OutOfRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clamp" => Ok(OutOfRange::Clamp),
+            "nodata" => Ok(OutOfRange::Nodata),
+            _ => Err(format!(
+                "unknown out of range handling {:?}, expected clamp or nodata",
+                s
+            )),
+        }
+    }
+}
+
+/// the value the margin is filled with where it extends beyond the edge of the vrt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeFill {
+    /// the nodata value of each band of the tile, 0 for bands without one
+    Nodata,
+    /// a value per band, bands past the end of the list use the last value
+    Values(Vec<f64>),
+}
+
+impl EdgeFill {
+    /// the fill value of `band_index` whose nodata value is `nodata`
+    pub fn value(&self, band_index: isize, nodata: Option<f64>) -> f64 {
+        match self {
+            EdgeFill::Nodata => nodata.unwrap_or(0.0),
+            EdgeFill::Values(values) => usize::try_from(band_index - 1)
+                .ok()
+                .and_then(|index| values.get(index))
+                .or(values.last())
+                .copied()
+                .unwrap_or(0.0),
+        }
+    }
+}
+
+/// parses `nodata` or a comma separated list of values, e.g. `0,0,255`
+impl FromStr for EdgeFill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "nodata" {
+            return Ok(EdgeFill::Nodata);
+        }
+        let mut values = Vec::new();
+        for value in s.split(',') {
+            match value.trim().parse::<f64>() {
+                Ok(value) => values.push(value),
+                Err(_) => {
+                    return Err(format!(
+                        "invalid fill value {:?}, expected nodata or a list like 0,0,255",
+                        value
+                    ))
+                }
+            }
+        }
+        Ok(EdgeFill::Values(values))
+    }
+}
+
+/// what happens to a tile whose margin extends beyond the edge of the vrt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MarginPolicy {
+    /// the output stops at the edge of the vrt and is smaller than the full margin, the tile is
+    /// reported as clamped
+    #[default]
+    Clamp,
+    /// the tile fails with `MarginBeyondReference`
+    Error,
+    /// the output has the full margin, the part beyond the edge of the vrt is filled
+    Fill(EdgeFill),
+}
+
+/// parses `clamp`, `error`, `fill` with the nodata values or `fill:VALUES`, e.g. `fill:0,0,255`
+impl FromStr for MarginPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" => Ok(MarginPolicy::Clamp),
+            "error" => Ok(MarginPolicy::Error),
+            "fill" => Ok(MarginPolicy::Fill(EdgeFill::Nodata)),
+            _ => match s.strip_prefix("fill:") {
+                Some(values) => Ok(MarginPolicy::Fill(values.parse()?)),
+                None => Err(format!(
+                    "unknown margin policy {:?}, expected clamp, error, fill or fill:VALUES",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// the value a band is filled with where the output has no pixels of the source, the margin
+/// beyond the edge of the vrt or the shortfall of a crop.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FillStrategy {
+    /// the nodata value of the band, 0 for bands without one
+    #[default]
+    Nodata,
+    /// a fixed value, e.g. 255 for a qa band
+    Value(f64),
+}
+
+/// parses `nodata` or a value
+impl FromStr for FillStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "nodata" {
+            return Ok(FillStrategy::Nodata);
+        }
+        match s.parse::<f64>() {
+            Ok(value) => Ok(FillStrategy::Value(value)),
+            Err(_) => Err(format!("invalid fill {:?}, expected nodata or a value", s)),
+        }
+    }
+}
+
+/// the nodata value and fill of a band of the outputs, for products that mix bands with
+/// different conventions, e.g. reflectance bands filled with 0 and a qa band filled with 255.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BandPolicy {
+    /// the nodata value of the output band, the nodata pixels of the source are replaced with it.
+    /// the band keeps the nodata value of the source when not set
+    pub nodata: Option<f64>,
+    pub fill: FillStrategy,
+}
+
+impl BandPolicy {
+    /// the nodata value of the output band whose source has `source_nodata`
+    pub fn nodata_value(&self, source_nodata: Option<f64>) -> Option<f64> {
+        self.nodata.or(source_nodata)
+    }
+
+    /// the fill value of the band whose source has `source_nodata`
+    pub fn fill_value(&self, source_nodata: Option<f64>) -> f64 {
+        match self.fill {
+            FillStrategy::Nodata => self.nodata_value(source_nodata).unwrap_or(0.0),
+            FillStrategy::Value(value) => value,
+        }
+    }
+}
+
+/// parses comma separated `nodata=VALUE` and `fill=nodata|VALUE`, e.g. `nodata=255,fill=255`
+impl FromStr for BandPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = BandPolicy::default();
+        for setting in s.split(',') {
+            match setting.trim().split_once('=') {
+                Some(("nodata", value)) => match value.parse::<f64>() {
+                    Ok(nodata) => policy.nodata = Some(nodata),
+                    Err(_) => return Err(format!("invalid nodata value {:?}", value)),
+                },
+                Some(("fill", value)) => policy.fill = value.parse()?,
+                _ => {
+                    return Err(format!(
+                        "invalid band policy {:?}, expected nodata=VALUE or fill=nodata|VALUE",
+                        setting
+                    ))
+                }
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// the order the tiles are padded in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TileOrder {
+    /// by file name
+    #[default]
+    Name,
+    /// along a hilbert curve through the tile centers, neighboring tiles are padded close
+    /// together in time so the vrt blocks they share are still cached
+    Hilbert,
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(TileOrder::Name),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(format!("unknown order {:?}, expected name or hilbert", s)),
+        }
+    }
+}
+
+/// what a batch does with inputs of the same name in different geopackages or directories, whose
+/// outputs would collide in the output directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateNames {
+    /// the first input keeps the output, the others fail
+    #[default]
+    Error,
+    /// prefix the outputs with the name of the geopackage or directory of their input, e.g.
+    /// `a_tile.tif` and `b_tile.tif`
+    PrefixParent,
+    /// write the outputs to a subdirectory named after the geopackage or directory of their
+    /// input, e.g. `a/tile.tif` and `b/tile.tif`
+    Mirror,
+}
+
+impl DuplicateNames {
+    /// the output of `input` when `output` is also the output of other inputs
+    pub(crate) fn rename(&self, input: &Path, output: &Path) -> PathBuf {
+        let parent = match gpkg::split_table_path(input) {
+            Some((file, _)) => file.file_stem(),
+            None => input.parent().and_then(Path::file_name),
+        };
+        let parent = parent.unwrap_or_default().to_string_lossy();
+        let file_name = output.file_name().unwrap_or_default();
+        let dir = output.parent().unwrap_or(Path::new(""));
+        match self {
+            DuplicateNames::Error => output.to_path_buf(),
+            DuplicateNames::PrefixParent => {
+                dir.join(format!("{}_{}", parent, file_name.to_string_lossy()))
+            }
+            DuplicateNames::Mirror => dir.join(parent.as_ref()).join(file_name),
+        }
+    }
+}
+
+impl FromStr for DuplicateNames {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(DuplicateNames::Error),
+            "prefix-parent" => Ok(DuplicateNames::PrefixParent),
+            "mirror" => Ok(DuplicateNames::Mirror),
+            _ => Err(format!(
+                "unknown duplicate name handling {:?}, expected error, prefix-parent or mirror",
+                s
+            )),
+        }
+    }
+}
+
+/// what a batch does when the file system of the output directory may not have room for its
+/// outputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DiskSpaceCheck {
+    /// fail before the first tile
+    #[default]
+    Error,
+    /// warn and run anyway
+    Warn,
+    /// do not check
+    Off,
+}
+
+impl FromStr for DiskSpaceCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(DiskSpaceCheck::Error),
+            "warn" => Ok(DiskSpaceCheck::Warn),
+            "off" => Ok(DiskSpaceCheck::Off),
+            _ => Err(format!(
+                "unknown disk space check {:?}, expected error, warn or off",
+                s
+            )),
+        }
+    }
+}
+
+/// what a batch does when tiles fail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// log the failed tiles, process the rest and report the failures
+    #[default]
+    ContinueAndReport,
+    /// stop at the first failed tile, e.g. for validation runs in ci
+    FailFast,
+    /// stop once this many tiles failed
+    FailAfterNFailures(usize),
+}
+
+impl ErrorPolicy {
+    /// the number of failed tiles that stops the batch, `None` when it never stops
+    pub(crate) fn max_failures(&self) -> Option<usize> {
+        match self {
+            ErrorPolicy::ContinueAndReport => None,
+            ErrorPolicy::FailFast => Some(1),
+            ErrorPolicy::FailAfterNFailures(failures) => Some((*failures).max(1)),
+        }
+    }
+}
+
+/// the raster format of the outputs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// geotiff, written directly
+    #[default]
+    GTiff,
+    /// cloud optimized geotiff, written to memory first and then copied
+    Cog,
+    /// a vrt that points to a geotiff with the same name next to it
+    Vrt,
+    /// erdas imagine (.img)
+    Hfa,
+    /// a geopackage per output holding a raster table named after the output
+    Gpkg,
+    /// a vrt that crops the reference of a pad run to the padded window, no pixels are copied
+    VirtualVrt,
+    /// a zarr v2 store per output, a directory holding the output as a single chunk per band and
+    /// consolidated metadata
+    Zarr,
+}
+
+impl OutputFormat {
+    /// the short name of the gdal driver.
+    pub fn driver_name(&self) -> &'static str {
+        match self {
+            OutputFormat::GTiff => "GTiff",
+            OutputFormat::Cog => "COG",
+            OutputFormat::Vrt | OutputFormat::VirtualVrt => "VRT",
+            OutputFormat::Hfa => "HFA",
+            OutputFormat::Gpkg => "GPKG",
+            OutputFormat::Zarr => "Zarr",
+        }
+    }
+
+    /// the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::GTiff | OutputFormat::Cog => "tif",
+            OutputFormat::Vrt | OutputFormat::VirtualVrt => "vrt",
+            OutputFormat::Hfa => "img",
+            OutputFormat::Gpkg => "gpkg",
+            OutputFormat::Zarr => "zarr",
+        }
+    }
+
+    /// the output path for an input path, tiff based formats keep the name of a tiff input.
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        let is_tiff = path
+            .extension()
+            .is_some_and(|extension| extension == "tif" || extension == "tiff");
+        match self {
+            OutputFormat::GTiff | OutputFormat::Cog if is_tiff => path.to_path_buf(),
+            _ => path.with_extension(self.extension()),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "gtiff" | "geotiff" | "tif" => Ok(OutputFormat::GTiff),
+            "cog" => Ok(OutputFormat::Cog),
+            "vrt" => Ok(OutputFormat::Vrt),
+            "hfa" | "img" => Ok(OutputFormat::Hfa),
+            "gpkg" | "geopackage" => Ok(OutputFormat::Gpkg),
+            "virtual" | "virtual-vrt" => Ok(OutputFormat::VirtualVrt),
+            "zarr" => Ok(OutputFormat::Zarr),
+            _ => Err(format!(
+                "unknown output format {:?}, expected gtiff, cog, vrt, hfa, gpkg, virtual or zarr",
+                s
+            )),
+        }
+    }
+}
+
+/// options shared by every output written by the crate.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputOptions {
+    /// build internal overviews on each output when set
+    pub overviews: Option<Overviews>,
+    /// copy the `.prj` and `.aux.xml` sidecars of the source raster and regenerate its world file
+    pub sidecars: bool,
+    /// compute the band statistics of each output and store them as metadata when set
+    pub statistics: Option<StatisticsMode>,
+    /// how the BIGTIFF creation option is chosen
+    pub bigtiff: BigTiff,
+    /// the raster format of the outputs
+    pub format: OutputFormat,
+    /// skip the tiles whose output is up to date according to the state file of the output
+    /// directory, and record every tile in it as soon as it is written, so an interrupted run
+    /// resumes where it stopped
+    pub resume: bool,
+    /// write each output to a subdirectory of the output directory built from this template
+    pub shard: Option<ShardTemplate>,
+    /// name each output by the id of its tile instead of the name of the input
+    pub tile_id: Option<TileIdPattern>,
+    /// what happens to inputs whose outputs would have the same name
+    pub duplicate_names: DuplicateNames,
+    /// the threads gdal uses to compress each tiff output
+    pub compression_threads: CompressionThreads,
+    /// write a STAC item next to each output and a catalog of them to the output directory
+    pub stac: bool,
+    /// write a `<name>.provenance.json` of the inputs, versions and timings next to each output
+    pub provenance: bool,
+    /// write a json line per failed tile to `errors.jsonl` in the output directory
+    pub error_log: bool,
+    /// write the sha256 of every output to `manifest.sha256` in the output directory
+    pub manifest: bool,
+    /// append the outputs to this tar archive and remove them from the output directory
+    pub archive: Option<PathBuf>,
+    /// allow an output directory that is the input directory or inside of it
+    pub allow_overlapping_dirs: bool,
+    /// copy the other files of the input directory, e.g. xml metadata and reports, to the output
+    /// directory
+    pub copy_non_raster_files: bool,
+    /// convert the pixels to this type instead of keeping f32, f64 or the type of a palette
+    pub output_type: Option<OutputType>,
+    /// how values are rounded to an integer output type
+    pub rounding: Rounding,
+    /// what happens to values the output type can not hold
+    pub out_of_range: OutOfRange,
+    /// the nodata value of converted outputs whose source nodata value the output type can not
+    /// hold, the largest value of unsigned and the smallest of signed types when not set
+    pub output_nodata: Option<f64>,
+    /// the bands of the inputs written to the outputs, in this order, all bands when empty
+    pub bands: Vec<isize>,
+    /// the nodata value and fill of single bands of the inputs, the other bands keep the nodata
+    /// value of the source and are filled like `PadOptions::margin_policy`
+    pub band_policies: Vec<(isize, BandPolicy)>,
+}
+
+impl OutputOptions {
+    /// build internal overviews with the given levels and resampling method on each output.
+    /// an empty list of levels disables overview generation.
+    pub fn overviews(mut self, levels: Vec<i32>, resampling: &str) -> Self {
+        self.overviews = if levels.is_empty() {
+            None
+        } else {
+            Some(Overviews {
+                levels,
+                resampling: resampling.to_string(),
+            })
+        };
+        self
+    }
+
+    /// copy the sidecar files of each source raster next to its output.
+    pub fn sidecars(mut self, sidecars: bool) -> Self {
+        self.sidecars = sidecars;
+        self
+    }
+
+    /// compute band statistics on each output and embed them as metadata.
+    pub fn statistics(mut self, statistics: Option<StatisticsMode>) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// override the automatic bigtiff detection.
+    pub fn bigtiff(mut self, bigtiff: BigTiff) -> Self {
+        self.bigtiff = bigtiff;
+        self
+    }
+
+    /// set the raster format of the outputs.
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// skip tiles whose inputs and parameters did not change since the previous run.
+    /// a tile is not redone when only the pixels of its neighbors changed.
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// compress each tiff output with `threads` threads, a number or `CompressionThreads::AllCpus`,
+    /// independent of the number of jobs.
+    pub fn compression_threads(mut self, threads: impl Into<CompressionThreads>) -> Self {
+        self.compression_threads = threads.into();
+        self
+    }
+
+    /// write each output to the subdirectory of the output directory given by `template`.
+    pub fn shard(mut self, template: ShardTemplate) -> Self {
+        self.shard = Some(template);
+        self
+    }
+
+    /// name each output by the id `pattern` extracts from the name of its input, keeping the
+    /// extension. crop pairs the buffered rasters with the originals by the same id.
+    pub fn tile_id(mut self, pattern: TileIdPattern) -> Self {
+        self.tile_id = Some(pattern);
+        self
+    }
+
+    /// set what happens to inputs of the same name, e.g. the tables of the same name of two
+    /// geopackages. only the outputs that collide are renamed, outputs that still collide fail.
+    pub fn duplicate_names(mut self, duplicate_names: DuplicateNames) -> Self {
+        self.duplicate_names = duplicate_names;
+        self
+    }
+
+    /// write a STAC item `<stem>.json` next to each output and a `catalog.json` linking them to
+    /// the output directory.
+    pub fn stac(mut self, stac: bool) -> Self {
+        self.stac = stac;
+        self
+    }
+
+    /// write the provenance of each output next to it: the inputs and their sha256, the
+    /// reference, the margin, the gdal and crate versions, the command line and the timings.
+    pub fn provenance(mut self, provenance: bool) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// write the input, output, error and extent of every failed tile as a json line to
+    /// `errors.jsonl` in the output directory, and remove the file when no tile failed.
+    pub fn error_log(mut self, error_log: bool) -> Self {
+        self.error_log = error_log;
+        self
+    }
+
+    /// write the sha256 of every output to `manifest.sha256` in the output directory, see
+    /// `verify_manifest`.
+    pub fn manifest(mut self, manifest: bool) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    /// append every output with its sidecars to the tar archive at `path` as soon as it is
+    /// written and remove it from the output directory, e.g. to deliver thousands of tiles as
+    /// one file.
+    pub fn archive(mut self, path: PathBuf) -> Self {
+        self.archive = Some(path);
+        self
+    }
+
+    /// allow writing into the input directory or a directory inside of it, where the outputs
+    /// replace inputs with the same name. `crop_in_place` always allows it.
+    pub fn allow_overlapping_dirs(mut self, allow: bool) -> Self {
+        self.allow_overlapping_dirs = allow;
+        self
+    }
+
+    /// copy the files of the input directory that are not rasters or their sidecars to the output
+    /// directory, so it is a complete replacement of the input directory.
+    pub fn copy_non_raster_files(mut self, copy: bool) -> Self {
+        self.copy_non_raster_files = copy;
+        self
+    }
+
+    /// convert the pixels of the outputs to `output_type`, with nodata pixels mapped to the
+    /// nodata value of the output. see `rounding`, `out_of_range` and `output_nodata`.
+    pub fn output_type(mut self, output_type: OutputType) -> Self {
+        self.output_type = Some(output_type);
+        self
+    }
+
+    /// set how values are rounded to an integer output type.
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// set what happens to values the output type can not hold.
+    pub fn out_of_range(mut self, out_of_range: OutOfRange) -> Self {
+        self.out_of_range = out_of_range;
+        self
+    }
+
+    /// set the nodata value of converted outputs whose source nodata value does not fit the
+    /// output type.
+    pub fn output_nodata(mut self, nodata: f64) -> Self {
+        self.output_nodata = Some(nodata);
+        self
+    }
+
+    /// write only `bands` of each input to its output, numbered from 1 and in this order, e.g.
+    /// `vec![1, 3]` for the first and third band. an empty list writes every band.
+    pub fn bands(mut self, bands: Vec<isize>) -> Self {
+        self.bands = bands;
+        self
+    }
+
+    /// set the nodata value and fill of band `band_index` of the inputs.
+    pub fn band_policy(mut self, band_index: isize, policy: BandPolicy) -> Self {
+        self.band_policies.retain(|(band, _)| *band != band_index);
+        self.band_policies.push((band_index, policy));
+        self
+    }
+
+    /// the policy of band `band_index` of the inputs, if it has one
+    pub(crate) fn band_policy_of(&self, band_index: isize) -> Option<&BandPolicy> {
+        self.band_policies
+            .iter()
+            .find(|(band, _)| *band == band_index)
+            .map(|(_, policy)| policy)
+    }
+
+    /// the value the pixels of band `band_index` beyond the reference are filled with, where
+    /// `nodata` is the nodata value of the band after its policy. the fill of a band policy wins
+    /// over `edge_fill`
+    pub(crate) fn fill_value(
+        &self,
+        band_index: isize,
+        nodata: Option<f64>,
+        edge_fill: &EdgeFill,
+    ) -> f64 {
+        match self.band_policy_of(band_index).map(|policy| policy.fill) {
+            Some(FillStrategy::Value(value)) => value,
+            Some(FillStrategy::Nodata) => nodata.unwrap_or(0.0),
+            None => edge_fill.value(band_index, nodata),
+        }
+    }
+
+    /// the path in `output_dir` the output of `input` is written to
+    pub(crate) fn output_path(
+        &self,
+        output_dir: &Path,
+        input: &Path,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let mut file_name = match gpkg::tile_file_name(input) {
+            Some(file_name) => file_name,
+            None => return Err(format!("{:?} has no file name", input).into()),
+        };
+        if let Some(pattern) = &self.tile_id {
+            let mut id = pattern.tile_id(input);
+            if let Some(extension) = Path::new(&file_name).extension() {
+                id = format!("{}.{}", id, extension.to_string_lossy());
+            }
+            file_name = id.into();
+        }
+        let directory = match &self.shard {
+            Some(template) => output_dir.join(template.directory(input)?),
+            None => output_dir.to_path_buf(),
+        };
+        Ok(self.format.output_path(&directory.join(file_name)))
+    }
+}
+
+/// options for `mosaic_tiles`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MosaicOptions {
+    /// the format of the mosaic, of the other output options only `bigtiff` and
+    /// `compression_threads` apply
+    pub output: OutputOptions,
+    /// write the mosaic when the rasters leave gaps in their bounding box
+    pub allow_gaps: bool,
+}
+
+impl MosaicOptions {
+    /// set the format the mosaic is written in, e.g. `OutputFormat::Cog`.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// write the mosaic when the rasters leave gaps, which become nodata.
+    pub fn allow_gaps(mut self, allow_gaps: bool) -> Self {
+        self.allow_gaps = allow_gaps;
+        self
+    }
+}
+
+/// options for `vrt_buffer_with_options`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PadOptions {
+    /// how the padded files are written
+    pub output: OutputOptions,
+    /// the number of tiles padded at the same time
+    pub jobs: usize,
+    /// bands padded from their own vrt instead of the main one, as (1 based band index, vrt path).
+    /// the first band of each of these vrts is read.
+    pub band_vrts: Vec<(isize, PathBuf)>,
+    /// the bands of the main vrt the bands of the tiles are padded from, as (1 based band index
+    /// of the tile, 1 based band index of the vrt). bands that are not listed read the band of
+    /// the vrt with the same index
+    pub band_map: Vec<(isize, isize)>,
+    /// fill the nodata pixels of the margins from this mosaic, e.g. a coarser mosaic that covers
+    /// the holes of the reference
+    pub fallback_vrt: Option<PathBuf>,
+    /// the margins of single tiles by file name, with or without the extension, that replace
+    /// the margin of the batch
+    pub margin_overrides: BTreeMap<String, Margin>,
+    /// copy tiles that do not overlap the vrt to the output unpadded instead of only skipping them
+    pub pass_through_outside: bool,
+    /// how the tile origin is snapped to the pixel grid of the vrt
+    pub snapping: Snapping,
+    /// gdal configuration and open options set before the tiles are padded
+    pub gdal: GdalConfig,
+    /// stops the batch early when cancelled
+    pub cancel: CancelToken,
+    /// copy the pixels of the tile itself from the tile and only read the margins from the vrt,
+    /// tiles that are not on the grid of the vrt are always read from the vrt
+    pub center_from_tile: bool,
+    /// skip tiles whose pixels are all nodata
+    pub skip_empty: bool,
+    /// write a vrt mosaic of the padded files to this path after the run
+    pub mosaic_vrt: Option<PathBuf>,
+    /// how the vrt is resampled to the grid of a tile with a different resolution
+    pub resampling: Resampling,
+    /// the kind of the bands as (1 based band index, kind), bands that are not listed are
+    /// `BandKind::Auto`
+    pub band_kinds: Vec<(isize, BandKind)>,
+    /// what happens to tiles whose margin extends beyond the edge of the vrt
+    pub margin_policy: MarginPolicy,
+    /// the order the tiles are padded in
+    pub order: TileOrder,
+    /// the number of tiles that read from the vrts at the same time, any number when not set.
+    /// lower than `jobs` on storage that slows down with concurrent reads
+    pub io_threads: Option<usize>,
+    /// an expression applied to every pixel of the padded tiles that is not nodata, the outputs
+    /// are f32, or f64 when an input band is f64
+    pub calc: Option<Calc>,
+    /// only pad the tiles that intersect this extent, in the crs of the tiles
+    pub aoi: Option<GeoExtent>,
+    /// only pad the rasters of the input directory in this list
+    pub file_list: Option<Vec<PathBuf>>,
+    /// keep the index of the tile grids in the input directory between runs, see `TileIndex`
+    pub index: bool,
+    /// called with the report of every tile as soon as it is done
+    pub listener: TileListener,
+    /// what happens when the output directory may not have room for the padded tiles
+    pub disk_check: DiskSpaceCheck,
+    /// only process this many tiles, tiles whose output is up to date are not counted
+    pub max_tiles: Option<usize>,
+    /// do not start tiles once the batch has run this long
+    pub max_duration: Option<Duration>,
+    /// fail a tile with `TimedOut` once it has run this long
+    pub tile_timeout: Option<Duration>,
+    /// whether the batch goes on after tiles failed
+    pub error_policy: ErrorPolicy,
+    /// write vrts with the size and georeferencing of the padded tiles but without pixels
+    pub metadata_only: bool,
+}
+
+impl Default for PadOptions {
+    fn default() -> Self {
+        PadOptions {
+            output: OutputOptions::default(),
+            jobs: 1,
+            band_vrts: Vec::new(),
+            band_map: Vec::new(),
+            fallback_vrt: None,
+            margin_overrides: BTreeMap::new(),
+            pass_through_outside: false,
+            snapping: Snapping::default(),
+            gdal: GdalConfig::default(),
+            cancel: CancelToken::default(),
+            center_from_tile: true,
+            skip_empty: false,
+            mosaic_vrt: None,
+            resampling: Resampling::default(),
+            band_kinds: Vec::new(),
+            margin_policy: MarginPolicy::default(),
+            order: TileOrder::default(),
+            io_threads: None,
+            calc: None,
+            aoi: None,
+            file_list: None,
+            index: false,
+            listener: TileListener::default(),
+            disk_check: DiskSpaceCheck::default(),
+            max_tiles: None,
+            max_duration: None,
+            tile_timeout: None,
+            error_policy: ErrorPolicy::default(),
+            metadata_only: false,
+        }
+    }
+}
+
+impl PadOptions {
+    /// set the number of tiles padded at the same time.
+    /// each worker thread opens its own handle to the vrt.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// pad band `band_index` of the tiles from the first band of `vrt_file`
+    /// instead of the main vrt.
+    pub fn band_vrt(mut self, band_index: isize, vrt_file: PathBuf) -> Self {
+        self.band_vrts.push((band_index, vrt_file));
+        self
+    }
+
+    /// fill the margin pixels that are nodata in the reference, e.g. where neighbor tiles are
+    /// missing, from `vrt_file`, such as a national 10 m mosaic under a 1 m reference. band `n`
+    /// of a tile is filled from band `n` of the fallback, resampled like the reference.
+    pub fn fallback_vrt(mut self, vrt_file: PathBuf) -> Self {
+        self.fallback_vrt = Some(vrt_file);
+        self
+    }
+
+    /// pad the tiles named in `overrides` by their own margin instead of the margin of the batch,
+    /// e.g. coastal tiles that need a wider margin, see `read_margin_overrides`. the margin used
+    /// is recorded in the metadata of the output and in its `TileReport`
+    pub fn margin_overrides(mut self, overrides: BTreeMap<String, Margin>) -> Self {
+        self.margin_overrides = overrides;
+        self
+    }
+
+    /// pad band `band_index` of the tiles from band `vrt_band` of the main vrt, e.g. when the vrt
+    /// has more bands than the tiles.
+    pub fn band_map(mut self, band_index: isize, vrt_band: isize) -> Self {
+        self.band_map.retain(|(band, _)| *band != band_index);
+        self.band_map.push((band_index, vrt_band));
+        self
+    }
+
+    /// copy tiles that are completely outside of the vrt to the output unpadded.
+    /// they are still reported as skipped.
+    pub fn pass_through_outside(mut self, pass_through_outside: bool) -> Self {
+        self.pass_through_outside = pass_through_outside;
+        self
+    }
+
+    /// set how the tile origin is snapped to the pixel grid of the vrt.
+    pub fn snapping(mut self, snapping: Snapping) -> Self {
+        self.snapping = snapping;
+        self
+    }
+
+    /// set the options used to write the padded files.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// set the gdal configuration and open options.
+    pub fn gdal(mut self, gdal: GdalConfig) -> Self {
+        self.gdal = gdal;
+        self
+    }
+
+    /// stop the batch when `cancel` is cancelled, e.g. from a ctrl-c handler.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// copy the center of the padded tiles from the tiles, the default, or read the whole window
+    /// from the vrt when the pixels of the vrt differ from the tiles, e.g. with overlapping tiles.
+    pub fn center_from_tile(mut self, center_from_tile: bool) -> Self {
+        self.center_from_tile = center_from_tile;
+        self
+    }
+
+    /// write every padded tile as a vrt with the size, georeferencing and bands of the padded
+    /// tile but no pixels, for checking the footprints of a run in a gis in seconds before the
+    /// real run. needs `OutputFormat::VirtualVrt`, the stubs do not read the reference.
+    pub fn metadata_only(mut self, metadata_only: bool) -> Self {
+        self.metadata_only = metadata_only;
+        self
+    }
+
+    /// skip tiles whose bands all have a nodata value and contain nothing else.
+    /// no output is written for them and they are reported as skipped.
+    pub fn skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// write a vrt mosaic of the padded files to `path` once every tile is done,
+    /// its path is returned in `BatchReport::mosaic`.
+    pub fn mosaic_vrt(mut self, path: PathBuf) -> Self {
+        self.mosaic_vrt = Some(path);
+        self
+    }
+
+    /// set how the vrt is resampled for tiles whose resolution differs from the vrt.
+    pub fn resampling(mut self, resampling: Resampling) -> Self {
+        self.resampling = resampling;
+        self
+    }
+
+    /// set the kind of band `band_index`, categorical bands are always resampled with nearest
+    /// neighbor.
+    pub fn band_kind(mut self, band_index: isize, kind: BandKind) -> Self {
+        self.band_kinds.retain(|(band, _)| *band != band_index);
+        self.band_kinds.push((band_index, kind));
+        self
+    }
+
+    /// pad every tile to the full margin, filling the part beyond the edge of the vrt with a
+    /// value per band. the same as `MarginPolicy::Fill`.
+    pub fn edge_fill(mut self, edge_fill: EdgeFill) -> Self {
+        self.margin_policy = MarginPolicy::Fill(edge_fill);
+        self
+    }
+
+    /// set what happens to tiles whose margin extends beyond the edge of the vrt.
+    pub fn margin_policy(mut self, margin_policy: MarginPolicy) -> Self {
+        self.margin_policy = margin_policy;
+        self
+    }
+
+    /// limit the number of tiles that read from the vrts at the same time.
+    pub fn io_threads(mut self, io_threads: usize) -> Self {
+        self.io_threads = Some(io_threads);
+        self
+    }
+
+    /// set the order the tiles are padded in.
+    pub fn order(mut self, order: TileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// apply `calc` to every pixel of the padded tiles that is not nodata, e.g. to convert units
+    /// in the same pass.
+    pub fn calc(mut self, calc: Calc) -> Self {
+        self.calc = Some(calc);
+        self
+    }
+
+    /// only pad the tiles that intersect `aoi`. in neighbor mode only the tiles around them are
+    /// read. with the `geo` feature a polygon is passed as `GeoExtent::try_from(&polygon)?`.
+    pub fn aoi(mut self, aoi: GeoExtent) -> Self {
+        self.aoi = Some(aoi);
+        self
+    }
+
+    /// only pad the rasters of the input directory that `files` lists, e.g. the tiles that
+    /// changed since the last run. in neighbor mode the margins are still read from every tile.
+    pub fn file_list(mut self, files: Vec<PathBuf>) -> Self {
+        self.file_list = Some(files);
+        self
+    }
+
+    /// read the tile grids from the index in the input directory and update it, so the tiles
+    /// are not opened again to filter or order them.
+    pub fn index(mut self, index: bool) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// call `listener` with the report of every tile as soon as it is done, e.g. to show progress.
+    pub fn listener(mut self, listener: TileListener) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// set what happens when the output directory may not have room for the padded tiles.
+    pub fn disk_check(mut self, disk_check: DiskSpaceCheck) -> Self {
+        self.disk_check = disk_check;
+        self
+    }
+
+    /// only process the first `max_tiles` tiles in processing order, e.g. to try the parameters on
+    /// a slice of the data. with `resume` the next run continues with the following tiles.
+    pub fn max_tiles(mut self, max_tiles: usize) -> Self {
+        self.max_tiles = Some(max_tiles);
+        self
+    }
+
+    /// skip the tiles that were not started after the batch has run for `max_duration`.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// fail a tile that runs longer than `timeout` with `TimedOut` and go on with the next tile,
+    /// e.g. a corrupt tile whose reads do not end. the timeout is checked between the reads and
+    /// by gdal's copies, a single read that never returns can not be interrupted.
+    pub fn tile_timeout(mut self, timeout: Duration) -> Self {
+        self.tile_timeout = Some(timeout);
+        self
+    }
+
+    /// stop the batch after failed tiles instead of going on, the tiles that were not started
+    /// are skipped with `SkipReason::Aborted`.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+}
+
+/// options for `crop_down_to_size_with_options` and `crop_to_extent_with_options`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CropOptions {
+    /// how the cropped files are written
+    pub output: OutputOptions,
+    /// how the extent to crop to is snapped to the pixel grid of the buffered raster
+    pub snapping: Snapping,
+    /// gdal configuration and open options set before the rasters are cropped
+    pub gdal: GdalConfig,
+    /// stops the batch early when cancelled
+    pub cancel: CancelToken,
+    /// delete each input once its cropped output has been written and checked
+    pub cleanup: bool,
+    /// the number of rasters `crop_down_to_size_with_options` crops at the same time, 0 and 1
+    /// crop them one after the other
+    pub jobs: usize,
+    /// keep a raster that an output replaces as `<name>.bak`
+    pub backup: bool,
+    /// called with the report of every raster as soon as it is done
+    pub listener: TileListener,
+    /// what happens when the output directory may not have room for the cropped rasters
+    pub disk_check: DiskSpaceCheck,
+    /// only process this many tiles, tiles whose output is up to date are not counted
+    pub max_tiles: Option<usize>,
+    /// do not start tiles once the batch has run this long
+    pub max_duration: Option<Duration>,
+    /// fail a tile with `TimedOut` once it has run this long
+    pub tile_timeout: Option<Duration>,
+    /// whether the batch goes on after tiles failed
+    pub error_policy: ErrorPolicy,
+    /// copy the pixels in strips of this many rows and stage cog outputs on disk, so the memory
+    /// a crop needs does not grow with the size of the raster
+    pub chunk_rows: Option<usize>,
+    /// only crop the rasters of the input directory in this list
+    pub file_list: Option<Vec<PathBuf>>,
+    /// fill the part of the extent to crop to that the buffered raster falls short of with
+    /// nodata instead of failing
+    pub fill_shortfall: bool,
+}
+
+impl CropOptions {
+    /// set how the extent to crop to is snapped to the pixel grid of the buffered raster.
+    pub fn snapping(mut self, snapping: Snapping) -> Self {
+        self.snapping = snapping;
+        self
+    }
+
+    /// set the options used to write the cropped files.
+    pub fn output(mut self, output: OutputOptions) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// set the gdal configuration and open options.
+    pub fn gdal(mut self, gdal: GdalConfig) -> Self {
+        self.gdal = gdal;
+        self
+    }
+
+    /// stop the batch when `cancel` is cancelled, e.g. from a ctrl-c handler.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// delete the buffered inputs once their cropped outputs have been written and checked.
+    pub fn cleanup(mut self, cleanup: bool) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// set the number of rasters to crop at the same time.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// keep each raster an output replaces as `<name>.bak`, e.g. the originals replaced by
+    /// `crop_in_place_with_options`.
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.backup = backup;
+        self
+    }
+
+    /// call `listener` with the report of every raster as soon as it is done.
+    pub fn listener(mut self, listener: TileListener) -> Self {
+        self.listener = listener;
+        self
+    }
+
+    /// set what happens when the output directory may not have room for the cropped rasters.
+    pub fn disk_check(mut self, disk_check: DiskSpaceCheck) -> Self {
+        self.disk_check = disk_check;
+        self
+    }
+
+    /// only process the first `max_tiles` tiles in processing order, e.g. to try the parameters on
+    /// a slice of the data. with `resume` the next run continues with the following tiles.
+    pub fn max_tiles(mut self, max_tiles: usize) -> Self {
+        self.max_tiles = Some(max_tiles);
+        self
+    }
+
+    /// skip the tiles that were not started after the batch has run for `max_duration`.
+    pub fn max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+
+    /// fail a tile that runs longer than `timeout` with `TimedOut` and go on with the next tile,
+    /// e.g. a corrupt tile whose reads do not end. the timeout is checked between the reads and
+    /// by gdal's copies, a single read that never returns can not be interrupted.
+    pub fn tile_timeout(mut self, timeout: Duration) -> Self {
+        self.tile_timeout = Some(timeout);
+        self
+    }
+
+    /// stop the batch after failed tiles instead of going on, the tiles that were not started
+    /// are skipped with `SkipReason::Aborted`.
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// copy the pixels `chunk_rows` rows at a time instead of a block row at a time and stage cog
+    /// outputs in a geotiff on disk instead of in memory, for rasters that do not fit in memory.
+    pub fn chunk_rows(mut self, chunk_rows: usize) -> Self {
+        self.chunk_rows = Some(chunk_rows);
+        self
+    }
+
+    /// only crop the rasters of the input directory that `files` lists.
+    pub fn file_list(mut self, files: Vec<PathBuf>) -> Self {
+        self.file_list = Some(files);
+        self
+    }
+
+    /// fill the rows and columns of the extent to crop to that a buffered raster does not cover,
+    /// e.g. one written by gdalwarp with a slightly different origin, with the nodata value of
+    /// each band, 0 for bands without one. without it such rasters fail with the pixels they
+    /// are short by on each side.
+    pub fn fill_shortfall(mut self, fill_shortfall: bool) -> Self {
+        self.fill_shortfall = fill_shortfall;
+        self
+    }
+}
+
+/// options for `pad_process_crop`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PipelineOptions {
+    /// how the tiles are padded, the padded rasters are only intermediates
+    pub pad: PadOptions,
+    /// how the processed rasters are cropped into the output directory
+    pub crop: CropOptions,
+    /// the directory the run directories are created in, the system temp directory when not set
+    pub temp_root: Option<PathBuf>,
+    /// keep the run directory with the padded and processed rasters instead of removing it
+    pub keep_temp: bool,
+    /// start without checking that the temp root has room for the intermediates
+    pub skip_disk_check: bool,
+}
+
+impl PipelineOptions {
+    /// set the options the tiles are padded with.
+    pub fn pad(mut self, pad: PadOptions) -> Self {
+        self.pad = pad;
+        self
+    }
+
+    /// set the options the processed rasters are cropped with.
+    pub fn crop(mut self, crop: CropOptions) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    /// create the run directories in `temp_root` instead of the system temp directory, e.g. on
+    /// a fast local disk.
+    pub fn temp_root(mut self, temp_root: PathBuf) -> Self {
+        self.temp_root = Some(temp_root);
+        self
+    }
+
+    /// keep the intermediates of the run, `PipelineReport::temp_dir` tells where they are.
+    pub fn keep_temp(mut self, keep_temp: bool) -> Self {
+        self.keep_temp = keep_temp;
+        self
+    }
+
+    /// skip the check that the temp root has room for the padded and processed rasters.
+    pub fn skip_disk_check(mut self, skip_disk_check: bool) -> Self {
+        self.skip_disk_check = skip_disk_check;
+        self
+    }
+}
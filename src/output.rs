@@ -0,0 +1,318 @@
+//! creating and finishing the output rasters.
+use crate::{
+    gpkg,
+    path::{create_copy, create_dataset},
+    sidecar::appended,
+    stac, stats, vsi, OutputFormat, OutputOptions,
+};
+use gdal::{Dataset, Driver, DriverManager, Metadata};
+use gdal_sys::{GDALDataType, GDALGetDataTypeSizeBytes, GDALGetRasterDataType};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// checks that the driver of the output format is available and can write the outputs.
+pub(crate) fn check_driver(format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let driver = get_driver(format.driver_name())?;
+    // cog and vrt outputs are written with CreateCopy from a staging dataset
+    let capability = match format {
+        OutputFormat::Cog | OutputFormat::Vrt | OutputFormat::VirtualVrt => "DCAP_CREATECOPY",
+        OutputFormat::GTiff | OutputFormat::Hfa | OutputFormat::Gpkg | OutputFormat::Zarr => {
+            "DCAP_CREATE"
+        }
+    };
+    if driver.metadata_item(capability, "").as_deref() != Some("YES") {
+        return Err(format!(
+            "the {} driver does not support {}",
+            format.driver_name(),
+            capability
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// the temporary name an output is written to, `finish_output` renames it to the final name once
+/// the output is complete so a crash never leaves a truncated file under the final name.
+pub(crate) fn partial_path(path: &Path) -> PathBuf {
+    // the geopackage driver warns about files without the .gpkg extension
+    if gpkg::is_geopackage(path) {
+        let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+        file_name.push(".partial.gpkg");
+        return path.with_file_name(file_name);
+    }
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".partial");
+    path.with_file_name(file_name)
+}
+
+/// creates the dataset the pixels of an output are written to with bands of `data_type`,
+/// choosing the BIGTIFF creation option from the output options.
+/// formats that can not be written directly get a staging dataset which `finish_output` copies
+/// to `path`, a MEM dataset for cogs and a geotiff next to the output for vrts.
+pub(crate) fn create_output(
+    path: &Path,
+    size: (usize, usize),
+    bands: isize,
+    data_type: GDALDataType::Type,
+    output_options: &OutputOptions,
+) -> Result<Dataset, Box<dyn Error>> {
+    let (driver_name, create_path) = match output_options.format {
+        OutputFormat::Cog => ("MEM", PathBuf::new()),
+        OutputFormat::Vrt => {
+            let data_path = path.with_extension("tif");
+            if data_path == path {
+                return Err(format!("the vrt output {:?} needs a .vrt extension", path).into());
+            }
+            ("GTiff", data_path)
+        }
+        OutputFormat::GTiff | OutputFormat::Hfa | OutputFormat::Gpkg | OutputFormat::Zarr => {
+            (output_options.format.driver_name(), partial_path(path))
+        }
+        OutputFormat::VirtualVrt => {
+            return Err(format!(
+                "{:?} can not be written as a virtual vrt, only padded tiles can",
+                path
+            )
+            .into())
+        }
+    };
+    let driver = get_driver(driver_name)?;
+
+    // the table or array keeps the name of the output, not the one of the partial file
+    let name = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let creation_options = match driver_name {
+        "GTiff" => tiff_creation_options(size, bands, data_type, output_options),
+        "GPKG" => vec![("RASTER_TABLE".to_string(), name)],
+        "Zarr" => zarr_creation_options(size, name),
+        _ => Vec::new(),
+    };
+
+    create_dataset(
+        &driver,
+        &create_path,
+        size,
+        bands,
+        data_type,
+        &creation_options,
+    )
+}
+
+/// applies the post processing steps from the output options to a freshly written dataset,
+/// copies staging datasets and moves the finished output to `path`
+pub(crate) fn finish_output(
+    mut ds: Dataset,
+    path: &Path,
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(overviews) = &output_options.overviews {
+        // an empty band list builds the overviews for every band
+        if let Err(e) = ds.build_overviews(&overviews.resampling, &overviews.levels, &[]) {
+            return Err(Box::new(e));
+        };
+    }
+
+    if let Some(mode) = output_options.statistics {
+        stats::write_statistics(&ds, mode)?;
+    }
+
+    let creation_options = match output_options.format {
+        OutputFormat::GTiff | OutputFormat::Hfa | OutputFormat::Gpkg | OutputFormat::Zarr => None,
+        OutputFormat::Cog => {
+            let band = match ds.rasterband(1) {
+                Ok(band) => band,
+                Err(e) => return Err(Box::new(e)),
+            };
+            let data_type = unsafe { GDALGetRasterDataType(band.c_rasterband()) };
+            Some(tiff_creation_options(
+                ds.raster_size(),
+                ds.raster_count(),
+                data_type,
+                output_options,
+            ))
+        }
+        OutputFormat::Vrt | OutputFormat::VirtualVrt => Some(Vec::new()),
+    };
+    if let Some(creation_options) = creation_options {
+        let driver = get_driver(output_options.format.driver_name())?;
+        drop(create_copy(
+            &ds,
+            &driver,
+            &partial_path(path),
+            &creation_options,
+        )?);
+    }
+
+    let item = if output_options.stac {
+        Some(stac::item(&ds, path, output_options.format)?)
+    } else {
+        None
+    };
+
+    // closing the dataset flushes it to disk before it gets its final name
+    drop(ds);
+    if vsi::is_file(&staging_path(path)) {
+        vsi::remove_file(&staging_path(path))?;
+    }
+    // gdal keeps what the format can not hold, e.g. attribute tables, in a pam sidecar
+    let pam_sidecar = appended(&partial_path(path), "aux.xml");
+    if vsi::is_file(&pam_sidecar) {
+        vsi::rename(&pam_sidecar, &appended(path, "aux.xml"))?;
+    }
+    // a zarr store is a directory, renaming onto one that is not empty fails
+    if vsi::is_dir(path) {
+        vsi::remove_dir_all(path)?;
+    }
+    vsi::rename(&partial_path(path), path)?;
+
+    if let Some(item) = item {
+        fs::write(stac::item_path(path), serde_json::to_string_pretty(&item)?)?;
+    }
+
+    Ok(())
+}
+
+/// writes a copy of `ds` to `path` in the output format through its partial file, for outputs that
+/// gdal assembles from other rasters, such as a mosaic
+pub(crate) fn copy_output(
+    ds: &Dataset,
+    path: &Path,
+    output_options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    let creation_options = match output_options.format {
+        OutputFormat::GTiff | OutputFormat::Cog => {
+            let band = match ds.rasterband(1) {
+                Ok(band) => band,
+                Err(e) => return Err(Box::new(e)),
+            };
+            let data_type = unsafe { GDALGetRasterDataType(band.c_rasterband()) };
+            tiff_creation_options(
+                ds.raster_size(),
+                ds.raster_count(),
+                data_type,
+                output_options,
+            )
+        }
+        OutputFormat::VirtualVrt => {
+            return Err(format!("{:?} can not be written as a virtual vrt", path).into())
+        }
+        OutputFormat::Zarr => zarr_creation_options(
+            ds.raster_size(),
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        OutputFormat::Vrt | OutputFormat::Hfa | OutputFormat::Gpkg => Vec::new(),
+    };
+    let driver = get_driver(output_options.format.driver_name())?;
+    drop(create_copy(
+        ds,
+        &driver,
+        &partial_path(path),
+        &creation_options,
+    )?);
+    if vsi::is_dir(path) {
+        vsi::remove_dir_all(path)?;
+    }
+    vsi::rename(&partial_path(path), path)?;
+    Ok(())
+}
+
+/// same as `create_output` but a cog is staged in a geotiff next to the output instead of in
+/// memory, so writing it holds no more than gdal's block cache. `finish_output` removes the
+/// staging geotiff
+pub(crate) fn create_output_on_disk(
+    path: &Path,
+    size: (usize, usize),
+    bands: isize,
+    data_type: GDALDataType::Type,
+    output_options: &OutputOptions,
+) -> Result<Dataset, Box<dyn Error>> {
+    if output_options.format != OutputFormat::Cog {
+        return create_output(path, size, bands, data_type, output_options);
+    }
+    let driver = get_driver("GTiff")?;
+    let creation_options = tiff_creation_options(size, bands, data_type, output_options);
+    create_dataset(
+        &driver,
+        &staging_path(path),
+        size,
+        bands,
+        data_type,
+        &creation_options,
+    )
+}
+
+/// the geotiff a cog is staged in by `create_output_on_disk`
+fn staging_path(path: &Path) -> PathBuf {
+    appended(&partial_path(path), "staging.tif")
+}
+
+/// a zarr v2 store with consolidated metadata whose array `name` is a single chunk of `size`, so a
+/// reader gets a whole output with one request per band
+fn zarr_creation_options(size: (usize, usize), name: String) -> Vec<(String, String)> {
+    vec![
+        ("FORMAT".to_string(), "ZARR_V2".to_string()),
+        ("CREATE_ZMETADATA".to_string(), "YES".to_string()),
+        ("ARRAY_NAME".to_string(), name),
+        (
+            "BLOCKSIZE".to_string(),
+            format!("{},{}", size.0.max(1), size.1.max(1)),
+        ),
+    ]
+}
+
+/// the bytes of the output at `path`, the sum of its files for a zarr store
+pub(crate) fn output_bytes(path: &Path) -> u64 {
+    if !vsi::is_dir(path) {
+        return vsi::file_size(path);
+    }
+    match vsi::read_dir(path) {
+        Ok(entries) => entries.iter().map(|entry| output_bytes(entry)).sum(),
+        Err(_) => 0,
+    }
+}
+
+/// removes the partial file of `path`, or the partial directory of a zarr store
+pub(crate) fn remove_partial(path: &Path) {
+    let partial = partial_path(path);
+    let _ = if vsi::is_dir(&partial) {
+        vsi::remove_dir_all(&partial)
+    } else {
+        vsi::remove_file(&partial)
+    };
+}
+
+/// the BIGTIFF creation option for an output of the given size and data type and the NUM_THREADS
+/// used to compress it
+fn tiff_creation_options(
+    size: (usize, usize),
+    bands: isize,
+    data_type: GDALDataType::Type,
+    output_options: &OutputOptions,
+) -> Vec<(String, String)> {
+    let pixel_bytes = unsafe { GDALGetDataTypeSizeBytes(data_type) } as u64;
+    let estimated_bytes = size.0 as u64 * size.1 as u64 * bands as u64 * pixel_bytes;
+    let mut creation_options = Vec::new();
+    if let Some(bigtiff) = output_options.bigtiff.creation_value(estimated_bytes) {
+        creation_options.push(("BIGTIFF".to_string(), bigtiff.to_string()));
+    }
+    if let Some(threads) = output_options.compression_threads.creation_value() {
+        creation_options.push(("NUM_THREADS".to_string(), threads));
+    }
+    creation_options
+}
+
+pub(crate) fn get_driver(name: &str) -> Result<Driver, Box<dyn Error>> {
+    match DriverManager::get_driver_by_name(name) {
+        Ok(driver) => Ok(driver),
+        Err(e) => Err(Box::new(e)),
+    }
+}
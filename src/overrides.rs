@@ -0,0 +1,100 @@
+//! per tile margins that replace the margin of a batch, e.g. larger margins for coastal or steep
+//! tiles, read from a csv or json file.
+//!
+//! a csv file holds `name,margin` or `name,x,y` rows, a header row, empty lines and `#` comments
+//! are skipped. a json file, one whose name ends in `.json`, holds an object of names to a margin
+//! or an `[x, y]` array, e.g. `{"tile_3_4.tif": 64, "tile_3_5": [64, 32]}`. the names are the
+//! file names of the tiles, with or without their extension.
+use crate::{geometry::Margin, gpkg::tile_file_name};
+use serde_json::Value;
+use std::{collections::BTreeMap, error::Error, fs, path::Path};
+
+/// reads the per tile margins of the csv or json file at `path`
+pub fn read_margin_overrides(path: &Path) -> Result<BTreeMap<String, Margin>, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    if is_json {
+        json_overrides(&text).map_err(|e| format!("{:?}: {}", path, e).into())
+    } else {
+        csv_overrides(&text).map_err(|e| format!("{:?}: {}", path, e).into())
+    }
+}
+
+/// the margin of `tile`, its override when `overrides` has one and `margin` otherwise
+pub(crate) fn tile_margin(
+    overrides: &BTreeMap<String, Margin>,
+    tile: &Path,
+    margin: Margin,
+) -> Margin {
+    if overrides.is_empty() {
+        return margin;
+    }
+    let file_name = tile_file_name(tile).unwrap_or_default();
+    let stem = Path::new(&file_name).file_stem().unwrap_or_default();
+    overrides
+        .get(file_name.to_string_lossy().as_ref())
+        .or_else(|| overrides.get(stem.to_string_lossy().as_ref()))
+        .copied()
+        .unwrap_or(margin)
+}
+
+fn csv_overrides(text: &str) -> Result<BTreeMap<String, Margin>, String> {
+    let mut overrides = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let pixels: Result<Vec<usize>, _> = fields[1..].iter().map(|field| field.parse()).collect();
+        let margin = match (pixels, fields.len()) {
+            (Ok(pixels), 2) => Margin::Uniform(pixels[0]),
+            (Ok(pixels), 3) => Margin::PerAxis {
+                x: pixels[0],
+                y: pixels[1],
+            },
+            // the header
+            (Err(_), _) if overrides.is_empty() => continue,
+            _ => {
+                return Err(format!(
+                    "line {} is not name,margin or name,x,y: {:?}",
+                    number + 1,
+                    line
+                ))
+            }
+        };
+        overrides.insert(fields[0].to_string(), margin);
+    }
+    Ok(overrides)
+}
+
+fn json_overrides(text: &str) -> Result<BTreeMap<String, Margin>, String> {
+    let object = match serde_json::from_str::<Value>(text) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => return Err("expected an object of tile names to margins".to_string()),
+        Err(e) => return Err(e.to_string()),
+    };
+    let pixels = |value: &Value| value.as_u64().map(|pixels| pixels as usize);
+    let mut overrides = BTreeMap::new();
+    for (name, value) in object {
+        let margin = match &value {
+            Value::Array(axes) if axes.len() == 2 => match (pixels(&axes[0]), pixels(&axes[1])) {
+                (Some(x), Some(y)) => Some(Margin::PerAxis { x, y }),
+                _ => None,
+            },
+            value => pixels(value).map(Margin::Uniform),
+        };
+        match margin {
+            Some(margin) => overrides.insert(name, margin),
+            None => {
+                return Err(format!(
+                    "the margin of {:?} is not a number of pixels or [x, y]: {}",
+                    name, value
+                ))
+            }
+        };
+    }
+    Ok(overrides)
+}
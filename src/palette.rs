@@ -0,0 +1,113 @@
+//! color tables, category names and raster attribute tables of classified rasters.
+//!
+//! the pixels are written as f32 and a geotiff band of f32 can not hold a color table, so rasters
+//! with a palette keep their Byte or UInt16 data type. the palette values are whole numbers and
+//! pass through the f64 buffers unchanged.
+//!
+//! geotiffs keep the attribute tables in a `.aux.xml` next to the file (gdal pam), formats that
+//! have no place for them, or gdal with `GDAL_PAM_ENABLED=NO`, lose them with a warning.
+use crate::messages::message;
+use gdal::Dataset;
+use gdal_sys::{
+    CPLErr, GDALDataType, GDALGetDefaultRAT, GDALGetRasterCategoryNames, GDALGetRasterDataType,
+    GDALSetDefaultRAT, GDALSetRasterCategoryNames,
+};
+use std::error::Error;
+
+/// the data type of the outputs of `bands` of `ds`: the type of the bands when the first band has
+/// a color table and every band is Byte or every band is UInt16, f64 when a band is f64 so its
+/// precision is kept, f32 otherwise
+pub(crate) fn output_data_type(
+    ds: &Dataset,
+    bands: &[isize],
+) -> Result<GDALDataType::Type, Box<dyn Error>> {
+    let (mut data_types, mut has_palette) = (Vec::new(), false);
+    for &band_index in bands {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        if data_types.is_empty() {
+            has_palette = band.color_table().is_some();
+        }
+        data_types.push(unsafe { GDALGetRasterDataType(band.c_rasterband()) });
+    }
+    match data_types.first() {
+        Some(&first)
+            if has_palette
+                && matches!(first, GDALDataType::GDT_Byte | GDALDataType::GDT_UInt16)
+                && data_types.iter().all(|data_type| *data_type == first) =>
+        {
+            Ok(first)
+        }
+        _ if data_types.contains(&GDALDataType::GDT_Float64) => Ok(GDALDataType::GDT_Float64),
+        _ => Ok(GDALDataType::GDT_Float32),
+    }
+}
+
+/// true when band `band_index` of `ds` holds classes: it is Byte and has a color table
+pub(crate) fn is_categorical(ds: &Dataset, band_index: isize) -> Result<bool, Box<dyn Error>> {
+    let band = match ds.rasterband(band_index) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let data_type = unsafe { GDALGetRasterDataType(band.c_rasterband()) };
+    Ok(data_type == GDALDataType::GDT_Byte && band.color_table().is_some())
+}
+
+/// copies the color table, the category names and the attribute table of `bands` of `source` to
+/// the bands of `target` in turn
+pub(crate) fn copy_palettes(
+    source: &Dataset,
+    target: &Dataset,
+    bands: &[isize],
+) -> Result<(), Box<dyn Error>> {
+    for (target_index, &band_index) in (1..=target.raster_count()).zip(bands) {
+        let source_band = match source.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let mut target_band = match target.rasterband(target_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        // only Byte and UInt16 bands hold a color table
+        let target_type = unsafe { GDALGetRasterDataType(target_band.c_rasterband()) };
+        let holds_palette = matches!(
+            target_type,
+            GDALDataType::GDT_Byte | GDALDataType::GDT_UInt16
+        );
+        if let Some(color_table) = source_band.color_table().filter(|_| holds_palette) {
+            target_band.set_color_table(&color_table);
+        }
+
+        // the table belongs to the source band, gdal copies it into the target
+        let result = unsafe {
+            let rat = GDALGetDefaultRAT(source_band.c_rasterband());
+            if rat.is_null() {
+                CPLErr::CE_None
+            } else {
+                GDALSetDefaultRAT(target_band.c_rasterband(), rat)
+            }
+        };
+        if result != CPLErr::CE_None {
+            message(&format!(
+                "Warning: the output format can not hold the raster attribute table of band {}, it is not copied",
+                band_index
+            ));
+        }
+
+        // the names belong to the source band, gdal copies them into the target
+        let result = unsafe {
+            let names = GDALGetRasterCategoryNames(source_band.c_rasterband());
+            if names.is_null() {
+                continue;
+            }
+            GDALSetRasterCategoryNames(target_band.c_rasterband(), names)
+        };
+        if result != CPLErr::CE_None {
+            return Err(format!("could not copy the category names of band {}", band_index).into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! opening and creating datasets without losing non utf-8 paths.
+//!
+//! the gdal crate converts paths with `to_string_lossy`, which replaces the bytes of a non utf-8
+//! path on unix and so opens or creates the wrong file. these helpers hand the path to gdal as
+//! the raw bytes on unix and as utf-8 on windows, where gdal expects utf-8 filenames. windows
+//! extended-length paths (`\\?\`) are passed through unchanged.
+use crate::cancel::{abort_past_deadline, check_tile_deadline};
+use gdal::{Dataset, Driver};
+use gdal_sys::{
+    GDALCreate, GDALCreateCopy, GDALDataType, GDALOpenEx, GDAL_OF_RASTER, GDAL_OF_UPDATE,
+    GDAL_OF_VECTOR, GDAL_OF_VERBOSE_ERROR,
+};
+use std::{
+    error::Error,
+    ffi::{c_char, c_int, CStr, CString},
+    path::Path,
+    ptr,
+};
+
+/// the path as a c string in the encoding gdal expects
+pub(crate) fn gdal_path(path: &Path) -> Result<CString, Box<dyn Error>> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = match path.to_str() {
+        Some(path) => path.as_bytes(),
+        None => return Err(format!("{:?} is not valid unicode", path).into()),
+    };
+    match CString::new(bytes) {
+        Ok(path) => Ok(path),
+        Err(_) => Err(format!("{:?} contains a nul byte", path).into()),
+    }
+}
+
+/// opens a raster, `open_options` are passed to the driver as KEY=VALUE
+pub(crate) fn open_dataset(
+    path: &Path,
+    open_options: &[(String, String)],
+) -> Result<Dataset, Box<dyn Error>> {
+    open_with_flags(path, GDAL_OF_RASTER, open_options)
+}
+
+/// opens a raster for writing, e.g. a vrt whose bands are changed
+pub(crate) fn open_raster_for_update(path: &Path) -> Result<Dataset, Box<dyn Error>> {
+    open_with_flags(path, GDAL_OF_RASTER | GDAL_OF_UPDATE, &[])
+}
+
+/// opens a file for its vector layers and sql, e.g. a geopackage to list its tables
+pub(crate) fn open_vector_dataset(path: &Path) -> Result<Dataset, Box<dyn Error>> {
+    open_with_flags(path, GDAL_OF_VECTOR, &[])
+}
+
+fn open_with_flags(
+    path: &Path,
+    flags: u32,
+    open_options: &[(String, String)],
+) -> Result<Dataset, Box<dyn Error>> {
+    let c_path = gdal_path(path)?;
+    let open_options = key_value_list(open_options)?;
+    let c_dataset = unsafe {
+        GDALOpenEx(
+            c_path.as_ptr(),
+            flags | GDAL_OF_VERBOSE_ERROR,
+            ptr::null(),
+            open_options.pointers.as_ptr(),
+            ptr::null(),
+        )
+    };
+    if c_dataset.is_null() {
+        return Err(last_error(&format!("could not open {:?}", path)));
+    }
+    Ok(unsafe { Dataset::from_c_dataset(c_dataset) })
+}
+
+/// creates a raster with bands of `data_type`, `creation_options` are passed as KEY=VALUE
+pub(crate) fn create_dataset(
+    driver: &Driver,
+    path: &Path,
+    size: (usize, usize),
+    bands: isize,
+    data_type: GDALDataType::Type,
+    creation_options: &[(String, String)],
+) -> Result<Dataset, Box<dyn Error>> {
+    let c_path = gdal_path(path)?;
+    let creation_options = key_value_list(creation_options)?;
+    let c_dataset = unsafe {
+        GDALCreate(
+            driver.c_driver(),
+            c_path.as_ptr(),
+            size.0 as c_int,
+            size.1 as c_int,
+            bands as c_int,
+            data_type,
+            creation_options.pointers.as_ptr() as *mut *mut c_char,
+        )
+    };
+    if c_dataset.is_null() {
+        return Err(last_error(&format!("could not create {:?}", path)));
+    }
+    Ok(unsafe { Dataset::from_c_dataset(c_dataset) })
+}
+
+/// copies `ds` to a new raster written by `driver`
+pub(crate) fn create_copy(
+    ds: &Dataset,
+    driver: &Driver,
+    path: &Path,
+    creation_options: &[(String, String)],
+) -> Result<Dataset, Box<dyn Error>> {
+    let c_path = gdal_path(path)?;
+    let creation_options = key_value_list(creation_options)?;
+    let c_dataset = unsafe {
+        GDALCreateCopy(
+            driver.c_driver(),
+            c_path.as_ptr(),
+            ds.c_dataset(),
+            0,
+            creation_options.pointers.as_ptr() as *mut *mut c_char,
+            Some(abort_past_deadline),
+            ptr::null_mut(),
+        )
+    };
+    if c_dataset.is_null() {
+        // the progress function aborted the copy
+        check_tile_deadline()?;
+        return Err(last_error(&format!("could not create {:?}", path)));
+    }
+    Ok(unsafe { Dataset::from_c_dataset(c_dataset) })
+}
+
+/// a null terminated list of c strings, the pointers stay valid as long as the list lives
+struct StringList {
+    _strings: Vec<CString>,
+    pointers: Vec<*const c_char>,
+}
+
+fn string_list(values: impl Iterator<Item = String>) -> Result<StringList, Box<dyn Error>> {
+    let mut strings = Vec::new();
+    for value in values {
+        strings.push(CString::new(value)?);
+    }
+    let mut pointers: Vec<*const c_char> = strings.iter().map(|value| value.as_ptr()).collect();
+    pointers.push(ptr::null());
+    Ok(StringList {
+        _strings: strings,
+        pointers,
+    })
+}
+
+fn key_value_list(options: &[(String, String)]) -> Result<StringList, Box<dyn Error>> {
+    string_list(
+        options
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    )
+}
+
+/// the last gdal error message prefixed with `context`
+pub(crate) fn last_error(context: &str) -> Box<dyn Error> {
+    let message = unsafe { CStr::from_ptr(gdal_sys::CPLGetLastErrorMsg()) };
+    format!("{}: {}", context, message.to_string_lossy()).into()
+}
@@ -0,0 +1,142 @@
+//! padding, processing and cropping a tile set in one run.
+//!
+//! the padded and the processed rasters of a run are written to a directory of their own under
+//! the temp root, so runs on the same machine never share intermediates, and the directory is
+//! removed when the run ends.
+use crate::{
+    analyze, crop_down_to_size_with_options, disk::check_free_space, geometry::Margin,
+    messages::message, neighbor_buffer_with_options, vrt_buffer_with_options, BatchReport,
+    DiskSpaceCheck, PipelineOptions,
+};
+use serde::Serialize;
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// numbers the run directories of this process
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// the reports of the pad and the crop batch of a pipeline run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PipelineReport {
+    pub pad: BatchReport,
+    pub crop: BatchReport,
+    /// the directory of the intermediates when it was kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl PipelineReport {
+    pub fn cancelled(&self) -> bool {
+        self.pad.cancelled() || self.crop.cancelled()
+    }
+
+    /// true when the pad or the crop stopped after failed tiles, see `ErrorPolicy`
+    pub fn aborted(&self) -> bool {
+        self.pad.aborted() || self.crop.aborted()
+    }
+
+    /// the tiles that failed to pad or to crop
+    pub fn failed(&self) -> usize {
+        self.pad.failed() + self.crop.failed()
+    }
+}
+
+/// pads the geotiffs of `input_dir`, runs `process` on them and crops its results back to the
+/// extents of the originals into `output_dir`.
+/// reference: the mosaic the margin is read from, the neighbors in `input_dir` when `None`
+/// process: called with the directory of the padded rasters and the directory it must write the
+/// processed rasters to, with the same file names
+/// options: see `PipelineOptions`
+pub fn pad_process_crop(
+    input_dir: &Path,
+    reference: Option<&Path>,
+    output_dir: &Path,
+    margin: impl Into<Margin>,
+    process: impl FnOnce(&Path, &Path) -> Result<(), Box<dyn Error>>,
+    options: &PipelineOptions,
+) -> Result<PipelineReport, Box<dyn Error>> {
+    let margin = margin.into();
+    let root = match &options.temp_root {
+        Some(root) => root.clone(),
+        None => env::temp_dir(),
+    };
+    fs::create_dir_all(&root)?;
+    if !options.skip_disk_check {
+        // the padded and the processed rasters are on disk at the same time
+        let required = analyze(input_dir, reference, margin)?.total_bytes() * 2;
+        check_free_space(&root, required, DiskSpaceCheck::Error)?;
+    }
+
+    // the check above covers the intermediates of both batches
+    let pad_options = options.pad.clone().disk_check(DiskSpaceCheck::Off);
+    let crop_options = options.crop.clone().disk_check(DiskSpaceCheck::Off);
+
+    let mut run_dir = RunDir::create(&root)?;
+    let padded_dir = run_dir.path.join("padded");
+    let processed_dir = run_dir.path.join("processed");
+    fs::create_dir_all(&processed_dir)?;
+
+    let pad = match reference {
+        Some(reference) => {
+            vrt_buffer_with_options(input_dir, &padded_dir, reference, margin, &pad_options)?
+        }
+        None => neighbor_buffer_with_options(input_dir, &padded_dir, margin, &pad_options)?,
+    };
+    let mut report = PipelineReport {
+        pad,
+        ..PipelineReport::default()
+    };
+    if !report.pad.cancelled() && !report.pad.aborted() {
+        process(&padded_dir, &processed_dir)?;
+        report.crop =
+            crop_down_to_size_with_options(input_dir, &processed_dir, output_dir, &crop_options)?;
+    }
+
+    if options.keep_temp {
+        run_dir.keep = true;
+        report.temp_dir = Some(run_dir.path.clone());
+    }
+    Ok(report)
+}
+
+/// a directory for the intermediates of a single run, removed when dropped unless kept.
+struct RunDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl RunDir {
+    /// creates a directory in `root` named after the process, the time and a counter, so runs in
+    /// one or in several processes never get the same directory
+    fn create(root: &Path) -> Result<Self, Box<dyn Error>> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        loop {
+            let run = RUNS.fetch_add(1, Ordering::Relaxed);
+            let path = root.join(format!("vrt_buffer_{}_{}_{}", process::id(), nanos, run));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(RunDir { path, keep: false }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            message(&format!("Warning: could not remove {:?}: {}", self.path, e));
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! the provenance of an output, a `<name>.provenance.json` next to it that records what the
+//! output was derived from and how, for audits of derived rasters.
+//!
+//! the file holds the inputs with their sha256, the reference of a pad with the sha256 of a vrt
+//! file, the margin that was added or trimmed, the gdal and crate versions, the command line of
+//! the process and the time the tile took. inputs on gdal's virtual file systems have no sha256.
+use crate::{
+    geometry::Margin, gpkg, manifest::sha256_file, messages::message, stac::rfc3339, vsi,
+    TileReport, TileStatus, TrimmedMargin,
+};
+use gdal::version::version_info;
+use serde::Serialize;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[derive(Debug, Serialize)]
+struct Provenance {
+    output: PathBuf,
+    inputs: Vec<Input>,
+    /// the reference a pad read the margin from, `neighbors` for a run without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reference: Option<Input>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    margin: Option<Margin>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trimmed: Option<TrimmedMargin>,
+    gdal_version: String,
+    crate_version: &'static str,
+    command_line: Vec<String>,
+    finished: String,
+    seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Input {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// the provenance file of `output`
+pub(crate) fn provenance_path(output: &Path) -> PathBuf {
+    output.with_extension("provenance.json")
+}
+
+/// writes the provenance of the output of a written tile, a tile whose provenance can not be
+/// written fails.
+/// inputs: the rasters the output was made from
+/// reference: the reference of a pad and the sha256 of a vrt file
+pub(crate) fn write_provenance(
+    report: &mut TileReport,
+    inputs: &[&Path],
+    reference: Option<(&str, Option<&str>)>,
+) {
+    if report.status != TileStatus::Written {
+        return;
+    }
+    if let Err(e) = write(report, inputs, reference) {
+        message(&format!(
+            "Could not write the provenance of {:?}: {}",
+            report.output, e
+        ));
+        report.status = TileStatus::Failed(format!("could not write the provenance: {}", e));
+    }
+}
+
+fn write(
+    report: &TileReport,
+    inputs: &[&Path],
+    reference: Option<(&str, Option<&str>)>,
+) -> Result<(), Box<dyn Error>> {
+    let mut hashed = Vec::new();
+    for input in inputs {
+        let sha256 = match vsi::is_virtual(input) {
+            true => None,
+            false => Some(sha256_file(gpkg::file_of(input))?),
+        };
+        hashed.push(Input {
+            path: input.display().to_string(),
+            sha256,
+        });
+    }
+    let provenance = Provenance {
+        output: report.output.clone(),
+        inputs: hashed,
+        reference: reference.map(|(path, sha256)| Input {
+            path: path.to_string(),
+            sha256: sha256.map(str::to_string),
+        }),
+        margin: report.margin,
+        trimmed: report.trimmed,
+        gdal_version: version_info("RELEASE_NAME"),
+        crate_version: env!("CARGO_PKG_VERSION"),
+        command_line: std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        finished: rfc3339(SystemTime::now())?,
+        seconds: report.seconds,
+    };
+    fs::write(
+        provenance_path(&report.output),
+        serde_json::to_string_pretty(&provenance)?,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! padded tiles at several resolutions in one run, for algorithms that work on an image pyramid
+//! such as a multi-scale tpi.
+//!
+//! a level of factor 2 holds every tile decimated to half of its rows and columns and padded by
+//! the same number of pixels as the full resolution, so its margin reaches twice as far. the
+//! margins are read from the overviews of the reference when it has them, gdal resamples the
+//! reference with `PadOptions::resampling` otherwise. every level is written to a `level_<factor>`
+//! subdirectory of the output directory.
+//!
+//! the decimated tiles are small vrts over the tiles in gdal's in memory file system, they only
+//! live as long as their level is padded.
+use crate::{
+    absolute_path, band_resampling, check_driver, check_separate_dirs, data_type_name,
+    geometry::{Margin, PixelWindow},
+    gpkg,
+    messages::message,
+    pad_tiles,
+    palette::copy_palettes,
+    path::open_raster_for_update,
+    select_tiles,
+    virtual_tile::{virtual_tile_xml, VirtualBand},
+    BatchReport, OutputFormat, PadOptions, TileEvent, TileListener, VrtSource,
+};
+use gdal::vsi::{create_mem_file, unlink_mem_file};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// numbers the in memory directories of the decimated tiles of this process
+static LEVELS: AtomicUsize = AtomicUsize::new(0);
+
+/// the name of the subdirectory of the output directory a level is written to
+fn level_dir_name(factor: usize) -> String {
+    format!("level_{}", factor)
+}
+
+/// pads the tiles of `input_dir` from `reference` like `vrt_buffer_with_options` once for every
+/// factor of `factors`, 1 being the full resolution, into the `level_<factor>` subdirectories of
+/// `output_dir`. the report holds the tiles of every level with the inputs they were
+/// decimated from.
+/// margin: in pixels of each level
+/// options: see `PadOptions`, `resume` only applies to the full resolution
+pub fn vrt_buffer_pyramid(
+    input_dir: &Path,
+    output_dir: &Path,
+    reference: &Path,
+    margin: impl Into<Margin>,
+    factors: &[usize],
+    options: &PadOptions,
+) -> Result<BatchReport, Box<dyn Error>> {
+    if factors.is_empty() || factors.contains(&0) {
+        return Err(
+            "the pyramid needs at least one level and every factor must be 1 or more".into(),
+        );
+    }
+    if options.output.format == OutputFormat::VirtualVrt
+        || options.mosaic_vrt.is_some()
+        || options.output.archive.is_some()
+    {
+        return Err(
+            "pyramid levels can not be written as virtual vrts, into a single mosaic vrt or into \
+             a single archive"
+                .into(),
+        );
+    }
+    check_separate_dirs(input_dir, output_dir, &options.output)?;
+    options.gdal.apply()?;
+    check_driver(options.output.format)?;
+
+    let margin = margin.into();
+    let vrt = VrtSource::open(reference)?;
+    let (tiles, index) = select_tiles(input_dir, options)?;
+    let source = reference.display().to_string();
+    let mut reports = Vec::new();
+    for &factor in factors {
+        let level_dir = output_dir.join(level_dir_name(factor));
+        if factor == 1 {
+            let report = pad_tiles(
+                &tiles,
+                index.as_ref(),
+                &level_dir,
+                &vrt,
+                &source,
+                margin,
+                options,
+            )?;
+            reports.extend(report.tiles);
+            continue;
+        }
+
+        let mem_dir = format!(
+            "/vsimem/vrt_buffer_{}_level_{}",
+            std::process::id(),
+            LEVELS.fetch_add(1, Ordering::Relaxed)
+        );
+        let (mut level_tiles, mut originals) = (Vec::new(), HashMap::new());
+        for tile in &tiles {
+            let level_tile = match gpkg::tile_file_name(tile) {
+                Some(file_name) => Path::new(&mem_dir).join(file_name),
+                None => continue,
+            };
+            match write_level_tile(tile, &level_tile, factor, options) {
+                Ok(()) => {
+                    originals.insert(level_tile.clone(), tile.clone());
+                    level_tiles.push(level_tile);
+                }
+                Err(e) => message(&format!(
+                    "Could not decimate {:?} by {}: {}. Skipping...",
+                    tile, factor, e
+                )),
+            }
+        }
+
+        // the state file of the run can not track the in memory tiles, the listener is passed
+        // the tiles the levels were decimated from
+        let originals = Arc::new(originals);
+        let (previous, listener_originals) = (options.listener.clone(), originals.clone());
+        let level_options = options
+            .clone()
+            .output(options.output.clone().resume(false))
+            .listener(TileListener::events(move |event| {
+                let mut event = event.clone();
+                let input = match &mut event {
+                    TileEvent::TileStarted { input, .. } => input,
+                    TileEvent::TileFinished(report) | TileEvent::TileFailed(report) => {
+                        &mut report.input
+                    }
+                };
+                if let Some(original) = listener_originals.get(input) {
+                    *input = original.clone();
+                }
+                previous.send(&event);
+            }));
+        let report = pad_tiles(
+            &level_tiles,
+            None,
+            &level_dir,
+            &vrt,
+            &source,
+            margin,
+            &level_options,
+        );
+        for level_tile in &level_tiles {
+            if let Err(e) = unlink_mem_file(level_tile) {
+                message(&format!(
+                    "Warning: could not remove {:?}: {}",
+                    level_tile, e
+                ));
+            }
+        }
+        for mut tile in report?.tiles {
+            if let Some(original) = originals.get(&tile.input) {
+                tile.input = original.clone();
+            }
+            reports.push(tile);
+        }
+    }
+    Ok(BatchReport::new(reports, None))
+}
+
+/// writes a vrt of `tile` decimated by `factor` to `path`, with the color tables and attribute
+/// tables of the tile. the last row and column of the vrt are partly beyond the tile when its
+/// size is not a multiple of `factor`
+fn write_level_tile(
+    tile: &Path,
+    path: &Path,
+    factor: usize,
+    options: &PadOptions,
+) -> Result<(), Box<dyn Error>> {
+    let ds = options.gdal.open(tile)?;
+    let geotransform = match ds.geo_transform() {
+        Ok(geotransform) => geotransform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let mut level_geotransform = geotransform;
+    for i in [1, 2, 4, 5] {
+        level_geotransform[i] *= factor as f64;
+    }
+    let (cols, rows) = ds.raster_size();
+    let size = (cols.div_ceil(factor), rows.div_ceil(factor));
+
+    let source = match gpkg::split_table_path(tile) {
+        Some((file, table)) => gpkg::table_path(&absolute_path(file)?, table),
+        None => absolute_path(tile)?,
+    };
+    let mut bands = Vec::new();
+    for band_index in 1..=ds.raster_count() {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        bands.push(VirtualBand {
+            source: source.clone(),
+            source_band: band_index,
+            window: PixelWindow::new(0, 0, size.0 * factor, size.1 * factor),
+            data_type: data_type_name(&band),
+            nodata: band.no_data_value(),
+            resampling: band_resampling(&ds, band_index, options)?,
+        });
+    }
+    let xml = virtual_tile_xml(
+        size,
+        &level_geotransform,
+        &ds.projection(),
+        &bands,
+        (0, 0),
+        size,
+    )?;
+    if let Err(e) = create_mem_file(path, xml.into_bytes()) {
+        return Err(Box::new(e));
+    }
+    // the vrt driver writes the palettes into the vrt when the dataset is closed
+    let level_ds = open_raster_for_update(path)?;
+    let all_bands: Vec<isize> = (1..=ds.raster_count()).collect();
+    copy_palettes(&ds, &level_ds, &all_bands)
+}
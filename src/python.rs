@@ -0,0 +1,79 @@
+//! python bindings, enabled with the `python` feature and built with maturin.
+//!
+//! the functions return the batch report as a dict with the same layout as the `--json` output
+//! of the command line and raise `VrtBufferError` when the run could not start.
+use crate::{
+    crop_down_to_size_with_options, neighbor_buffer_with_options, vrt_buffer_with_options,
+    BatchReport, CropOptions, PadOptions,
+};
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use std::{error::Error, path::PathBuf};
+
+create_exception!(vrt_buffer, VrtBufferError, PyException);
+
+/// pads the geotiffs of `input_dir` into `output_dir`, reading the margin from `vrt` or, when it
+/// is None, from the neighboring geotiffs.
+#[pyfunction]
+#[pyo3(signature = (input_dir, output_dir, margin, vrt=None, jobs=1))]
+fn pad(
+    py: Python<'_>,
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    margin: usize,
+    vrt: Option<PathBuf>,
+    jobs: usize,
+) -> PyResult<PyObject> {
+    let options = PadOptions::default().jobs(jobs);
+    let report = py.allow_threads(|| {
+        into_message(match &vrt {
+            Some(vrt) => vrt_buffer_with_options(&input_dir, &output_dir, vrt, margin, &options),
+            None => neighbor_buffer_with_options(&input_dir, &output_dir, margin, &options),
+        })
+    });
+    report_to_dict(py, report)
+}
+
+/// crops the buffered geotiffs of `input_dir` to the extent of the originals in `original_dir`
+/// and saves them to `output_dir`.
+#[pyfunction]
+#[pyo3(signature = (original_dir, input_dir, output_dir, cleanup=false, jobs=1))]
+fn crop(
+    py: Python<'_>,
+    original_dir: PathBuf,
+    input_dir: PathBuf,
+    output_dir: PathBuf,
+    cleanup: bool,
+    jobs: usize,
+) -> PyResult<PyObject> {
+    let options = CropOptions::default().cleanup(cleanup).jobs(jobs);
+    let report = py.allow_threads(|| {
+        into_message(crop_down_to_size_with_options(
+            &original_dir,
+            &input_dir,
+            &output_dir,
+            &options,
+        ))
+    });
+    report_to_dict(py, report)
+}
+
+/// the boxed errors are not `Send`, only their message leaves the thread without the gil
+fn into_message(result: Result<BatchReport, Box<dyn Error>>) -> Result<BatchReport, String> {
+    result.map_err(|e| e.to_string())
+}
+
+fn report_to_dict(py: Python<'_>, report: Result<BatchReport, String>) -> PyResult<PyObject> {
+    let report = report.map_err(VrtBufferError::new_err)?;
+    let json =
+        serde_json::to_string(&report).map_err(|e| VrtBufferError::new_err(e.to_string()))?;
+    let dict = py.import("json")?.call_method1("loads", (json,))?;
+    Ok(dict.unbind())
+}
+
+#[pymodule]
+fn vrt_buffer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(pad, m)?)?;
+    m.add_function(wrap_pyfunction!(crop, m)?)?;
+    m.add("VrtBufferError", m.py().get_type::<VrtBufferError>())?;
+    Ok(())
+}
@@ -0,0 +1,323 @@
+//! the per tile outcome of a batch run.
+use crate::geometry::{GeoExtent, Margin, PixelWindow};
+use serde::Serialize;
+use std::{
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc},
+};
+
+/// what happens to a tile of a running batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TileEvent {
+    /// a worker thread started the tile
+    TileStarted { input: PathBuf, output: PathBuf },
+    /// the tile was written or skipped
+    TileFinished(TileReport),
+    /// the tile failed, the report holds the error
+    TileFailed(TileReport),
+}
+
+impl TileEvent {
+    /// the tile the event is about
+    pub fn input(&self) -> &Path {
+        match self {
+            TileEvent::TileStarted { input, .. } => input,
+            TileEvent::TileFinished(report) | TileEvent::TileFailed(report) => &report.input,
+        }
+    }
+}
+
+/// a function called with the report of every tile as soon as the tile is done, from the thread
+/// that processed it, so the tiles are reported in the order they finish. `TileListener::events`
+/// and `TileListener::channel` also see the tiles as they are started.
+#[derive(Clone, Default)]
+pub struct TileListener(Option<Arc<ListenerFn>>);
+
+type ListenerFn = dyn Fn(&TileEvent) + Send + Sync;
+
+impl TileListener {
+    /// a listener of the reports of the tiles that are done
+    pub fn new(listener: impl Fn(&TileReport) + Send + Sync + 'static) -> Self {
+        TileListener::events(move |event| match event {
+            TileEvent::TileStarted { .. } => {}
+            TileEvent::TileFinished(report) | TileEvent::TileFailed(report) => listener(report),
+        })
+    }
+
+    /// a listener of every event of the tiles
+    pub fn events(listener: impl Fn(&TileEvent) + Send + Sync + 'static) -> Self {
+        TileListener(Some(Arc::new(listener)))
+    }
+
+    /// a listener that sends every event of the tiles to `sender`, e.g. to a thread that
+    /// forwards them to a dashboard. events are dropped once the receiver is gone
+    pub fn channel(sender: Sender<TileEvent>) -> Self {
+        TileListener::events(move |event| {
+            let _ = sender.send(event.clone());
+        })
+    }
+
+    /// passes `event` to the listener
+    pub(crate) fn send(&self, event: &TileEvent) {
+        if let Some(listener) = &self.0 {
+            listener(event);
+        }
+    }
+
+    pub(crate) fn started(&self, input: &Path, output: &Path) {
+        self.send(&TileEvent::TileStarted {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+        });
+    }
+
+    pub(crate) fn notify(&self, report: &TileReport) {
+        if self.0.is_none() {
+            return;
+        }
+        self.send(&match report.status {
+            TileStatus::Failed(_) => TileEvent::TileFailed(report.clone()),
+            _ => TileEvent::TileFinished(report.clone()),
+        });
+    }
+}
+
+impl fmt::Debug for TileListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() {
+            "TileListener"
+        } else {
+            "TileListener(None)"
+        })
+    }
+}
+
+/// listeners are equal when they share the same function
+impl PartialEq for TileListener {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(listener), Some(other)) => Arc::ptr_eq(listener, other),
+            (listener, other) => listener.is_none() && other.is_none(),
+        }
+    }
+}
+
+/// the name of the error log in the output directory
+pub const ERROR_LOG_FILE_NAME: &str = "errors.jsonl";
+
+/// why a tile was not processed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// the tile does not overlap the reference vrt
+    OutsideVrt,
+    /// every pixel of the tile is nodata
+    Empty,
+    /// the output is up to date according to the state file of the output directory
+    Unchanged,
+    /// the batch was cancelled before the tile was started
+    Cancelled,
+    /// the batch reached its maximum number of tiles or its maximum duration before the tile
+    /// was started
+    Limit,
+    /// the batch stopped after failed tiles before the tile was started, see `ErrorPolicy`
+    Aborted,
+}
+
+/// what happened to a single tile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "detail", rename_all = "snake_case")]
+pub enum TileStatus {
+    /// the output was written
+    Written,
+    /// the tile was skipped, when passing through unpadded tiles the output is a plain copy
+    Skipped(SkipReason),
+    /// processing the tile failed with the given error message
+    Failed(String),
+}
+
+/// the pixels a crop trimmed off each side of a buffered raster: the margin it was buffered with,
+/// less where the margin stopped at the edge of the reference.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TrimmedMargin {
+    pub left: usize,
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+}
+
+/// the result of a tile that did not fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileOutcome {
+    pub status: TileStatus,
+    /// the pixel window that was read from the source raster
+    pub window: Option<PixelWindow>,
+    /// the decoded bytes read from the vrt
+    pub bytes_read: u64,
+    /// the margin stops at the edge of the vrt, see `MarginPolicy::Clamp`
+    pub clamped: bool,
+    /// the margin a crop trimmed off the buffered raster
+    pub trimmed: Option<TrimmedMargin>,
+}
+
+impl TileOutcome {
+    pub fn written(window: PixelWindow) -> Self {
+        TileOutcome {
+            status: TileStatus::Written,
+            window: Some(window),
+            bytes_read: 0,
+            clamped: false,
+            trimmed: None,
+        }
+    }
+
+    pub fn skipped(reason: SkipReason) -> Self {
+        TileOutcome {
+            status: TileStatus::Skipped(reason),
+            window: None,
+            bytes_read: 0,
+            clamped: false,
+            trimmed: None,
+        }
+    }
+}
+
+/// the outcome of a single tile.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TileReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    #[serde(flatten)]
+    pub status: TileStatus,
+    /// the pixel window that was read from the source raster
+    pub window: Option<PixelWindow>,
+    /// the wall time spent on the tile
+    pub seconds: f64,
+    /// the decoded bytes read from the vrt, the pixels of the tile itself are not counted
+    pub bytes_read: u64,
+    /// the size of the output file
+    pub bytes_written: u64,
+    /// the margin stops at the edge of the vrt, see `MarginPolicy::Clamp`
+    pub clamped: bool,
+    /// the margin a pad added, which differs between tiles with `PadOptions::margin_overrides`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin: Option<Margin>,
+    /// the margin a crop inferred from the buffered raster and trimmed off it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trimmed: Option<TrimmedMargin>,
+}
+
+/// the sums over every tile of a batch run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct BatchTotals {
+    /// the wall time of the tiles added up, more than the wall time of the run with several jobs
+    pub seconds: f64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// the outcome of every tile of a batch run, in processing order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BatchReport {
+    pub tiles: Vec<TileReport>,
+    pub totals: BatchTotals,
+    /// the vrt mosaic of the outputs, when one was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mosaic: Option<PathBuf>,
+}
+
+impl BatchReport {
+    /// a report of `tiles` with their totals
+    pub fn new(tiles: Vec<TileReport>, mosaic: Option<PathBuf>) -> Self {
+        let mut totals = BatchTotals::default();
+        for tile in &tiles {
+            totals.seconds += tile.seconds;
+            totals.bytes_read += tile.bytes_read;
+            totals.bytes_written += tile.bytes_written;
+        }
+        BatchReport {
+            tiles,
+            totals,
+            mosaic,
+        }
+    }
+
+    pub fn written(&self) -> usize {
+        self.count(|status| matches!(status, TileStatus::Written))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|status| matches!(status, TileStatus::Skipped(_)))
+    }
+
+    /// true when the batch was cancelled before every tile was processed
+    pub fn cancelled(&self) -> bool {
+        self.count(|status| *status == TileStatus::Skipped(SkipReason::Cancelled)) > 0
+    }
+
+    /// true when the batch stopped after failed tiles before every tile was processed
+    pub fn aborted(&self) -> bool {
+        self.count(|status| *status == TileStatus::Skipped(SkipReason::Aborted)) > 0
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|status| matches!(status, TileStatus::Failed(_)))
+    }
+
+    /// the tiles whose margin stops at the edge of the vrt
+    pub fn clamped(&self) -> Vec<&TileReport> {
+        self.tiles.iter().filter(|tile| tile.clamped).collect()
+    }
+
+    fn count(&self, f: impl Fn(&TileStatus) -> bool) -> usize {
+        self.tiles.iter().filter(|tile| f(&tile.status)).count()
+    }
+}
+
+/// a line of the error log.
+#[derive(Serialize)]
+struct TileError<'a> {
+    input: &'a Path,
+    output: &'a Path,
+    error: &'a str,
+    /// the extent the tile was padded or cropped to, as [min_x, min_y, max_x, max_y]
+    extent: Option<[f64; 4]>,
+}
+
+/// writes a json line per failed tile to the error log of `output_dir`, so the failures can be
+/// triaged and run again. the error log of an earlier run is removed when no tile failed.
+/// extent: the extent a tile was padded or cropped to, when it can be computed
+pub(crate) fn write_error_log(
+    output_dir: &Path,
+    reports: &[TileReport],
+    extent: impl Fn(&TileReport) -> Option<GeoExtent>,
+) -> Result<(), Box<dyn Error>> {
+    let path = output_dir.join(ERROR_LOG_FILE_NAME);
+    let mut lines = String::new();
+    for report in reports {
+        let error = match &report.status {
+            TileStatus::Failed(error) => error,
+            _ => continue,
+        };
+        let line = TileError {
+            input: &report.input,
+            output: &report.output,
+            error,
+            extent: extent(report)
+                .map(|extent| [extent.min_x, extent.min_y, extent.max_x, extent.max_y]),
+        };
+        lines.push_str(&serde_json::to_string(&line)?);
+        lines.push('\n');
+    }
+
+    if lines.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)?;
+        }
+        return Ok(());
+    }
+    fs::write(path, lines)?;
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! spreading the outputs over subdirectories so no directory holds every tile.
+use crate::{geometry::GeoExtent, gpkg::tile_file_name};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// the file stem, or the characters `start..end` of it
+    Stem(Option<(usize, usize)>),
+    /// the column of a grid with cells of this size that holds the center of the tile
+    X(f64),
+    /// the row of a grid with cells of this size that holds the center of the tile
+    Y(f64),
+}
+
+/// a template for the subdirectory of an output, relative to the output directory.
+///
+/// `{stem}` is the file stem of the input and `{stem:2-5}` the characters 2 to 5 of it, `{x:SIZE}`
+/// and `{y:SIZE}` are the column and row of a grid of SIZE map units that holds the center of the
+/// tile. e.g. `{x:100000}_{y:100000}` or `{stem:0-2}/{stem:2-5}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShardTemplate {
+    parts: Vec<Part>,
+}
+
+impl ShardTemplate {
+    /// the subdirectory of the output of `tile`
+    pub fn directory(&self, tile: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let file_name = tile_file_name(tile).unwrap_or_default();
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let mut extent = None;
+        let mut directory = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => directory.push_str(text),
+                Part::Stem(None) => directory.push_str(&stem),
+                Part::Stem(Some((start, end))) => {
+                    let chars: String = stem.chars().skip(*start).take(end - start).collect();
+                    if chars.chars().count() != end - start {
+                        return Err(format!(
+                            "the name of {:?} is too short for {{stem:{}-{}}}",
+                            tile, start, end
+                        )
+                        .into());
+                    }
+                    directory.push_str(&chars);
+                }
+                Part::X(size) | Part::Y(size) => {
+                    let extent: &GeoExtent = match &extent {
+                        Some(extent) => extent,
+                        None => extent.insert(GeoExtent::from_raster(tile)?),
+                    };
+                    let center = match part {
+                        Part::X(_) => (extent.min_x + extent.max_x) / 2.0,
+                        _ => (extent.min_y + extent.max_y) / 2.0,
+                    };
+                    directory.push_str(&(center / size).floor().to_string());
+                }
+            }
+        }
+        Ok(PathBuf::from(directory))
+    }
+}
+
+impl FromStr for ShardTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(format!("unclosed {{ in shard template {:?}", s)),
+            };
+            parts.push(parse_placeholder(&rest[start + 1..end])?);
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(ShardTemplate { parts })
+    }
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part, String> {
+    let invalid = || format!("invalid shard placeholder {{{}}}", placeholder);
+    match placeholder.split_once(':') {
+        None if placeholder == "stem" => Ok(Part::Stem(None)),
+        Some(("stem", range)) => {
+            let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+            let start = start.parse::<usize>().map_err(|_| invalid())?;
+            let end = end.parse::<usize>().map_err(|_| invalid())?;
+            if end <= start {
+                return Err(invalid());
+            }
+            Ok(Part::Stem(Some((start, end))))
+        }
+        Some((axis @ ("x" | "y"), size)) => match size.parse::<f64>() {
+            Ok(size) if size > 0.0 => Ok(if axis == "x" {
+                Part::X(size)
+            } else {
+                Part::Y(size)
+            }),
+            _ => Err(invalid()),
+        },
+        _ => Err(invalid()),
+    }
+}
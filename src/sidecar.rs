@@ -0,0 +1,141 @@
+//! copying of the sidecar files that travel next to a raster (`.prj`, `.aux.xml` and world files)
+//! and of the other files of an input directory.
+use crate::{
+    absolute_path, gpkg, path::open_dataset, provenance::provenance_path, stac, vsi,
+    ERROR_LOG_FILE_NAME, INDEX_FILE_NAME, MANIFEST_FILE_NAME, STATE_FILE_NAME,
+};
+use std::{
+    error::Error,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// sidecars named by replacing the extension of the raster, e.g. `tile.prj`
+const REPLACED_EXTENSIONS: [&str; 1] = ["prj"];
+
+/// sidecars named by appending to the file name of the raster, e.g. `tile.tif.aux.xml`
+const APPENDED_EXTENSIONS: [&str; 1] = ["aux.xml"];
+
+/// world files hold the geotransform, so they are rewritten for the output instead of copied
+const WORLD_FILE_EXTENSIONS: [&str; 3] = ["tfw", "tifw", "wld"];
+
+/// copies the sidecars of `source` next to `output`.
+/// world files are regenerated from the geotransform of `output`, which must already be written.
+pub fn copy_sidecars(source: &Path, output: &Path) -> Result<(), Box<dyn Error>> {
+    for extension in REPLACED_EXTENSIONS {
+        let sidecar = source.with_extension(extension);
+        if sidecar.is_file() {
+            fs::copy(&sidecar, output.with_extension(extension))?;
+        }
+    }
+
+    for extension in APPENDED_EXTENSIONS {
+        let sidecar = appended(source, extension);
+        if sidecar.is_file() {
+            fs::copy(&sidecar, appended(output, extension))?;
+        }
+    }
+
+    for extension in WORLD_FILE_EXTENSIONS {
+        if source.with_extension(extension).is_file() {
+            write_world_file(output, &output.with_extension(extension))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// copies the files of `input_dir` that are neither rasters nor written for them to `output_dir`,
+/// e.g. xml metadata, las indices and reports, so the output directory can replace the input
+/// directory. sidecars, stac items and provenance files of the rasters and the files the crate keeps for a run,
+/// such as the state file and the manifest, describe the inputs and are left out. subdirectories
+/// are not copied. returns the number of copied files
+pub(crate) fn copy_non_raster_files(
+    input_dir: &Path,
+    output_dir: &Path,
+) -> Result<usize, Box<dyn Error>> {
+    vsi::create_dir_all(output_dir)?;
+    // copying a directory onto itself would truncate the files
+    if absolute_path(input_dir)? == absolute_path(output_dir)? {
+        return Ok(0);
+    }
+    let is_raster = |path: &Path| {
+        gpkg::is_geopackage(path)
+            || path
+                .extension()
+                .is_some_and(|extension| extension == "tif" || extension == "tiff")
+    };
+    let mut files = vsi::read_dir(input_dir)?;
+    files.retain(|path| vsi::is_file(path));
+    let mut skipped: Vec<PathBuf> = [
+        STATE_FILE_NAME,
+        INDEX_FILE_NAME,
+        MANIFEST_FILE_NAME,
+        ERROR_LOG_FILE_NAME,
+        stac::CATALOG_FILE_NAME,
+    ]
+    .iter()
+    .map(|name| input_dir.join(name))
+    .collect();
+    for raster in files.iter().filter(|path| is_raster(path)) {
+        skipped.extend(sidecar_paths(raster));
+        skipped.push(stac::item_path(raster));
+        skipped.push(provenance_path(raster));
+    }
+
+    let mut copied = 0;
+    for file in files {
+        if is_raster(&file) || skipped.contains(&file) {
+            continue;
+        }
+        if let Some(file_name) = file.file_name() {
+            vsi::copy(&file, &output_dir.join(file_name))?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// the paths the sidecars of `raster` have, whether they exist or not
+pub(crate) fn sidecar_paths(raster: &Path) -> Vec<PathBuf> {
+    let replaced = REPLACED_EXTENSIONS.iter().chain(&WORLD_FILE_EXTENSIONS);
+    let mut paths: Vec<PathBuf> = replaced
+        .map(|extension| raster.with_extension(extension))
+        .collect();
+    paths.extend(
+        APPENDED_EXTENSIONS
+            .iter()
+            .map(|extension| appended(raster, extension)),
+    );
+    paths
+}
+
+pub(crate) fn appended(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// writes the world file of `raster`, the world file references the center of the upper left pixel
+fn write_world_file(raster: &Path, world_file: &Path) -> Result<(), Box<dyn Error>> {
+    let ds = open_dataset(raster, &[])?;
+    let gt = match ds.geo_transform() {
+        Ok(gt) => gt,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let contents = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n",
+        gt[1],
+        gt[4],
+        gt[2],
+        gt[5],
+        gt[0] + gt[1] / 2.0 + gt[2] / 2.0,
+        gt[3] + gt[4] / 2.0 + gt[5] / 2.0,
+    );
+    fs::write(world_file, contents)?;
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! thread safe access to the reference mosaic, a vrt or any other raster gdal can read.
+//!
+//! gdal datasets are neither `Send` nor `Sync`, so a single opened vrt can not be shared between
+//! threads. `VrtSource` only holds the path and lazily opens one dataset per thread, which is
+//! cached for the lifetime of that thread.
+//!
+//! the geotransform, size, bands, data type and nodata of the mosaic are read once when it is
+//! opened, so the padding of a tile does not ask gdal for them again, which is slow on network
+//! file systems. `ReferenceMosaic` is the same handle for callers that open the reference once
+//! and pass it to many calls.
+use crate::{
+    data_type::data_type_name, geometry::GeoTransform, manifest::sha256_file, path::open_dataset,
+};
+use gdal::{programs::raster::build_vrt, vsi::unlink_mem_file, Dataset};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// numbers the in memory vrts so every one gets its own entry in the dataset caches
+static IN_MEMORY_VRTS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static DATASETS: RefCell<HashMap<PathBuf, Dataset>> = RefCell::new(HashMap::new());
+}
+
+/// a handle to the reference mosaic that can be shared freely between threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VrtSource {
+    path: PathBuf,
+    /// the sha256 of a vrt file, recorded in the padded outputs
+    sha256: Option<String>,
+    geo_transform: GeoTransform,
+    size: (usize, usize),
+    /// the gdal name of the data type of the first band
+    dtype: String,
+    /// the nodata value of every band
+    nodata: Vec<Option<f64>>,
+}
+
+/// a reference mosaic opened once, e.g. with `ReferenceMosaic::open`, and passed to many calls
+/// such as `vrt_buffer_with_reference` or `pad_into_dataset`
+pub type ReferenceMosaic = VrtSource;
+
+impl VrtSource {
+    /// opens the mosaic once on the calling thread to validate it, reads its georeferencing and
+    /// bands and returns the handle. a mosaic without a geotransform fails.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let is_vrt_file = path.is_file()
+            && path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("vrt"));
+        let sha256 = match is_vrt_file {
+            true => Some(sha256_file(path)?),
+            false => None,
+        };
+        let mut source = VrtSource {
+            path: path.to_path_buf(),
+            sha256,
+            geo_transform: [0.0; 6],
+            size: (0, 0),
+            dtype: String::new(),
+            nodata: Vec::new(),
+        };
+        let (geo_transform, size, dtype, nodata) = source.with_dataset(|ds| {
+            let geo_transform = match ds.geo_transform() {
+                Ok(geo_transform) => geo_transform,
+                Err(e) => return Err(format!("{:?} has no geotransform: {}", path, e).into()),
+            };
+            let mut dtype = String::new();
+            let mut nodata = Vec::new();
+            for band_index in 1..=ds.raster_count() {
+                let band = match ds.rasterband(band_index) {
+                    Ok(band) => band,
+                    Err(e) => return Err(Box::new(e)),
+                };
+                if band_index == 1 {
+                    dtype = data_type_name(&band);
+                }
+                nodata.push(band.no_data_value());
+            }
+            Ok((geo_transform, ds.raster_size(), dtype, nodata))
+        })?;
+        source.geo_transform = geo_transform;
+        source.size = size;
+        source.dtype = dtype;
+        source.nodata = nodata;
+        Ok(source)
+    }
+
+    /// builds a vrt of `rasters` in gdal's in memory file system and returns the handle.
+    /// call `remove` once it is no longer used.
+    pub fn in_memory(rasters: &[PathBuf]) -> Result<Self, Box<dyn Error>> {
+        let id = IN_MEMORY_VRTS.fetch_add(1, Ordering::Relaxed);
+        let path = PathBuf::from(format!(
+            "/vsimem/vrt_buffer_{}_{}.vrt",
+            std::process::id(),
+            id
+        ));
+        write_vrt(rasters, &path)?;
+        VrtSource::open(&path)
+    }
+
+    /// deletes an in memory vrt created by `in_memory`.
+    pub fn remove(self) -> Result<(), Box<dyn Error>> {
+        DATASETS.with(|datasets| datasets.borrow_mut().remove(&self.path));
+        match unlink_mem_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// the path of the mosaic
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// the sha256 of the mosaic when it is a vrt file
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// the geotransform of the mosaic
+    pub fn geo_transform(&self) -> &GeoTransform {
+        &self.geo_transform
+    }
+
+    /// (cols, rows) of the mosaic
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// the number of bands
+    pub fn band_count(&self) -> usize {
+        self.nodata.len()
+    }
+
+    /// the gdal name of the data type of the first band, e.g. `Float32`
+    pub fn dtype(&self) -> &str {
+        &self.dtype
+    }
+
+    /// the nodata value of `band`, starting at 1, `None` when it has none or does not exist
+    pub fn nodata(&self, band: isize) -> Option<f64> {
+        let index = usize::try_from(band - 1).ok()?;
+        self.nodata.get(index).copied().flatten()
+    }
+
+    /// runs `f` with the dataset of the calling thread, opening it first if this thread has not
+    /// used the vrt yet.
+    pub fn with_dataset<R>(
+        &self,
+        f: impl FnOnce(&Dataset) -> Result<R, Box<dyn Error>>,
+    ) -> Result<R, Box<dyn Error>> {
+        DATASETS.with(|datasets| {
+            if !datasets.borrow().contains_key(&self.path) {
+                let ds = open_dataset(&self.path, &[])?;
+                datasets.borrow_mut().insert(self.path.clone(), ds);
+            }
+            let datasets = datasets.borrow();
+            f(&datasets[&self.path])
+        })
+    }
+}
+
+/// writes a vrt mosaic of `rasters` to `path`
+pub(crate) fn write_vrt(rasters: &[PathBuf], path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut datasets = Vec::new();
+    for raster in rasters {
+        datasets.push(open_dataset(raster, &[])?);
+    }
+
+    // the vrt is written to the file when the dataset is closed
+    match build_vrt(Some(path), &datasets, None) {
+        Ok(vrt_ds) => drop(vrt_ds),
+        Err(e) => return Err(Box::new(e)),
+    };
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! STAC items for the outputs and a catalog of them for the batch.
+//!
+//! the item of an output is written next to it as `<stem>.json` with the footprint in wgs84, the
+//! projection extension fields in the crs of the raster and the output as its data asset. the
+//! datetime of an item is the time the output was written, the crate does not know when the
+//! pixels were captured.
+use crate::{geometry::GeoExtent, report::TileReport, OutputFormat};
+use gdal::{
+    spatial_ref::{CoordTransform, SpatialRef},
+    Dataset,
+};
+use gdal_sys::OSRAxisMappingStrategy;
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const STAC_VERSION: &str = "1.0.0";
+const PROJECTION_EXTENSION: &str =
+    "https://stac-extensions.github.io/projection/v1.1.0/schema.json";
+pub(crate) const CATALOG_FILE_NAME: &str = "catalog.json";
+
+/// the path of the item of the output at `path`
+pub(crate) fn item_path(path: &Path) -> PathBuf {
+    path.with_extension("json")
+}
+
+/// the item of the output `ds` that is saved to `path`
+pub(crate) fn item(
+    ds: &Dataset,
+    path: &Path,
+    format: OutputFormat,
+) -> Result<Value, Box<dyn Error>> {
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let (cols, rows) = ds.raster_size();
+    let extent = GeoExtent::from_geotransform(&geo_transform, (cols, rows));
+    let projection = ds.projection();
+
+    let mut properties = json!({
+        "datetime": rfc3339(SystemTime::now())?,
+        "proj:shape": [rows, cols],
+        "proj:transform": [
+            geo_transform[1], geo_transform[2], geo_transform[0],
+            geo_transform[4], geo_transform[5], geo_transform[3],
+        ],
+        "proj:bbox": [extent.min_x, extent.min_y, extent.max_x, extent.max_y],
+    });
+    // rasters without a crs have no footprint on the globe
+    let (geometry, bbox) = if projection.is_empty() {
+        properties["proj:epsg"] = Value::Null;
+        (Value::Null, None)
+    } else {
+        let spatial_ref = match SpatialRef::from_wkt(&projection) {
+            Ok(spatial_ref) => spatial_ref,
+            Err(e) => return Err(Box::new(e)),
+        };
+        properties["proj:epsg"] = match spatial_ref.auth_name() {
+            Ok(name) if name == "EPSG" => spatial_ref.auth_code().map_or(Value::Null, Value::from),
+            _ => Value::Null,
+        };
+        properties["proj:wkt2"] = Value::from(projection.as_str());
+        let ring = wgs84_ring(&spatial_ref, &extent)?;
+        let bbox = ring.iter().fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |[min_x, min_y, max_x, max_y], &[x, y]| {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        );
+        (
+            json!({ "type": "Polygon", "coordinates": [ring] }),
+            Some(bbox),
+        )
+    };
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut item = json!({
+        "type": "Feature",
+        "stac_version": STAC_VERSION,
+        "stac_extensions": [PROJECTION_EXTENSION],
+        "id": path.file_stem().unwrap_or_default().to_string_lossy(),
+        "geometry": geometry,
+        "properties": properties,
+        "links": [],
+        "assets": {
+            "data": {
+                "href": format!("./{}", file_name),
+                "type": media_type(format),
+                "roles": ["data"],
+            }
+        },
+    });
+    if let Some(bbox) = bbox {
+        item["bbox"] = json!(bbox);
+    }
+    Ok(item)
+}
+
+/// writes a catalog to `output_dir` that links the items of every output of the run that has
+/// one, including outputs that were up to date
+pub(crate) fn write_catalog(
+    output_dir: &Path,
+    reports: &[TileReport],
+) -> Result<(), Box<dyn Error>> {
+    let mut links = vec![json!({
+        "rel": "root",
+        "href": format!("./{}", CATALOG_FILE_NAME),
+        "type": "application/json",
+    })];
+    for report in reports {
+        let item = item_path(&report.output);
+        if !item.is_file() {
+            continue;
+        }
+        let relative = item.strip_prefix(output_dir).unwrap_or(&item);
+        let href: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        links.push(json!({
+            "rel": "item",
+            "href": format!("./{}", href.join("/")),
+            "type": "application/geo+json",
+        }));
+    }
+
+    let id = output_dir
+        .file_name()
+        .map_or("vrt_buffer".into(), |name| name.to_string_lossy());
+    let catalog = json!({
+        "type": "Catalog",
+        "stac_version": STAC_VERSION,
+        "id": id,
+        "description": "rasters written by vrt_buffer",
+        "links": links,
+    });
+    fs::write(
+        output_dir.join(CATALOG_FILE_NAME),
+        serde_json::to_string_pretty(&catalog)?,
+    )?;
+    Ok(())
+}
+
+/// the corners of `extent` in wgs84 longitude and latitude
+fn wgs84_ring(
+    spatial_ref: &SpatialRef,
+    extent: &GeoExtent,
+) -> Result<Vec<[f64; 2]>, Box<dyn Error>> {
+    let wgs84 = match SpatialRef::from_epsg(4326) {
+        Ok(wgs84) => wgs84,
+        Err(e) => return Err(Box::new(e)),
+    };
+    // geojson is longitude first whatever the axis order of the crs
+    spatial_ref.set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+    wgs84.set_axis_mapping_strategy(OSRAxisMappingStrategy::OAMS_TRADITIONAL_GIS_ORDER);
+    let transform = match CoordTransform::new(spatial_ref, &wgs84) {
+        Ok(transform) => transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let ring = extent.ring();
+    let mut xs: Vec<f64> = ring.iter().map(|(x, _)| *x).collect();
+    let mut ys: Vec<f64> = ring.iter().map(|(_, y)| *y).collect();
+    let mut zs = vec![0.0; ring.len()];
+    if let Err(e) = transform.transform_coords(&mut xs, &mut ys, &mut zs) {
+        return Err(Box::new(e));
+    };
+    Ok(xs.into_iter().zip(ys).map(|(x, y)| [x, y]).collect())
+}
+
+fn media_type(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::GTiff => "image/tiff; application=geotiff",
+        OutputFormat::Cog => "image/tiff; application=geotiff; profile=cloud-optimized",
+        OutputFormat::Vrt | OutputFormat::VirtualVrt => "application/xml",
+        OutputFormat::Hfa => "application/x-erdas-hfa",
+        OutputFormat::Gpkg => "application/geopackage+sqlite3",
+        OutputFormat::Zarr => "application/vnd+zarr",
+    }
+}
+
+/// `time` as an rfc 3339 timestamp in utc, e.g. 2024-05-01T12:30:00Z
+pub(crate) fn rfc3339(time: SystemTime) -> Result<String, Box<dyn Error>> {
+    let seconds = time.duration_since(UNIX_EPOCH)?.as_secs();
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // the civil date of a day count since 1970-01-01, from howard hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    ))
+}
@@ -0,0 +1,168 @@
+//! the state file that lets a rerun skip the tiles that are already up to date.
+use crate::{gpkg, messages::message, TileReport, TileStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+/// the name of the state file in the output directory
+pub const STATE_FILE_NAME: &str = ".vrt_buffer_state.json";
+
+/// an input of a tile and its modification time when the tile was written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct InputState {
+    path: PathBuf,
+    modified_ns: u64,
+}
+
+/// what a completed tile was made from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TileState {
+    inputs: Vec<InputState>,
+    parameters: String,
+}
+
+/// the completed tiles of an output directory, keyed by output file name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct BatchState {
+    tiles: BTreeMap<String, TileState>,
+}
+
+impl BatchState {
+    /// reads the state file of `output_dir`, a missing file is an empty state.
+    pub(crate) fn load(output_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = output_dir.join(STATE_FILE_NAME);
+        if !path.exists() {
+            return Ok(BatchState::default());
+        }
+        let json = fs::read_to_string(&path)?;
+        match serde_json::from_str(&json) {
+            Ok(state) => Ok(state),
+            Err(e) => Err(format!("invalid state file {:?}: {}", path, e).into()),
+        }
+    }
+
+    /// writes the state file of `output_dir`, replacing the previous one in a single rename.
+    pub(crate) fn save(&self, output_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let path = output_dir.join(STATE_FILE_NAME);
+        let partial = crate::output::partial_path(&path);
+        fs::write(&partial, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    /// true when `output` exists and was written from the same inputs, unmodified since, with
+    /// the same parameters.
+    pub(crate) fn is_unchanged(&self, output: &Path, inputs: &[&Path], parameters: &str) -> bool {
+        let recorded = match self.tiles.get(&key(output)) {
+            Some(recorded) => recorded,
+            None => return false,
+        };
+        match tile_state(inputs, parameters) {
+            Ok(current) => output.exists() && *recorded == current,
+            Err(_) => false,
+        }
+    }
+
+    /// records that `output` was written from `inputs` with `parameters`.
+    pub(crate) fn record(
+        &mut self,
+        output: &Path,
+        inputs: &[&Path],
+        parameters: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.tiles
+            .insert(key(output), tile_state(inputs, parameters)?);
+        Ok(())
+    }
+}
+
+/// the state of a running batch, saved as every tile is written so an interrupted run resumes
+/// where it stopped.
+pub(crate) struct StateFile {
+    output_dir: Option<PathBuf>,
+    state: Mutex<BatchState>,
+}
+
+impl StateFile {
+    /// the state of `output_dir` when the batch resumes, otherwise an empty state that is never
+    /// saved
+    pub(crate) fn load(output_dir: Option<&Path>, resume: bool) -> Result<Self, Box<dyn Error>> {
+        let (output_dir, state) = match output_dir {
+            Some(output_dir) if resume => (Some(output_dir), BatchState::load(output_dir)?),
+            _ => (None, BatchState::default()),
+        };
+        Ok(StateFile {
+            output_dir: output_dir.map(Path::to_path_buf),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// see `BatchState::is_unchanged`
+    pub(crate) fn is_unchanged(&self, output: &Path, inputs: &[&Path], parameters: &str) -> bool {
+        match self.state.lock() {
+            Ok(state) => state.is_unchanged(output, inputs, parameters),
+            Err(_) => false,
+        }
+    }
+
+    /// records the written tile of `report` and saves the state file, the tile fails when the
+    /// state can not be saved
+    pub(crate) fn record(&self, report: &mut TileReport, inputs: &[&Path], parameters: &str) {
+        let output_dir = match &self.output_dir {
+            Some(output_dir) if report.status == TileStatus::Written => output_dir,
+            _ => return,
+        };
+        if let Err(e) = self.save_tile(output_dir, &report.output, inputs, parameters) {
+            message(&format!(
+                "Could not save the state of {:?}: {}",
+                report.output, e
+            ));
+            report.status = TileStatus::Failed(format!("could not save the state file: {}", e));
+        }
+    }
+
+    fn save_tile(
+        &self,
+        output_dir: &Path,
+        output: &Path,
+        inputs: &[&Path],
+        parameters: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return Err("a thread panicked while saving the state file".into()),
+        };
+        state.record(output, inputs, parameters)?;
+        state.save(output_dir)
+    }
+}
+
+fn key(output: &Path) -> String {
+    output
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn tile_state(inputs: &[&Path], parameters: &str) -> Result<TileState, Box<dyn Error>> {
+    let mut input_states = Vec::new();
+    for input in inputs {
+        // a table of a geopackage changes with its file
+        let modified = fs::metadata(gpkg::file_of(input))?.modified()?;
+        input_states.push(InputState {
+            path: input.to_path_buf(),
+            modified_ns: modified.duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+        });
+    }
+    Ok(TileState {
+        inputs: input_states,
+        parameters: parameters.to_string(),
+    })
+}
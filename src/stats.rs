@@ -0,0 +1,98 @@
+//! band statistics of the outputs.
+use crate::path::open_dataset;
+use gdal::{Dataset, Metadata};
+use std::{error::Error, path::Path, str::FromStr};
+
+/// how band statistics are computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatisticsMode {
+    /// allow gdal to compute the statistics from overviews or a subset of the blocks
+    Approximate,
+    /// read every pixel
+    Exact,
+}
+
+impl FromStr for StatisticsMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "approx" | "approximate" => Ok(StatisticsMode::Approximate),
+            "exact" => Ok(StatisticsMode::Exact),
+            _ => Err(format!(
+                "unknown statistics mode {:?}, expected approx or exact",
+                s
+            )),
+        }
+    }
+}
+
+/// the statistics of a single band.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandStatistics {
+    /// the 1 based band index
+    pub band: isize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+/// computes the statistics of every band of the raster at `path`.
+pub fn compute_stats(
+    path: &Path,
+    mode: StatisticsMode,
+) -> Result<Vec<BandStatistics>, Box<dyn Error>> {
+    let ds = open_dataset(path, &[])?;
+    dataset_statistics(&ds, mode)
+}
+
+/// computes the statistics of every band of `ds` and stores them as band metadata,
+/// the same `STATISTICS_*` items `gdalinfo -stats` writes.
+pub(crate) fn write_statistics(ds: &Dataset, mode: StatisticsMode) -> Result<(), Box<dyn Error>> {
+    for stats in dataset_statistics(ds, mode)? {
+        let mut band = match ds.rasterband(stats.band) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        for (key, value) in [
+            ("STATISTICS_MINIMUM", stats.min),
+            ("STATISTICS_MAXIMUM", stats.max),
+            ("STATISTICS_MEAN", stats.mean),
+            ("STATISTICS_STDDEV", stats.std_dev),
+        ] {
+            if let Err(e) = band.set_metadata_item(key, &value.to_string(), "") {
+                return Err(Box::new(e));
+            };
+        }
+    }
+
+    Ok(())
+}
+
+fn dataset_statistics(
+    ds: &Dataset,
+    mode: StatisticsMode,
+) -> Result<Vec<BandStatistics>, Box<dyn Error>> {
+    let mut all_stats = Vec::new();
+    for band_index in 1..=ds.raster_count() {
+        let band = match ds.rasterband(band_index) {
+            Ok(band) => band,
+            Err(e) => return Err(Box::new(e)),
+        };
+        let stats = match band.get_statistics(true, mode == StatisticsMode::Approximate) {
+            Ok(Some(stats)) => stats,
+            Ok(None) => return Err(format!("no statistics for band {}", band_index).into()),
+            Err(e) => return Err(Box::new(e)),
+        };
+        all_stats.push(BandStatistics {
+            band: band_index,
+            min: stats.min,
+            max: stats.max,
+            mean: stats.mean,
+            std_dev: stats.std_dev,
+        });
+    }
+
+    Ok(all_stats)
+}
@@ -0,0 +1,267 @@
+//! synthetic rasters for tests, enabled with the `test-utils` feature.
+//!
+//! `write_mosaic` writes a grid of tiles and a vrt of them whose pixel values are known from
+//! their position in the mosaic, so padded and cropped outputs can be checked pixel by pixel.
+//! `write_mosaic_f64` writes f64 values an f32 can not hold, to check that they are kept exactly.
+//! `write_mosaic_with_pixel_size` writes pixels that are not square.
+//! `write_mosaic_with_orientation` writes south up tiles or tiles whose columns run west.
+//! the mosaics can be written to a `/vsimem/` directory, which keeps a test off the disk.
+use crate::{
+    output::get_driver,
+    path::{create_dataset, open_dataset},
+    source::write_vrt,
+    vsi,
+};
+use gdal::raster::Buffer;
+use gdal_sys::GDALDataType;
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+/// the tiles and the vrt written by `write_mosaic`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// the directory holding only the tiles
+    pub tiles_dir: PathBuf,
+    /// the tiles in row major order
+    pub tiles: Vec<PathBuf>,
+    pub vrt: PathBuf,
+    /// the width and height of every tile in pixels
+    pub tile_size: usize,
+}
+
+/// the value of the pixel at column `col` and row `row` of the whole mosaic
+pub fn pixel_value(col: usize, row: usize) -> f32 {
+    (row * 10_000 + col) as f32
+}
+
+/// the value of the pixel at column `col` and row `row` of a mosaic of `write_mosaic_f64`, which
+/// an f32 can not hold
+pub fn precise_pixel_value(col: usize, row: usize) -> f64 {
+    f64::from(pixel_value(col, row)) + 1.0 / 3.0
+}
+
+/// writes `columns` by `rows` single band f32 tiles of `tile_size` pixels to `dir/tiles` and a
+/// vrt of them to `dir/mosaic.vrt`. the pixels are 1 map unit, the upper left corner of the
+/// mosaic is at (0, 0) and every pixel holds `pixel_value` of its position in the mosaic.
+pub fn write_mosaic(
+    dir: &Path,
+    columns: usize,
+    rows: usize,
+    tile_size: usize,
+) -> Result<Fixture, Box<dyn Error>> {
+    write_tiles(
+        dir,
+        (columns, rows),
+        tile_size,
+        (1.0, 1.0),
+        GDALDataType::GDT_Float32,
+        |col, row| f64::from(pixel_value(col, row)),
+    )
+}
+
+/// same as `write_mosaic` with pixels of `pixel_size` (width, height) map units, e.g. (0.5, 1.0)
+/// for pixels twice as tall as wide.
+pub fn write_mosaic_with_pixel_size(
+    dir: &Path,
+    columns: usize,
+    rows: usize,
+    tile_size: usize,
+    pixel_size: (f64, f64),
+) -> Result<Fixture, Box<dyn Error>> {
+    write_tiles(
+        dir,
+        (columns, rows),
+        tile_size,
+        pixel_size,
+        GDALDataType::GDT_Float32,
+        |col, row| f64::from(pixel_value(col, row)),
+    )
+}
+
+/// same as `write_mosaic` with f64 tiles whose pixels hold `precise_pixel_value`.
+pub fn write_mosaic_f64(
+    dir: &Path,
+    columns: usize,
+    rows: usize,
+    tile_size: usize,
+) -> Result<Fixture, Box<dyn Error>> {
+    write_tiles(
+        dir,
+        (columns, rows),
+        tile_size,
+        (1.0, 1.0),
+        GDALDataType::GDT_Float64,
+        precise_pixel_value,
+    )
+}
+
+/// same as `write_mosaic` with tiles whose columns run west when `flip.0` is set and whose rows
+/// run north, south up, when `flip.1` is set. the pixels at a position hold the same values as in
+/// `write_mosaic`. gdal only builds vrts of north up rasters, the vrt is of north up copies of the
+/// tiles in `dir/north_up/tiles`
+pub fn write_mosaic_with_orientation(
+    dir: &Path,
+    columns: usize,
+    rows: usize,
+    tile_size: usize,
+    flip: (bool, bool),
+) -> Result<Fixture, Box<dyn Error>> {
+    let north_up = write_mosaic(&dir.join("north_up"), columns, rows, tile_size)?;
+    let driver = get_driver("GTiff")?;
+    let tiles_dir = dir.join("tiles");
+    vsi::create_dir_all(&tiles_dir)?;
+
+    let mut tiles = Vec::new();
+    for north_up_tile in &north_up.tiles {
+        let band = read_band(north_up_tile)?;
+        let path = tiles_dir.join(north_up_tile.file_name().unwrap_or_default());
+        let size = (tile_size, tile_size);
+        let data_type = GDALDataType::GDT_Float32;
+        let mut ds = create_dataset(&driver, &path, size, 1, data_type, &[])?;
+        let mut geo_transform = [band.origin.0, 1.0, 0.0, band.origin.1, 0.0, -1.0];
+        if flip.0 {
+            geo_transform[0] += tile_size as f64;
+            geo_transform[1] = -1.0;
+        }
+        if flip.1 {
+            geo_transform[3] -= tile_size as f64;
+            geo_transform[5] = 1.0;
+        }
+        if let Err(e) = ds.set_geo_transform(&geo_transform) {
+            return Err(Box::new(e));
+        };
+
+        let mut data = Vec::with_capacity(band.data.len());
+        for row in 0..tile_size {
+            let row = if flip.1 { tile_size - 1 - row } else { row };
+            let pixels = &band.data[row * tile_size..(row + 1) * tile_size];
+            if flip.0 {
+                data.extend(pixels.iter().rev());
+            } else {
+                data.extend_from_slice(pixels);
+            }
+        }
+        let mut flipped = match ds.rasterband(1) {
+            Ok(flipped) => flipped,
+            Err(e) => return Err(Box::new(e)),
+        };
+        if let Err(e) = flipped.write((0, 0), size, &Buffer { size, data }) {
+            return Err(Box::new(e));
+        };
+        tiles.push(path);
+    }
+
+    Ok(Fixture {
+        tiles_dir,
+        tiles,
+        vrt: north_up.vrt,
+        tile_size,
+    })
+}
+
+fn write_tiles(
+    dir: &Path,
+    (columns, rows): (usize, usize),
+    tile_size: usize,
+    (pixel_width, pixel_height): (f64, f64),
+    data_type: GDALDataType::Type,
+    value: impl Fn(usize, usize) -> f64,
+) -> Result<Fixture, Box<dyn Error>> {
+    let driver = get_driver("GTiff")?;
+    let tiles_dir = dir.join("tiles");
+    vsi::create_dir_all(&tiles_dir)?;
+
+    let mut tiles = Vec::new();
+    for tile_row in 0..rows {
+        for tile_col in 0..columns {
+            let path = tiles_dir.join(format!("tile_{}_{}.tif", tile_row, tile_col));
+            let mut ds = create_dataset(&driver, &path, (tile_size, tile_size), 1, data_type, &[])?;
+            let x_origin = (tile_col * tile_size) as f64 * pixel_width;
+            let y_origin = -((tile_row * tile_size) as f64 * pixel_height);
+            let geo_transform = [x_origin, pixel_width, 0.0, y_origin, 0.0, -pixel_height];
+            if let Err(e) = ds.set_geo_transform(&geo_transform) {
+                return Err(Box::new(e));
+            };
+
+            let mut data = Vec::with_capacity(tile_size * tile_size);
+            for row in 0..tile_size {
+                for col in 0..tile_size {
+                    data.push(value(
+                        tile_col * tile_size + col,
+                        tile_row * tile_size + row,
+                    ));
+                }
+            }
+            let buffer = Buffer {
+                size: (tile_size, tile_size),
+                data,
+            };
+            let mut band = match ds.rasterband(1) {
+                Ok(band) => band,
+                Err(e) => return Err(Box::new(e)),
+            };
+            if let Err(e) = band.write((0, 0), (tile_size, tile_size), &buffer) {
+                return Err(Box::new(e));
+            };
+            tiles.push(path);
+        }
+    }
+
+    let vrt = dir.join("mosaic.vrt");
+    write_vrt(&tiles, &vrt)?;
+    Ok(Fixture {
+        tiles_dir,
+        tiles,
+        vrt,
+        tile_size,
+    })
+}
+
+/// the first band of a raster read by `read_band`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Band {
+    /// (cols, rows)
+    pub size: (usize, usize),
+    /// the upper left corner in map units
+    pub origin: (f64, f64),
+    /// the pixels in row major order
+    pub data: Vec<f32>,
+}
+
+/// reads the first band of a raster as f32
+pub fn read_band(path: &Path) -> Result<Band, Box<dyn Error>> {
+    let ds = open_dataset(path, &[])?;
+    let geo_transform = match ds.geo_transform() {
+        Ok(geo_transform) => geo_transform,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let band = match ds.rasterband(1) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let size = ds.raster_size();
+    match band.read_as::<f32>((0, 0), size, size, None) {
+        Ok(buffer) => Ok(Band {
+            size,
+            origin: (geo_transform[0], geo_transform[3]),
+            data: buffer.data,
+        }),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// reads the pixels of the first band of a raster as f64, in row major order
+pub fn read_band_f64(path: &Path) -> Result<Vec<f64>, Box<dyn Error>> {
+    let ds = open_dataset(path, &[])?;
+    let band = match ds.rasterband(1) {
+        Ok(band) => band,
+        Err(e) => return Err(Box::new(e)),
+    };
+    let size = ds.raster_size();
+    match band.read_as::<f64>((0, 0), size, size, None) {
+        Ok(buffer) => Ok(buffer.data),
+        Err(e) => Err(Box::new(e)),
+    }
+}
@@ -0,0 +1,48 @@
+//! limiting how many threads read from the vrts at the same time.
+use std::sync::{Condvar, Mutex};
+
+/// a counting semaphore around the vrt reads of a batch.
+pub(crate) struct IoLimit {
+    limit: Option<usize>,
+    in_use: Mutex<usize>,
+    released: Condvar,
+}
+
+impl IoLimit {
+    /// at most `limit` reads at the same time, any number when `None`
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        IoLimit {
+            limit,
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// runs `f` once fewer than `limit` other threads are reading
+    pub(crate) fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let limit = match self.limit {
+            Some(limit) => limit.max(1),
+            None => return f(),
+        };
+        {
+            let mut in_use = self.in_use.lock().unwrap();
+            while *in_use >= limit {
+                in_use = self.released.wait(in_use).unwrap();
+            }
+            *in_use += 1;
+        }
+        // released on drop so a panicking read does not keep its slot
+        let _permit = Permit(self);
+        f()
+    }
+}
+
+struct Permit<'a>(&'a IoLimit);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut in_use = self.0.in_use.lock().unwrap();
+        *in_use -= 1;
+        self.0.released.notify_one();
+    }
+}
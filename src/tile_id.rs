@@ -0,0 +1,85 @@
+//! naming tiles by an id taken from their file names, e.g. the grid cell of `S2_T32UNE_2023.tif`,
+//! so tiles that were renamed by a processing step are still recognized as the same tile.
+use crate::gpkg::tile_file_name;
+use regex::Regex;
+use std::{fmt, path::Path, str::FromStr};
+
+/// how the id of a tile is extracted from its file stem.
+///
+/// a template such as `S2_{id}_*` matches the whole stem, `{id}` is the id and `*` any
+/// characters. anything else is a regular expression that is searched for in the stem, the id is
+/// its group named `id`, its first group or the whole match, e.g. `T\d{2}[A-Z]{3}`.
+/// tiles whose stem does not match are identified by their stem.
+#[derive(Clone)]
+pub struct TileIdPattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl TileIdPattern {
+    /// the id of `tile`, its file stem when the pattern does not match
+    pub fn tile_id(&self, tile: &Path) -> String {
+        let file_name = tile_file_name(tile).unwrap_or_default();
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let captures = match self.regex.captures(&stem) {
+            Some(captures) => captures,
+            None => return stem,
+        };
+        let id = captures
+            .name("id")
+            .or_else(|| captures.get(1))
+            .or_else(|| captures.get(0));
+        match id {
+            Some(id) if !id.as_str().is_empty() => id.as_str().to_string(),
+            _ => stem,
+        }
+    }
+}
+
+impl FromStr for TileIdPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = if s.contains("{id}") {
+            let parts: Vec<String> = s
+                .split("{id}")
+                .map(|part| {
+                    let literals: Vec<String> = part.split('*').map(regex::escape).collect();
+                    literals.join(".*")
+                })
+                .collect();
+            if parts.len() != 2 {
+                return Err(format!(
+                    "the tile id template {:?} must hold {{id}} once",
+                    s
+                ));
+            }
+            format!("^{}(?P<id>.+?){}$", parts[0], parts[1])
+        } else {
+            s.to_string()
+        };
+        match Regex::new(&regex) {
+            Ok(regex) => Ok(TileIdPattern {
+                pattern: s.to_string(),
+                regex,
+            }),
+            Err(e) => Err(format!("invalid tile id pattern {:?}: {}", s, e)),
+        }
+    }
+}
+
+impl fmt::Debug for TileIdPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TileIdPattern({:?})", self.pattern)
+    }
+}
+
+impl PartialEq for TileIdPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
@@ -0,0 +1,153 @@
+//! padded tiles written as small vrts that crop the reference mosaic to the padded window.
+//!
+//! no pixels are copied, which turns a pad run into seconds for steps that only read the padded
+//! tiles. the vrts point to the mosaic by its absolute path and break when it moves.
+//!
+//! the stubs of a metadata only run have the size, georeferencing and bands of the padded tiles
+//! but no sources, they read as nodata and only show where the outputs of a real run would be.
+use crate::{
+    geometry::{GeoTransform, PixelWindow},
+    output::partial_path,
+    Resampling,
+};
+use std::{
+    error::Error,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// where a band of a virtual tile reads its pixels from.
+pub(crate) struct VirtualBand {
+    /// the absolute path of the mosaic
+    pub(crate) source: PathBuf,
+    pub(crate) source_band: isize,
+    /// the window of the mosaic
+    pub(crate) window: PixelWindow,
+    /// the gdal name of the data type of the band, e.g. `Float32`
+    pub(crate) data_type: String,
+    pub(crate) nodata: Option<f64>,
+    pub(crate) resampling: Resampling,
+}
+
+/// the vrt of a tile of `size`. the windows of the bands are scaled into the part of the tile
+/// that starts at `offset` and has `window_size`, the rest of the tile is nodata
+pub(crate) fn virtual_tile_xml(
+    size: (usize, usize),
+    geo_transform: &GeoTransform,
+    projection: &str,
+    bands: &[VirtualBand],
+    offset: (usize, usize),
+    window_size: (usize, usize),
+) -> Result<String, Box<dyn Error>> {
+    tile_xml(
+        size,
+        geo_transform,
+        projection,
+        bands,
+        Some((offset, window_size)),
+    )
+}
+
+/// the vrt of a tile of `size` with the bands but none of the sources of `bands`
+pub(crate) fn stub_tile_xml(
+    size: (usize, usize),
+    geo_transform: &GeoTransform,
+    projection: &str,
+    bands: &[VirtualBand],
+) -> Result<String, Box<dyn Error>> {
+    tile_xml(size, geo_transform, projection, bands, None)
+}
+
+/// the vrt of a tile, the bands read their windows into the part of the tile given by `window`,
+/// (offset, window size), and have no source without it
+fn tile_xml(
+    size: (usize, usize),
+    geo_transform: &GeoTransform,
+    projection: &str,
+    bands: &[VirtualBand],
+    window: Option<((usize, usize), (usize, usize))>,
+) -> Result<String, Box<dyn Error>> {
+    let mut xml = String::new();
+    writeln!(
+        xml,
+        r#"<VRTDataset rasterXSize="{}" rasterYSize="{}">"#,
+        size.0, size.1
+    )?;
+    if !projection.is_empty() {
+        writeln!(xml, "  <SRS>{}</SRS>", escape(projection))?;
+    }
+    let geo_transform: Vec<String> = geo_transform
+        .iter()
+        .map(|value| value.to_string())
+        .collect();
+    writeln!(
+        xml,
+        "  <GeoTransform>{}</GeoTransform>",
+        geo_transform.join(", ")
+    )?;
+    for (band_index, band) in (1..).zip(bands) {
+        writeln!(
+            xml,
+            r#"  <VRTRasterBand dataType="{}" band="{}">"#,
+            band.data_type, band_index
+        )?;
+        if let Some(nodata) = band.nodata {
+            writeln!(xml, "    <NoDataValue>{}</NoDataValue>", nodata)?;
+        }
+        let Some((offset, window_size)) = window else {
+            writeln!(xml, "  </VRTRasterBand>")?;
+            continue;
+        };
+        writeln!(
+            xml,
+            r#"    <SimpleSource resampling="{}">"#,
+            resampling_name(band.resampling)
+        )?;
+        writeln!(
+            xml,
+            r#"      <SourceFilename relativeToVRT="0">{}</SourceFilename>"#,
+            escape(&band.source.to_string_lossy())
+        )?;
+        writeln!(xml, "      <SourceBand>{}</SourceBand>", band.source_band)?;
+        writeln!(
+            xml,
+            r#"      <SrcRect xOff="{}" yOff="{}" xSize="{}" ySize="{}"/>"#,
+            band.window.x_offset, band.window.y_offset, band.window.cols, band.window.rows
+        )?;
+        writeln!(
+            xml,
+            r#"      <DstRect xOff="{}" yOff="{}" xSize="{}" ySize="{}"/>"#,
+            offset.0, offset.1, window_size.0, window_size.1
+        )?;
+        writeln!(xml, "    </SimpleSource>")?;
+        writeln!(xml, "  </VRTRasterBand>")?;
+    }
+    writeln!(xml, "</VRTDataset>")?;
+    Ok(xml)
+}
+
+/// writes `xml` to `path` through its partial file
+pub(crate) fn write_virtual_tile(path: &Path, xml: &str) -> Result<(), Box<dyn Error>> {
+    let partial = partial_path(path);
+    fs::write(&partial, xml)?;
+    fs::rename(partial, path)?;
+    Ok(())
+}
+
+/// the name of the resampling in a vrt source
+fn resampling_name(resampling: Resampling) -> &'static str {
+    match resampling {
+        Resampling::Nearest => "nearest",
+        Resampling::Bilinear => "bilinear",
+        Resampling::Cubic => "cubic",
+        Resampling::Average => "average",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
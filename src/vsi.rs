@@ -0,0 +1,167 @@
+//! the file system calls of a run that also work on gdal's virtual file systems.
+//!
+//! paths starting with `/vsi`, e.g. `/vsimem/tiles`, are handed to gdal's vsi functions and all
+//! other paths to `std::fs`, so the tiles of a pad or crop can live in memory and tests and
+//! embedded users can run a round trip without touching the disk. the files the crate keeps for
+//! a whole run, such as the state file or the manifest, are only written to directories on disk.
+use crate::path::{gdal_path, last_error};
+use gdal_sys::{
+    CPLCopyFile, CSLCount, CSLDestroy, VSIMkdirRecursive, VSIReadDir, VSIRename, VSIRmdirRecursive,
+    VSIStatBufL, VSIStatExL, VSIUnlink, VSI_STAT_EXISTS_FLAG, VSI_STAT_NATURE_FLAG,
+    VSI_STAT_SIZE_FLAG,
+};
+use std::{
+    error::Error,
+    ffi::{c_int, CStr},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// the type bits of `st_mode` and the bits of a directory
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+
+/// true when `path` is on one of gdal's virtual file systems
+pub(crate) fn is_virtual(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("/vsi"))
+}
+
+/// the entries of `dir`, unsorted
+pub(crate) fn read_dir(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !is_virtual(dir) {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            paths.push(entry?.path());
+        }
+        return Ok(paths);
+    }
+    let c_dir = gdal_path(dir)?;
+    let list = unsafe { VSIReadDir(c_dir.as_ptr()) };
+    // an empty directory has no list either, only a missing one fails
+    if list.is_null() {
+        return match stat(dir) {
+            Some(_) => Ok(Vec::new()),
+            None => Err(format!("{:?} does not exist", dir).into()),
+        };
+    }
+    let count = unsafe { CSLCount(list) } as usize;
+    let mut paths = Vec::with_capacity(count);
+    for i in 0..count {
+        let name = unsafe { CStr::from_ptr(*list.add(i)) }.to_string_lossy();
+        if name != "." && name != ".." {
+            paths.push(dir.join(name.as_ref()));
+        }
+    }
+    unsafe { CSLDestroy(list) };
+    Ok(paths)
+}
+
+pub(crate) fn create_dir_all(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !is_virtual(dir) {
+        return Ok(fs::create_dir_all(dir)?);
+    }
+    if is_dir(dir) {
+        return Ok(());
+    }
+    let c_dir = gdal_path(dir)?;
+    if unsafe { VSIMkdirRecursive(c_dir.as_ptr(), 0o755) } != 0 {
+        return Err(last_error(&format!("could not create {:?}", dir)));
+    }
+    Ok(())
+}
+
+pub(crate) fn rename(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if !is_virtual(from) && !is_virtual(to) {
+        return Ok(fs::rename(from, to)?);
+    }
+    let (c_from, c_to) = (gdal_path(from)?, gdal_path(to)?);
+    if unsafe { VSIRename(c_from.as_ptr(), c_to.as_ptr()) } != 0 {
+        return Err(last_error(&format!(
+            "could not rename {:?} to {:?}",
+            from, to
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn copy(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if !is_virtual(from) && !is_virtual(to) {
+        fs::copy(from, to)?;
+        return Ok(());
+    }
+    let (c_from, c_to) = (gdal_path(from)?, gdal_path(to)?);
+    if unsafe { CPLCopyFile(c_to.as_ptr(), c_from.as_ptr()) } != 0 {
+        return Err(last_error(&format!(
+            "could not copy {:?} to {:?}",
+            from, to
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_file(path: &Path) -> Result<(), Box<dyn Error>> {
+    if !is_virtual(path) {
+        return Ok(fs::remove_file(path)?);
+    }
+    let c_path = gdal_path(path)?;
+    if unsafe { VSIUnlink(c_path.as_ptr()) } != 0 {
+        return Err(last_error(&format!("could not remove {:?}", path)));
+    }
+    Ok(())
+}
+
+pub(crate) fn remove_dir_all(dir: &Path) -> Result<(), Box<dyn Error>> {
+    if !is_virtual(dir) {
+        return Ok(fs::remove_dir_all(dir)?);
+    }
+    let c_dir = gdal_path(dir)?;
+    if unsafe { VSIRmdirRecursive(c_dir.as_ptr()) } != 0 {
+        return Err(last_error(&format!("could not remove {:?}", dir)));
+    }
+    Ok(())
+}
+
+pub(crate) fn exists(path: &Path) -> bool {
+    match is_virtual(path) {
+        true => stat(path).is_some(),
+        false => path.exists(),
+    }
+}
+
+pub(crate) fn is_file(path: &Path) -> bool {
+    match is_virtual(path) {
+        true => stat(path).is_some_and(|stat| !is_dir_mode(&stat)),
+        false => path.is_file(),
+    }
+}
+
+pub(crate) fn is_dir(path: &Path) -> bool {
+    match is_virtual(path) {
+        true => stat(path).is_some_and(|stat| is_dir_mode(&stat)),
+        false => path.is_dir(),
+    }
+}
+
+/// the size of the file at `path` in bytes, 0 when it does not exist
+pub(crate) fn file_size(path: &Path) -> u64 {
+    match is_virtual(path) {
+        true => stat(path).map_or(0, |stat| stat.st_size as u64),
+        false => fs::metadata(path).map_or(0, |metadata| metadata.len()),
+    }
+}
+
+// st_mode is a u16 on some platforms
+#[allow(clippy::unnecessary_cast)]
+fn is_dir_mode(stat: &VSIStatBufL) -> bool {
+    stat.st_mode as u32 & S_IFMT == S_IFDIR
+}
+
+fn stat(path: &Path) -> Option<VSIStatBufL> {
+    let c_path = gdal_path(path).ok()?;
+    let mut stat: VSIStatBufL = unsafe { std::mem::zeroed() };
+    let flags = VSI_STAT_EXISTS_FLAG | VSI_STAT_NATURE_FLAG | VSI_STAT_SIZE_FLAG;
+    match unsafe { VSIStatExL(c_path.as_ptr(), &mut stat, flags as c_int) } {
+        0 => Some(stat),
+        _ => None,
+    }
+}
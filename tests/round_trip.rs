@@ -0,0 +1,1313 @@
+//! pads a synthetic mosaic, crops it back and compares the pixels with the known values.
+use gdal::{raster::RasterCreationOption, Dataset, DriverManager};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+use vrt_buffer::{
+    crop_down_to_size, crop_down_to_size_with_options, crop_fixed, crop_fixed_with_options,
+    crop_pairs, crop_to_extent, describe, diff_dirs, mosaic_tiles, neighbor_buffer_with_options,
+    pad_pairs, read_margin_overrides, read_vrt_window,
+    test_utils::{
+        pixel_value, precise_pixel_value, read_band, read_band_f64, write_mosaic, write_mosaic_f64,
+        write_mosaic_with_orientation, write_mosaic_with_pixel_size,
+    },
+    verify_manifest, vrt_buffer, vrt_buffer_grid, vrt_buffer_pyramid, vrt_buffer_with_options,
+    vrt_buffer_with_reference, BandPolicy, CropOptions, DuplicateNames, EdgeFill, ErrorPolicy,
+    FillStrategy, GeoExtent, InvalidMargin, Margin, MarginPolicy, MosaicOptions, OutputFormat,
+    OutputOptions, PadOptions, ReferenceMosaic, TileEvent, TileIdPattern, TileListener, TileStatus,
+    TimedOut, ERROR_LOG_FILE_NAME, INDEX_FILE_NAME,
+};
+
+const TILE_SIZE: usize = 16;
+const MARGIN: usize = 4;
+
+#[test]
+fn pad_reads_the_margin_from_the_neighbors() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let report = vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    assert_eq!(report.failed(), 0);
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    assert_eq!(band.size, (padded_size, padded_size));
+    let first = TILE_SIZE - MARGIN;
+    assert_eq!(band.origin, (first as f64, -(first as f64)));
+    for row in 0..padded_size {
+        for col in 0..padded_size {
+            assert_eq!(
+                band.data[row * padded_size + col],
+                pixel_value(first + col, first + row),
+                "pixel ({}, {})",
+                col,
+                row
+            );
+        }
+    }
+}
+
+#[test]
+fn rectangular_pixels_pad_and_crop_per_axis() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic_with_pixel_size(dir.path(), 3, 3, TILE_SIZE, (0.5, 1.0)).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let margin = Margin::PerAxis {
+        x: MARGIN,
+        y: MARGIN / 2,
+    };
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        margin,
+        &PadOptions::default(),
+    )
+    .unwrap();
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let size = (TILE_SIZE + 2 * MARGIN, TILE_SIZE + MARGIN);
+    assert_eq!(band.size, size);
+    let (first_col, first_row) = (TILE_SIZE - MARGIN, TILE_SIZE - MARGIN / 2);
+    assert_eq!(band.origin, (first_col as f64 * 0.5, -(first_row as f64)));
+    for row in 0..size.1 {
+        for col in 0..size.0 {
+            assert_eq!(
+                band.data[row * size.0 + col],
+                pixel_value(first_col + col, first_row + row),
+                "pixel ({}, {})",
+                col,
+                row
+            );
+        }
+    }
+
+    let cropped_dir = dir.path().join("cropped");
+    crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+    let fixed_dir = dir.path().join("fixed");
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    let filled_dir = dir.path().join("filled");
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &filled_dir,
+        &fixture.vrt,
+        margin,
+        &options,
+    )
+    .unwrap();
+    crop_fixed_with_options(&filled_dir, &fixed_dir, margin, &CropOptions::default()).unwrap();
+    for tile in &fixture.tiles {
+        let original = read_band(tile).unwrap();
+        assert_eq!(
+            read_band(&cropped_dir.join(tile.file_name().unwrap())).unwrap(),
+            original
+        );
+        assert_eq!(
+            read_band(&fixed_dir.join(tile.file_name().unwrap())).unwrap(),
+            original
+        );
+    }
+}
+
+#[test]
+fn flipped_tiles_are_padded_north_up_and_cropped_back() {
+    for flip in [(false, false), (true, false), (false, true), (true, true)] {
+        let dir = tempfile::tempdir().unwrap();
+        let fixture = write_mosaic_with_orientation(dir.path(), 3, 3, TILE_SIZE, flip).unwrap();
+        let padded_dir = dir.path().join("padded");
+        let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+        let report = vrt_buffer_with_options(
+            &fixture.tiles_dir,
+            &padded_dir,
+            &fixture.vrt,
+            MARGIN,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.failed(), 0, "{:?}", flip);
+
+        // the padded tiles are north up
+        let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+        let padded_size = TILE_SIZE + 2 * MARGIN;
+        let first = TILE_SIZE - MARGIN;
+        for row in 0..padded_size {
+            for col in 0..padded_size {
+                assert_eq!(
+                    band.data[row * padded_size + col],
+                    pixel_value(first + col, first + row),
+                    "{:?} pixel ({}, {})",
+                    flip,
+                    col,
+                    row
+                );
+            }
+        }
+
+        // cropped to the extents of the flipped tiles they are the north up tiles
+        let cropped_dir = dir.path().join("cropped");
+        crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+        for tile in &fixture.tiles {
+            let name = tile.file_name().unwrap();
+            let north_up = dir.path().join("north_up/tiles").join(name);
+            assert_eq!(
+                read_band(&cropped_dir.join(name)).unwrap(),
+                read_band(&north_up).unwrap()
+            );
+        }
+
+        // a crop keeps the orientation of the raster it crops
+        let cropped = dir.path().join("inner.tif");
+        let (start, end) = ((TILE_SIZE + MARGIN) as f64, (2 * TILE_SIZE - MARGIN) as f64);
+        let extent = GeoExtent::new(start, -end, end, -start);
+        crop_to_extent(&fixture.tiles[4], &cropped, extent).unwrap();
+        let band = read_band(&cropped).unwrap();
+        let (near, far) = (TILE_SIZE + MARGIN, 2 * TILE_SIZE - MARGIN - 1);
+        let col = if flip.0 { far } else { near };
+        let row = if flip.1 { far } else { near };
+        assert_eq!(band.data[0], pixel_value(col, row), "{:?}", flip);
+    }
+}
+
+#[test]
+fn pad_fills_beyond_the_mosaic_edge() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().edge_fill(EdgeFill::Values(vec![-1.0]));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let band = read_band(&padded_dir.join("tile_0_0.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    assert_eq!(band.size, (padded_size, padded_size));
+    for row in 0..padded_size {
+        for col in 0..padded_size {
+            let expected = if col < MARGIN || row < MARGIN {
+                -1.0
+            } else {
+                pixel_value(col - MARGIN, row - MARGIN)
+            };
+            assert_eq!(band.data[row * padded_size + col], expected);
+        }
+    }
+}
+
+#[test]
+fn band_policies_set_the_nodata_value_and_fill_of_a_band() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let policy = BandPolicy {
+        nodata: Some(-9999.0),
+        fill: FillStrategy::Value(255.0),
+    };
+    let options = PadOptions::default()
+        .edge_fill(EdgeFill::Values(vec![-1.0]))
+        .output(OutputOptions::default().band_policy(1, policy));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let output = padded_dir.join("tile_0_0.tif");
+    let band = read_band(&output).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    assert_eq!(band.data[0], 255.0);
+    assert_eq!(band.data[MARGIN * padded_size + MARGIN], pixel_value(0, 0));
+    assert_eq!(describe(&output).unwrap().nodata, Some(-9999.0));
+    assert_eq!(
+        "nodata=0,fill=nodata".parse::<BandPolicy>().unwrap(),
+        BandPolicy {
+            nodata: Some(0.0),
+            fill: FillStrategy::Nodata,
+        }
+    );
+}
+
+#[test]
+fn pad_fills_the_holes_of_the_reference_from_the_fallback() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    // the center tile is its own reference, so its whole margin is a hole
+    let tiles_dir = dir.path().join("center");
+    std::fs::create_dir_all(&tiles_dir).unwrap();
+    let tile = tiles_dir.join("tile_1_1.tif");
+    std::fs::copy(fixture.tiles_dir.join("tile_1_1.tif"), &tile).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default()
+        .edge_fill(EdgeFill::Values(vec![f64::NAN]))
+        .fallback_vrt(fixture.vrt.clone());
+    let report = vrt_buffer_with_options(&tiles_dir, &padded_dir, &tile, MARGIN, &options).unwrap();
+    assert_eq!(report.failed(), 0);
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    let first = TILE_SIZE - MARGIN;
+    for row in 0..padded_size {
+        for col in 0..padded_size {
+            assert_eq!(
+                band.data[row * padded_size + col],
+                pixel_value(first + col, first + row),
+                "pixel ({}, {})",
+                col,
+                row
+            );
+        }
+    }
+}
+
+#[test]
+fn crop_restores_the_original_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    let report = crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+    assert_eq!(report.written(), fixture.tiles.len());
+
+    for tile in &fixture.tiles {
+        let cropped = cropped_dir.join(tile.file_name().unwrap());
+        assert_eq!(read_band(&cropped).unwrap(), read_band(tile).unwrap());
+    }
+}
+
+#[test]
+fn crop_reports_the_inferred_margin() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    let report = crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+
+    let center = report
+        .tiles
+        .iter()
+        .find(|tile| tile.input.ends_with("tile_1_1.tif"))
+        .unwrap();
+    let margin = center.trimmed.unwrap();
+    assert_eq!(
+        [margin.left, margin.top, margin.right, margin.bottom],
+        [MARGIN; 4]
+    );
+    // the margin of the upper left tile stops at the edge of the mosaic
+    let corner = report
+        .tiles
+        .iter()
+        .find(|tile| tile.input.ends_with("tile_0_0.tif"))
+        .unwrap();
+    assert_eq!(corner.trimmed.unwrap().left, 0);
+}
+
+#[test]
+fn crop_fills_the_shortfall_of_a_buffered_raster() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    // the padded tiles stand in for originals that the tiles fall short of by the margin
+    let originals_dir = dir.path().join("originals");
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &originals_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let failed_dir = dir.path().join("failed");
+    let report = crop_down_to_size(&originals_dir, &fixture.tiles_dir, &failed_dir).unwrap();
+    assert_eq!(report.failed(), 4);
+
+    let cropped_dir = dir.path().join("cropped");
+    let options = CropOptions::default().fill_shortfall(true);
+    let report =
+        crop_down_to_size_with_options(&originals_dir, &fixture.tiles_dir, &cropped_dir, &options)
+            .unwrap();
+    assert_eq!(report.failed(), 0);
+    let band = read_band(&cropped_dir.join("tile_0_0.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    assert_eq!(band.size, (padded_size, padded_size));
+    assert_eq!(band.data[0], 0.0);
+    assert_eq!(band.data[MARGIN * padded_size + MARGIN], pixel_value(0, 0));
+}
+
+#[test]
+fn crop_fixed_restores_the_original_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    crop_fixed(&padded_dir, &cropped_dir, MARGIN).unwrap();
+
+    for tile in &fixture.tiles {
+        let cropped = cropped_dir.join(tile.file_name().unwrap());
+        assert_eq!(read_band(&cropped).unwrap(), read_band(tile).unwrap());
+    }
+}
+
+#[test]
+fn pairs_pad_and_crop_to_renamed_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded = |tile: &PathBuf| {
+        let name = tile.file_stem().unwrap().to_str().unwrap();
+        dir.path().join(format!("padded/{}/buffered.tif", name))
+    };
+    let cropped = |tile: &PathBuf| dir.path().join("cropped").join(tile.file_name().unwrap());
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    let pairs = fixture
+        .tiles
+        .iter()
+        .map(|tile| (tile.clone(), padded(tile)));
+    let report = pad_pairs(pairs, &fixture.vrt, MARGIN, &options).unwrap();
+    assert_eq!(report.written(), 4);
+
+    let pairs = fixture
+        .tiles
+        .iter()
+        .map(|tile| (padded(tile), cropped(tile)));
+    let report = crop_pairs(pairs, MARGIN, &CropOptions::default()).unwrap();
+    assert_eq!(report.written(), 4);
+    for tile in &fixture.tiles {
+        assert_eq!(read_band(&cropped(tile)).unwrap(), read_band(tile).unwrap());
+    }
+
+    // the pairs have no output directory to resume in
+    let pairs = fixture
+        .tiles
+        .iter()
+        .map(|tile| (tile.clone(), padded(tile)));
+    let options = options.output(OutputOptions::default().resume(true));
+    assert!(pad_pairs(pairs, &fixture.vrt, MARGIN, &options).is_err());
+}
+
+#[test]
+fn round_trip_in_memory() {
+    let dir = Path::new("/vsimem/vrt_buffer_round_trip");
+    let fixture = write_mosaic(dir, 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.join("padded");
+    let cropped_dir = dir.join("cropped");
+    let report = vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    assert_eq!(report.failed(), 0);
+    assert!(report.tiles.iter().all(|tile| tile.bytes_written > 0));
+    crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+
+    for tile in &fixture.tiles {
+        let cropped = cropped_dir.join(tile.file_name().unwrap());
+        assert_eq!(read_band(&cropped).unwrap(), read_band(tile).unwrap());
+    }
+    // the files of a whole run need a directory on disk
+    let options = PadOptions::default().output(OutputOptions::default().resume(true));
+    assert!(vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &dir.join("resumed"),
+        &fixture.vrt,
+        MARGIN,
+        &options
+    )
+    .is_err());
+}
+
+#[test]
+fn chunked_crops_restore_the_original_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    let options = CropOptions::default()
+        .chunk_rows(3)
+        .output(OutputOptions::default().format(OutputFormat::Cog));
+    crop_down_to_size_with_options(&fixture.tiles_dir, &padded_dir, &cropped_dir, &options)
+        .unwrap();
+
+    for tile in &fixture.tiles {
+        let cropped = cropped_dir.join(tile.file_name().unwrap());
+        assert_eq!(read_band(&cropped).unwrap(), read_band(tile).unwrap());
+    }
+    // the staging geotiffs are removed
+    let staged = std::fs::read_dir(&cropped_dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .to_string_lossy()
+                .ends_with(".staging.tif")
+        })
+        .count();
+    assert_eq!(staged, 0);
+}
+
+#[test]
+fn diff_compares_the_cropped_tiles_with_the_originals() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+
+    let diff = diff_dirs(&fixture.tiles_dir, &cropped_dir, 0.0, 1).unwrap();
+    assert!(diff.is_identical(), "{}", diff);
+    assert_eq!(diff.rasters.len(), 4);
+
+    // the padded tiles are larger than the originals
+    let diff = diff_dirs(&fixture.tiles_dir, &padded_dir, 0.0, 1).unwrap();
+    assert_eq!(diff.incomparable.len(), 4);
+}
+
+#[test]
+fn crop_fixed_rejects_a_margin_the_tiles_were_not_padded_with() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    let report = crop_fixed(&padded_dir, &cropped_dir, MARGIN + 1).unwrap();
+
+    assert_eq!((report.written(), report.failed()), (0, 4));
+}
+
+#[test]
+fn describe_reports_the_padded_grid() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().edge_fill(EdgeFill::Nodata);
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let tile = describe(&fixture.tiles[3]).unwrap();
+    let padded = describe(&padded_dir.join("tile_1_1.tif")).unwrap();
+    assert_eq!(
+        padded.size,
+        (TILE_SIZE + 2 * MARGIN, TILE_SIZE + 2 * MARGIN)
+    );
+    assert_eq!((padded.bands, padded.dtype.as_str()), (1, "Float32"));
+    assert_eq!(padded.crs_wkt, tile.crs_wkt);
+    assert_eq!(
+        padded.geotransform[0],
+        tile.geotransform[0] - MARGIN as f64 * tile.geotransform[1]
+    );
+    assert_eq!(
+        padded.geotransform[3],
+        tile.geotransform[3] - MARGIN as f64 * tile.geotransform[5]
+    );
+}
+
+#[test]
+fn margin_overrides_pad_single_tiles_wider() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let margins = dir.path().join("margins.csv");
+    std::fs::write(&margins, "name,margin\ntile_1_1,8\ntile_0_1.tif,2,6\n").unwrap();
+    let overrides = read_margin_overrides(&margins).unwrap();
+    assert_eq!(overrides["tile_0_1.tif"], Margin::PerAxis { x: 2, y: 6 });
+
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default()
+        .edge_fill(EdgeFill::Nodata)
+        .margin_overrides(overrides.clone());
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.failed(), 0);
+
+    for (name, margin) in [
+        ("tile_0_0.tif", Margin::Uniform(MARGIN)),
+        ("tile_0_1.tif", Margin::PerAxis { x: 2, y: 6 }),
+        ("tile_1_1.tif", Margin::Uniform(8)),
+    ] {
+        let output = padded_dir.join(name);
+        let tile = report
+            .tiles
+            .iter()
+            .find(|tile| tile.output == output)
+            .unwrap();
+        assert_eq!(tile.margin, Some(margin));
+        assert_eq!(
+            describe(&output).unwrap().size,
+            (TILE_SIZE + 2 * margin.x(), TILE_SIZE + 2 * margin.y())
+        );
+    }
+
+    // every tile is at the edge of the mosaic, the error log has the extent of its own margin
+    let failed_dir = dir.path().join("failed");
+    let options = PadOptions::default()
+        .margin_policy(MarginPolicy::Error)
+        .margin_overrides(overrides)
+        .output(OutputOptions::default().error_log(true));
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &failed_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.failed(), 4);
+    let log = std::fs::read_to_string(failed_dir.join(ERROR_LOG_FILE_NAME)).unwrap();
+    for line in log.lines() {
+        let error: serde_json::Value = serde_json::from_str(line).unwrap();
+        let input = PathBuf::from(error["input"].as_str().unwrap());
+        let margin = match input.file_name().unwrap().to_str().unwrap() {
+            "tile_0_1.tif" => Margin::PerAxis { x: 2, y: 6 },
+            "tile_1_1.tif" => Margin::Uniform(8),
+            _ => Margin::Uniform(MARGIN),
+        };
+        let extent: Vec<f64> = serde_json::from_value(error["extent"].clone()).unwrap();
+        assert_eq!(
+            (extent[2] - extent[0], extent[3] - extent[1]),
+            (
+                (TILE_SIZE + 2 * margin.x()) as f64,
+                (TILE_SIZE + 2 * margin.y()) as f64
+            )
+        );
+    }
+}
+
+#[test]
+fn pad_rejects_a_margin_larger_than_the_reference() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let e = vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, 3 * TILE_SIZE).unwrap_err();
+
+    let invalid = e.downcast_ref::<InvalidMargin>().unwrap();
+    assert_eq!(invalid.maximum, (2 * TILE_SIZE, 2 * TILE_SIZE));
+    assert!(!padded_dir.exists());
+}
+
+#[test]
+fn fail_fast_skips_the_tiles_after_the_first_failure() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let cropped_dir = dir.path().join("cropped");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    let options = CropOptions::default().error_policy(ErrorPolicy::FailFast);
+    let report = crop_fixed_with_options(&padded_dir, &cropped_dir, MARGIN + 1, &options).unwrap();
+
+    assert_eq!((report.failed(), report.skipped()), (1, 3));
+    assert!(report.aborted());
+}
+
+#[test]
+fn pad_applies_the_calc_expression() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().calc("(A - 1) * 0.5".parse().unwrap());
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let first = TILE_SIZE - MARGIN;
+    assert_eq!(band.data[0], (pixel_value(first, first) - 1.0) * 0.5);
+}
+
+#[test]
+fn pad_writes_only_the_selected_bands() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().output(OutputOptions::default().bands(vec![1]));
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.failed(), 0);
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let first = TILE_SIZE - MARGIN;
+    assert_eq!(band.data[0], pixel_value(first, first));
+    assert_eq!(describe(&padded_dir.join("tile_1_1.tif")).unwrap().bands, 1);
+
+    // the tiles have a single band
+    let missing_dir = dir.path().join("missing");
+    let options = PadOptions::default().output(OutputOptions::default().bands(vec![2]));
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &missing_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!((report.written(), report.failed()), (0, 4));
+}
+
+#[test]
+fn pad_only_pads_the_listed_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().file_list(vec![fixture.tiles_dir.join("tile_1_1.tif")]);
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(report.tiles.len(), 1);
+    assert_eq!(report.written(), 1);
+    assert!(padded_dir.join("tile_1_1.tif").exists());
+}
+
+#[test]
+fn the_event_channel_sees_every_tile_start_and_finish() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let (sender, events) = mpsc::channel();
+    let options = PadOptions::default()
+        .jobs(2)
+        .listener(TileListener::channel(sender));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    drop(options);
+
+    let events: Vec<TileEvent> = events.iter().collect();
+    let started = events
+        .iter()
+        .filter(|event| matches!(event, TileEvent::TileStarted { .. }))
+        .count();
+    let finished = events
+        .iter()
+        .filter(|event| matches!(event, TileEvent::TileFinished(_)))
+        .count();
+    assert_eq!((started, finished), (4, 4));
+}
+
+#[test]
+fn pad_refuses_an_output_dir_inside_the_input_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let nested = fixture.tiles_dir.join("padded");
+    assert!(vrt_buffer(&fixture.tiles_dir, &fixture.tiles_dir, &fixture.vrt, MARGIN).is_err());
+    assert!(vrt_buffer(&fixture.tiles_dir, &nested, &fixture.vrt, MARGIN).is_err());
+    assert!(!nested.exists());
+}
+
+#[test]
+fn pad_only_pads_the_tiles_in_the_aoi() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default()
+        .aoi("20,-28,28,-20".parse().unwrap())
+        .index(true);
+    let report =
+        neighbor_buffer_with_options(&fixture.tiles_dir, &padded_dir, MARGIN, &options).unwrap();
+    assert_eq!(report.tiles.len(), 1);
+    assert!(fixture.tiles_dir.join(INDEX_FILE_NAME).exists());
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let first = TILE_SIZE - MARGIN;
+    assert_eq!(band.data[0], pixel_value(first, first));
+}
+
+#[test]
+fn pad_writes_virtual_tiles_that_read_the_mosaic() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options =
+        PadOptions::default().output(OutputOptions::default().format(OutputFormat::VirtualVrt));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let band = read_band(&padded_dir.join("tile_1_1.vrt")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    assert_eq!(band.size, (padded_size, padded_size));
+    let first = TILE_SIZE - MARGIN;
+    assert_eq!(band.data[0], pixel_value(first, first));
+    assert_eq!(
+        band.data[padded_size * padded_size - 1],
+        pixel_value(first + padded_size - 1, first + padded_size - 1)
+    );
+}
+
+#[test]
+fn metadata_only_pads_write_stubs_with_the_footprints_of_the_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    // the stubs do not read from a reference, so a neighbor run can write them too
+    let stub_dir = dir.path().join("stubs");
+    let options = PadOptions::default()
+        .metadata_only(true)
+        .output(OutputOptions::default().format(OutputFormat::VirtualVrt));
+    let report =
+        neighbor_buffer_with_options(&fixture.tiles_dir, &stub_dir, MARGIN, &options).unwrap();
+    assert_eq!(report.failed(), 0);
+
+    for name in ["tile_0_0", "tile_1_1", "tile_2_1"] {
+        let padded = describe(&padded_dir.join(format!("{}.tif", name))).unwrap();
+        let stub_path = stub_dir.join(format!("{}.vrt", name));
+        let stub = describe(&stub_path).unwrap();
+        assert_eq!(stub.size, padded.size);
+        assert_eq!(stub.geotransform, padded.geotransform);
+        assert_eq!(stub.bands, padded.bands);
+        assert!(!std::fs::read_to_string(&stub_path)
+            .unwrap()
+            .contains("SimpleSource"));
+    }
+
+    // the stubs are vrts whatever the format
+    let options = PadOptions::default().metadata_only(true);
+    let other_dir = dir.path().join("other");
+    assert!(vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &other_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options
+    )
+    .is_err());
+}
+
+#[test]
+fn pad_copies_the_non_raster_files_of_the_input_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    std::fs::write(fixture.tiles_dir.join("metadata.xml"), "<metadata/>").unwrap();
+    std::fs::write(fixture.tiles_dir.join("report.txt"), "flown 2024").unwrap();
+    std::fs::write(fixture.tiles_dir.join("tile_0_0.prj"), "").unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options =
+        PadOptions::default().output(OutputOptions::default().copy_non_raster_files(true));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(padded_dir.join("report.txt")).unwrap(),
+        "flown 2024"
+    );
+    assert!(padded_dir.join("metadata.xml").is_file());
+    // the sidecars of the tiles describe the unpadded tiles
+    assert!(!padded_dir.join("tile_0_0.prj").exists());
+}
+
+#[test]
+fn pad_writes_the_provenance_of_every_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().output(OutputOptions::default().provenance(true));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let text = std::fs::read_to_string(padded_dir.join("tile_0_1.provenance.json")).unwrap();
+    let provenance: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(
+        provenance["inputs"][0]["path"],
+        fixture.tiles[1].display().to_string()
+    );
+    assert_eq!(
+        provenance["inputs"][0]["sha256"].as_str().unwrap().len(),
+        64
+    );
+    assert_eq!(
+        provenance["reference"]["path"],
+        fixture.vrt.display().to_string()
+    );
+    assert_eq!(provenance["margin"], MARGIN);
+    assert_eq!(provenance["crate_version"], env!("CARGO_PKG_VERSION"));
+    assert!(provenance["seconds"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn pad_writes_a_zarr_store_with_one_chunk_per_tile() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().output(OutputOptions::default().format(OutputFormat::Zarr));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+
+    let store = padded_dir.join("tile_0_0.zarr");
+    assert!(store.join(".zmetadata").is_file());
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    let description = describe(&store).unwrap();
+    assert_eq!(description.size, (padded_size, padded_size));
+    assert_eq!(description.block_size, (padded_size, padded_size));
+    let band = read_band(&store).unwrap();
+    assert_eq!(band.data[MARGIN * padded_size + MARGIN], pixel_value(0, 0));
+}
+
+#[test]
+fn pad_pyramid_writes_a_decimated_level() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let report = vrt_buffer_pyramid(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &[1, 2],
+        &PadOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(report.written(), 18);
+    assert!(report
+        .tiles
+        .iter()
+        .all(|tile| tile.input.starts_with(&fixture.tiles_dir)));
+
+    let full = read_band(&padded_dir.join("level_1").join("tile_1_1.tif")).unwrap();
+    assert_eq!(full.size, (TILE_SIZE + 2 * MARGIN, TILE_SIZE + 2 * MARGIN));
+    let half = read_band(&padded_dir.join("level_2").join("tile_1_1.tif")).unwrap();
+    assert_eq!(
+        half.size,
+        (TILE_SIZE / 2 + 2 * MARGIN, TILE_SIZE / 2 + 2 * MARGIN)
+    );
+    let first = (TILE_SIZE - 2 * MARGIN) as f64;
+    assert_eq!(half.origin, (first, -first));
+}
+
+#[test]
+fn read_vrt_window_reads_an_extent_across_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 3, 3, TILE_SIZE).unwrap();
+    // columns 12 to 20 and rows 10 to 18, across the tiles of the first two rows and columns
+    let extent = GeoExtent::new(12.0, -18.0, 20.0, -10.0);
+    let bands = read_vrt_window::<f32>(&fixture.vrt, extent, (8, 8)).unwrap();
+    assert_eq!(bands[0].size, (8, 8));
+    for row in 0..8 {
+        for col in 0..8 {
+            assert_eq!(
+                bands[0].data[row * 8 + col],
+                pixel_value(12 + col, 10 + row)
+            );
+        }
+    }
+}
+
+#[test]
+fn pad_writes_a_manifest_that_verifies() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().output(OutputOptions::default().manifest(true));
+    vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    let check = verify_manifest(&padded_dir, 1).unwrap();
+    assert_eq!(check.verified, 4);
+    assert!(check.is_ok());
+
+    std::fs::write(padded_dir.join("tile_0_0.tif"), b"changed").unwrap();
+    let check = verify_manifest(&padded_dir, 1).unwrap();
+    assert_eq!(
+        check.mismatched,
+        vec![std::path::PathBuf::from("tile_0_0.tif")]
+    );
+}
+
+#[test]
+fn pad_appends_the_outputs_to_an_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let archive = dir.path().join("padded.tar");
+    let options = PadOptions::default().output(OutputOptions::default().archive(archive.clone()));
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.written(), 4);
+    assert_eq!(std::fs::read_dir(&padded_dir).unwrap().count(), 0);
+
+    let mut names: Vec<String> = tar::Archive::new(std::fs::File::open(&archive).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "tile_0_0.tif",
+            "tile_0_1.tif",
+            "tile_1_0.tif",
+            "tile_1_1.tif"
+        ]
+    );
+}
+
+#[test]
+fn resumed_runs_with_a_tile_limit_process_the_next_tiles() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default()
+        .max_tiles(2)
+        .output(OutputOptions::default().resume(true));
+    let pad = || {
+        vrt_buffer_with_options(
+            &fixture.tiles_dir,
+            &padded_dir,
+            &fixture.vrt,
+            MARGIN,
+            &options,
+        )
+        .unwrap()
+    };
+    let first = pad();
+    assert_eq!((first.written(), first.skipped()), (2, 2));
+    let second = pad();
+    assert_eq!((second.written(), second.skipped()), (2, 2));
+    assert!(padded_dir.join("tile_1_1.tif").is_file());
+}
+
+#[test]
+fn tile_ids_name_the_outputs_and_pair_the_originals() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    std::fs::copy(
+        fixture.tiles_dir.join("tile_0_0.tif"),
+        fixture.tiles_dir.join("tile_0_0_copy.tif"),
+    )
+    .unwrap();
+    let padded_dir = dir.path().join("padded");
+    let tile_id: TileIdPattern = r"^tile_(\d+_\d+)".parse().unwrap();
+    let output = OutputOptions::default().tile_id(tile_id);
+    let options = PadOptions::default().output(output.clone());
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!((report.written(), report.failed()), (4, 1));
+    assert!(padded_dir.join("0_0.tif").is_file());
+
+    let cropped_dir = dir.path().join("cropped");
+    let options = CropOptions::default().output(output);
+    let report =
+        crop_down_to_size_with_options(&fixture.tiles_dir, &padded_dir, &cropped_dir, &options)
+            .unwrap();
+    assert_eq!(report.written(), 4);
+    assert_eq!(
+        read_band(&cropped_dir.join("1_1.tif")).unwrap(),
+        read_band(&fixture.tiles_dir.join("tile_1_1.tif")).unwrap()
+    );
+}
+
+#[test]
+fn f64_tiles_round_trip_bit_exact() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic_f64(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let report = vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    assert_eq!(report.failed(), 0);
+
+    // the right margin of the upper left tile is read from the tile to its right
+    let padded = read_band_f64(&padded_dir.join("tile_0_0.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    let (col, row) = (TILE_SIZE + 2 * MARGIN - 1, MARGIN);
+    assert_eq!(
+        padded[row * padded_size + col].to_bits(),
+        precise_pixel_value(col - MARGIN, row - MARGIN).to_bits()
+    );
+
+    let cropped_dir = dir.path().join("cropped");
+    crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+    for tile in &fixture.tiles {
+        let cropped = read_band_f64(&cropped_dir.join(tile.file_name().unwrap())).unwrap();
+        let original = read_band_f64(tile).unwrap();
+        let bits = |data: &[f64]| data.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&cropped), bits(&original));
+    }
+}
+
+#[test]
+fn f64_tiles_keep_their_precision_through_calc() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic_f64(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let options = PadOptions::default().calc("A * 2".parse().unwrap());
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.failed(), 0);
+
+    // doubling is exact in f64, a f32 output would round the value
+    let padded = read_band_f64(&padded_dir.join("tile_0_0.tif")).unwrap();
+    let padded_size = TILE_SIZE + 2 * MARGIN;
+    let (col, row) = (TILE_SIZE + 2 * MARGIN - 1, MARGIN);
+    let expected = precise_pixel_value(col - MARGIN, row - MARGIN) * 2.0;
+    assert_ne!(expected as f32 as f64, expected);
+    assert_eq!(
+        padded[row * padded_size + col].to_bits(),
+        expected.to_bits()
+    );
+}
+
+#[test]
+fn grid_pads_synthetic_tiles_of_the_mosaic() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    let tile_size = 12;
+    let report = vrt_buffer_grid(
+        &fixture.vrt,
+        &padded_dir,
+        tile_size,
+        MARGIN,
+        &PadOptions::default(),
+    )
+    .unwrap();
+    // 32 pixels split into 12, 12 and 8
+    assert_eq!(report.written(), 9);
+
+    let band = read_band(&padded_dir.join("tile_1_1.tif")).unwrap();
+    let padded_size = tile_size + 2 * MARGIN;
+    assert_eq!(band.size, (padded_size, padded_size));
+    let first = tile_size - MARGIN;
+    for row in 0..padded_size {
+        for col in 0..padded_size {
+            assert_eq!(
+                band.data[row * padded_size + col],
+                pixel_value(first + col, first + row)
+            );
+        }
+    }
+}
+
+#[test]
+fn mosaic_merges_the_cropped_tiles_and_rejects_overlaps() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    vrt_buffer(&fixture.tiles_dir, &padded_dir, &fixture.vrt, MARGIN).unwrap();
+    let cropped_dir = dir.path().join("cropped");
+    crop_down_to_size(&fixture.tiles_dir, &padded_dir, &cropped_dir).unwrap();
+
+    let merged = dir.path().join("merged.tif");
+    let check = mosaic_tiles(&cropped_dir, &merged, &MosaicOptions::default()).unwrap();
+    assert!(check.is_seamless());
+    let band = read_band(&merged).unwrap();
+    assert_eq!(band.size, (2 * TILE_SIZE, 2 * TILE_SIZE));
+    assert_eq!(band.data[2 * TILE_SIZE + 20], pixel_value(20, 1));
+
+    // the padded tiles overlap by their margins
+    let rejected = dir.path().join("rejected.tif");
+    assert!(mosaic_tiles(&padded_dir, &rejected, &MosaicOptions::default()).is_err());
+    assert!(!rejected.exists());
+}
+
+#[test]
+fn an_opened_reference_pads_several_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let reference = ReferenceMosaic::open(&fixture.vrt).unwrap();
+    let description = describe(&fixture.vrt).unwrap();
+    assert_eq!(reference.size(), (2 * TILE_SIZE, 2 * TILE_SIZE));
+    assert_eq!(reference.geo_transform(), &description.geotransform);
+    assert_eq!(reference.band_count(), description.bands);
+    assert_eq!(reference.dtype(), description.dtype);
+    assert_eq!(reference.nodata(1), description.nodata);
+
+    for name in ["first", "second"] {
+        let padded_dir = dir.path().join(name);
+        let report = vrt_buffer_with_reference(
+            &fixture.tiles_dir,
+            &padded_dir,
+            &reference,
+            MARGIN,
+            &PadOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(report.failed(), 0);
+        // the margin of the upper left tile stops at the edge of the mosaic
+        let band = read_band(&padded_dir.join("tile_0_0.tif")).unwrap();
+        assert_eq!(band.size, (TILE_SIZE + MARGIN, TILE_SIZE + MARGIN));
+        assert_eq!(band.data[TILE_SIZE], pixel_value(TILE_SIZE, 0));
+    }
+}
+
+#[test]
+fn duplicate_names_rename_the_outputs_of_tables_of_the_same_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 1, TILE_SIZE).unwrap();
+    let input_dir = dir.path().join("geopackages");
+    std::fs::create_dir(&input_dir).unwrap();
+    let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+    for (tile, name) in fixture.tiles.iter().zip(["north", "south"]) {
+        let table = RasterCreationOption {
+            key: "RASTER_TABLE",
+            value: "dem",
+        };
+        let path = input_dir.join(format!("{}.gpkg", name));
+        Dataset::open(tile)
+            .unwrap()
+            .create_copy(&driver, path, &[table])
+            .unwrap();
+    }
+
+    let pad = |name: &str, duplicate_names: DuplicateNames| {
+        let padded_dir = dir.path().join(name);
+        let output = OutputOptions::default().duplicate_names(duplicate_names);
+        let report = vrt_buffer_with_options(
+            &input_dir,
+            &padded_dir,
+            &fixture.vrt,
+            MARGIN,
+            &PadOptions::default().output(output),
+        )
+        .unwrap();
+        (padded_dir, report.failed())
+    };
+    // both tables would be written to dem.tif
+    let (_, failed) = pad("error", DuplicateNames::Error);
+    assert_eq!(failed, 1);
+    let (padded_dir, failed) = pad("prefixed", DuplicateNames::PrefixParent);
+    assert_eq!(failed, 0);
+    assert!(padded_dir.join("north_dem.tif").is_file());
+    assert!(padded_dir.join("south_dem.tif").is_file());
+    let (padded_dir, failed) = pad("mirrored", DuplicateNames::Mirror);
+    assert_eq!(failed, 0);
+    assert!(padded_dir.join("north").join("dem.tif").is_file());
+    assert!(padded_dir.join("south").join("dem.tif").is_file());
+}
+
+#[test]
+fn tiles_past_their_timeout_fail_and_the_batch_goes_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let fixture = write_mosaic(dir.path(), 2, 2, TILE_SIZE).unwrap();
+    let padded_dir = dir.path().join("padded");
+    // every tile is past a timeout of 0 before it reads anything
+    let options = PadOptions::default().tile_timeout(Duration::ZERO);
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.failed(), fixture.tiles.len());
+    let timed_out = TimedOut {
+        timeout: Duration::ZERO,
+    };
+    for tile in &report.tiles {
+        assert_eq!(tile.status, TileStatus::Failed(timed_out.to_string()));
+    }
+
+    let options = PadOptions::default().tile_timeout(Duration::from_secs(600));
+    let report = vrt_buffer_with_options(
+        &fixture.tiles_dir,
+        &padded_dir,
+        &fixture.vrt,
+        MARGIN,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(report.written(), fixture.tiles.len());
+}